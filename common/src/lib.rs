@@ -0,0 +1,11 @@
+#![no_std]
+
+#[cfg(test)]
+extern crate std;
+extern crate alloc;
+
+pub mod rng;
+pub mod tree;
+
+pub use rng::XorShift;
+pub use tree::{drop_tree, BoxedNode};
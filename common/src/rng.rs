@@ -0,0 +1,35 @@
+// A small xorshift* generator, for the structures that draw random shapes,
+// such as the heights of skip list nodes or the priorities of a treap. It
+// needs to be fast rather than strong, and being seedable makes the shape
+// of a structure, and so its performance, reproducible from run to run.
+#[derive(Debug, Clone)]
+pub struct XorShift(u64);
+
+impl XorShift {
+    // Zero is a fixed point of the xorshift step, so it is replaced.
+    pub fn new(seed: u64) -> Self {
+        XorShift(if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed })
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::XorShift;
+
+    #[test]
+    fn zero_seed() {
+        let mut rng = XorShift::new(0);
+        assert!((0..100).all(|_| rng.next_u64() != 0));
+        let (mut a, mut b) = (XorShift::new(7), XorShift::new(7));
+        assert!((0..100).all(|_| a.next_u64() == b.next_u64()));
+    }
+}
@@ -0,0 +1,44 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+// A node of a binary tree whose links are Boxes.
+pub trait BoxedNode: Sized {
+    // Detaches both children, leaving the node a leaf.
+    fn take_children(&mut self) -> (Option<Box<Self>>, Option<Box<Self>>);
+}
+
+// Frees a tree from a worklist rather than recursively, as the default
+// Drop would, so that a tree as deep as it is long, such as the left spine
+// of a leftist heap or an unlucky treap, cannot overflow the stack.
+pub fn drop_tree<N: BoxedNode>(root: Option<Box<N>>) {
+    let mut stack: Vec<Box<N>> = root.into_iter().collect();
+    while let Some(mut node) = stack.pop() {
+        let (left, right) = node.take_children();
+        stack.extend(left);
+        stack.extend(right);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::boxed::Box;
+
+    use super::{drop_tree, BoxedNode};
+
+    struct Node(Option<Box<Node>>);
+
+    impl BoxedNode for Node {
+        fn take_children(&mut self) -> (Option<Box<Node>>, Option<Box<Node>>) {
+            (self.0.take(), None)
+        }
+    }
+
+    #[test]
+    fn deep_tree() {
+        let mut root = None;
+        for _ in 0..1_000_000 {
+            root = Some(Box::new(Node(root)));
+        }
+        drop_tree(root);
+    }
+}
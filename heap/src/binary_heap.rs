@@ -0,0 +1,45 @@
+use dary_heap::DaryHeap;
+use {Max, Min};
+
+// A priority queue laid out as an implicit binary tree in a single Vec:
+// the children of the element at i are at 2i + 1 and 2i + 2. There are no
+// links to follow, so it is compact and cache friendly, but two heaps
+// cannot be melded faster than by pushing one into the other. O decides
+// whether the least or the greatest element is on top.
+pub type Heap<T, O> = DaryHeap<T, 2, O>;
+
+pub type MinHeap<T> = Heap<T, Min>;
+pub type MaxHeap<T> = Heap<T, Max>;
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::{MaxHeap, MinHeap};
+
+    #[test]
+    fn both_orders() {
+        let elems: Vec<u32> = (0..500).map(|i| i * 7919 % 1000).collect();
+        let mut sorted = elems.clone();
+        sorted.sort();
+
+        let mut min = MinHeap::new();
+        min.extend(elems.iter().cloned());
+        assert_eq!(min.peek(), Some(&sorted[0]));
+        let mut popped = Vec::new();
+        while let Some(x) = min.pop() {
+            popped.push(x);
+        }
+        assert_eq!(popped, sorted);
+        assert_eq!(min.pop(), None);
+
+        let max = MaxHeap::from_vec(elems.clone());
+        assert_eq!(max.peek(), sorted.last());
+        assert_eq!(max.len(), 500);
+        let mut descending = sorted.clone();
+        descending.reverse();
+        assert_eq!(max.into_sorted_vec(), descending);
+        assert_eq!(elems.into_iter().collect::<MinHeap<_>>().into_sorted_vec(), sorted);
+        assert!(MinHeap::<u32>::default().into_sorted_vec().is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+use core::iter;
+
+use alloc::vec::Vec;
+
+// A binomial tree of rank r is a root whose children are binomial trees of
+// ranks 0 through r - 1, in that order, so it holds 2^r elements.
+#[derive(Debug, Clone)]
+struct Tree<T> {
+    elem: T,
+    children: Vec<Tree<T>>,
+}
+
+impl<T: Ord> Tree<T> {
+    // Links two trees of equal rank into one of the next rank.
+    fn link(self, other: Tree<T>) -> Tree<T> {
+        let (mut top, below) = if other.elem < self.elem { (other, self) } else { (self, other) };
+        top.children.push(below);
+        top
+    }
+}
+
+// A min-heap as a list of binomial trees, each no greater at its root than
+// at its children, with at most one tree of each rank, like the digits of
+// the length in binary. Melding two heaps adds them like binary numbers,
+// linking two trees of a rank into a carry of the next, so meld, push and
+// pop_min all take O(log n) time.
+#[derive(Debug, Clone)]
+pub struct BinomialHeap<T> {
+    // The tree of each rank, if any, with no None at the end.
+    trees: Vec<Option<Tree<T>>>,
+    len: usize,
+}
+
+impl<T: Ord> BinomialHeap<T> {
+    pub fn new() -> Self {
+        BinomialHeap { trees: Vec::new(), len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.trees.clear();
+        self.len = 0;
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        self.trees.iter().flatten().map(|tree| &tree.elem).min()
+    }
+
+    pub fn push(&mut self, elem: T) {
+        self.add(iter::once(Some(Tree { elem, children: Vec::new() })));
+        self.len += 1;
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        let rank = {
+            let mut ranks = self.trees.iter().enumerate().filter_map(|(rank, tree)| tree.as_ref().map(|tree| (rank, tree)));
+            let first = ranks.next()?;
+            ranks.fold(first, |min, tree| if tree.1.elem < min.1.elem { tree } else { min }).0
+        };
+        let tree = self.trees[rank].take().unwrap();
+        while let Some(&None) = self.trees.last() {
+            self.trees.pop();
+        }
+        self.len -= 1;
+        // The children of the root are a heap of their own.
+        self.add(tree.children.into_iter().map(Some));
+        Some(tree.elem)
+    }
+
+    pub fn meld(&mut self, other: BinomialHeap<T>) {
+        self.add(other.trees);
+        self.len += other.len;
+    }
+
+    // Adds in trees indexed by rank, carrying from each rank to the next.
+    fn add<I: IntoIterator<Item = Option<Tree<T>>>>(&mut self, others: I) {
+        let mut others = others.into_iter();
+        let mut carry = None;
+        let mut rank = 0;
+        loop {
+            let other = match others.next() {
+                Some(other) => other,
+                None if carry.is_none() => break,
+                None => None,
+            };
+            if rank == self.trees.len() {
+                self.trees.push(None);
+            }
+            let slot = &mut self.trees[rank];
+            match (slot.take(), other, carry.take()) {
+                (Some(a), Some(b), c) | (Some(a), c, Some(b)) | (c, Some(a), Some(b)) => {
+                    *slot = c;
+                    carry = Some(a.link(b));
+                },
+                (a, b, c) => *slot = a.or(b).or(c),
+            }
+            rank += 1;
+        }
+    }
+}
+
+impl<T: Ord> Default for BinomialHeap<T> {
+    fn default() -> Self {
+        BinomialHeap::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::BinomialHeap;
+
+    #[test]
+    fn melding() {
+        let mut evens = BinomialHeap::new();
+        let mut odds = BinomialHeap::new();
+        for i in (0..1000u32).rev() {
+            if i % 2 == 0 { evens.push(i) } else { odds.push(i) }
+        }
+        assert_eq!(evens.trees.len(), 9);
+        assert_eq!(evens.pop_min(), Some(0));
+        assert_eq!(odds.peek_min(), Some(&1));
+        evens.meld(odds);
+        assert_eq!(evens.len(), 999);
+        assert_eq!(evens.trees.len(), 10);
+        let mut popped = Vec::new();
+        while let Some(x) = evens.pop_min() {
+            popped.push(x);
+        }
+        assert_eq!(popped, (1..1000).collect::<Vec<_>>());
+        assert!(evens.trees.is_empty());
+    }
+}
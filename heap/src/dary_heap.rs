@@ -0,0 +1,198 @@
+use core::iter::FromIterator;
+use core::marker::PhantomData;
+use core::slice;
+
+use alloc::vec::Vec;
+
+use {Min, Order};
+
+// A heap in a single Vec like binary_heap::Heap, which is the case D = 2,
+// but each element has D children, at D*i + 1 through D*i + D. A wider
+// tree is shallower, which makes push cheaper,
+// since sifting up compares once per level, at the price of pop, which
+// compares against all D children per level. Arities of 4 or 8 suit
+// push-heavy workloads such as Dijkstra's algorithm. D is a constant, so
+// the loops over children are specialized for each arity.
+#[derive(Debug, Clone)]
+pub struct DaryHeap<T, const D: usize, O = Min> {
+    elems: Vec<T>,
+    order: PhantomData<O>,
+}
+
+impl<T: Ord, const D: usize, O: Order> DaryHeap<T, D, O> {
+    // Evaluated wherever a heap of some arity is built, so that an arity
+    // below 2 fails to compile instead of panicking at run time.
+    const ARITY_CHECK: () = assert!(D >= 2, "a d-ary heap needs an arity of at least 2");
+
+    pub fn new() -> Self {
+        DaryHeap::from_vec(Vec::new())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        DaryHeap::from_vec(Vec::with_capacity(capacity))
+    }
+
+    // Arranges the elements into a heap in place, in linear time, by
+    // sifting down every element that has children, from the last up.
+    pub fn from_vec(elems: Vec<T>) -> Self {
+        let () = Self::ARITY_CHECK;
+        let mut heap = DaryHeap { elems, order: PhantomData };
+        let len = heap.elems.len();
+        if len > 1 {
+            for i in (0..=(len - 2) / D).rev() {
+                heap.sift_down(i, len);
+            }
+        }
+        heap
+    }
+
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.elems.clear();
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.elems.first()
+    }
+
+    pub fn push(&mut self, elem: T) {
+        self.elems.push(elem);
+        let last = self.elems.len() - 1;
+        self.sift_up(last);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let last = self.elems.len().checked_sub(1)?;
+        self.elems.swap(0, last);
+        let top = self.elems.pop();
+        self.sift_down(0, last);
+        top
+    }
+
+    // Iterates in no particular order.
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.elems.iter()
+    }
+
+    // Gives up the elements in no particular order.
+    pub fn into_vec(self) -> Vec<T> {
+        self.elems
+    }
+
+    // Gives up the elements in the order that pop would return them,
+    // sorting in place.
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        // Moving the top to the end of the shrinking heap each time leaves
+        // the elements in the reverse of the order they were popped in.
+        for end in (1..self.elems.len()).rev() {
+            self.elems.swap(0, end);
+            self.sift_down(0, end);
+        }
+        self.elems.reverse();
+        self.elems
+    }
+
+    fn sift_up(&mut self, mut i: usize) {
+        while i > 0 {
+            let parent = (i - 1) / D;
+            if !O::above(&self.elems[i], &self.elems[parent]) {
+                break;
+            }
+            self.elems.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    // Sifts down within the first len elements.
+    fn sift_down(&mut self, mut i: usize, len: usize) {
+        loop {
+            let first = D * i + 1;
+            if first >= len {
+                break;
+            }
+            let mut best = first;
+            for child in first + 1..len.min(first + D) {
+                if O::above(&self.elems[child], &self.elems[best]) {
+                    best = child;
+                }
+            }
+            if !O::above(&self.elems[best], &self.elems[i]) {
+                break;
+            }
+            self.elems.swap(i, best);
+            i = best;
+        }
+    }
+}
+
+impl<T: Ord, const D: usize, O: Order> Default for DaryHeap<T, D, O> {
+    fn default() -> Self {
+        DaryHeap::new()
+    }
+}
+
+impl<T: Ord, const D: usize, O: Order> From<Vec<T>> for DaryHeap<T, D, O> {
+    fn from(elems: Vec<T>) -> Self {
+        DaryHeap::from_vec(elems)
+    }
+}
+
+impl<T: Ord, const D: usize, O: Order> FromIterator<T> for DaryHeap<T, D, O> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        DaryHeap::from_vec(iter.into_iter().collect())
+    }
+}
+
+impl<T: Ord, const D: usize, O: Order> Extend<T> for DaryHeap<T, D, O> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::DaryHeap;
+    use Max;
+
+    fn drain<const D: usize>(mut heap: DaryHeap<u32, D>) -> Vec<u32> {
+        let mut popped = Vec::new();
+        while let Some(x) = heap.pop() {
+            popped.push(x);
+        }
+        popped
+    }
+
+    #[test]
+    fn arities() {
+        let elems: Vec<u32> = (0..1000).map(|i| i * 7919 % 1000).collect();
+        let mut sorted = elems.clone();
+        sorted.sort();
+        for len in 0..20 {
+            let heap: DaryHeap<u32, 3> = DaryHeap::from_vec(elems[..len].to_vec());
+            let mut expected = elems[..len].to_vec();
+            expected.sort();
+            assert_eq!(heap.into_sorted_vec(), expected);
+        }
+        assert_eq!(drain::<2>(elems.iter().cloned().collect()), sorted);
+        assert_eq!(drain::<4>(elems.iter().cloned().collect()), sorted);
+        let mut heap: DaryHeap<u32, 8> = DaryHeap::new();
+        heap.extend(elems.iter().cloned());
+        assert_eq!(heap.peek(), Some(&0));
+        assert_eq!(drain(heap), sorted);
+
+        let max: DaryHeap<u32, 4, Max> = DaryHeap::from_vec(elems);
+        sorted.reverse();
+        assert_eq!(max.into_sorted_vec(), sorted);
+    }
+}
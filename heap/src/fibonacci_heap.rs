@@ -0,0 +1,351 @@
+use core::fmt;
+use core::mem;
+use core::ptr::NonNull;
+
+use alloc::vec::Vec;
+
+use pool::{self, Pool, Token};
+
+// A reference to an element of a FibonacciHeap, which follows the element
+// into any heap that its heap is melded into. Once the element is popped,
+// the handle names nothing, and is refused by every heap.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Handle(Token);
+
+type Ptr<T> = pool::Ptr<Node<T>>;
+
+struct Node<T> {
+    elem: T,
+    parent: Option<Ptr<T>>,
+    // Any one of the children, which form a circular list through left and
+    // right, as the roots do.
+    child: Option<Ptr<T>>,
+    left: Ptr<T>,
+    right: Ptr<T>,
+    degree: usize,
+    // Whether the node has lost a child since it last became a child.
+    mark: bool,
+}
+
+// A min-heap as a list of trees in which every node is no greater than its
+// children. Push and meld only add to the list of roots, and decrease_key
+// cuts the node out to become a root, cutting in turn any parent that has
+// now lost two children so that a tree of degree d keeps a size
+// exponential in d. Pop_min then consolidates the roots by linking those
+// of equal degree until all degrees differ. Pop takes O(log n) amortized
+// time and the others O(1), but for following a handle to its heap after
+// melds, which is logarithmic in their number. The nodes live in a Pool,
+// as a PairingHeap's do.
+pub struct FibonacciHeap<T> {
+    pool: Pool<Node<T>>,
+    // The least of the roots.
+    min: Option<Ptr<T>>,
+    len: usize,
+}
+
+impl<T: Ord> FibonacciHeap<T> {
+    pub fn new() -> Self {
+        FibonacciHeap { pool: Pool::new(), min: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn node(&self, i: Ptr<T>) -> &Node<T> {
+        self.pool.get(i)
+    }
+
+    fn node_mut(&mut self, i: Ptr<T>) -> &mut Node<T> {
+        self.pool.get_mut(i)
+    }
+
+    // Joins two circular lists, given any node of each, into one.
+    fn splice(&mut self, a: Ptr<T>, b: Ptr<T>) {
+        let a_right = self.node(a).right;
+        let b_left = self.node(b).left;
+        self.node_mut(a).right = b;
+        self.node_mut(b).left = a;
+        self.node_mut(b_left).right = a_right;
+        self.node_mut(a_right).left = b_left;
+    }
+
+    // Takes the node out of its circular list, leaving it in a list of its
+    // own.
+    fn unlink(&mut self, i: Ptr<T>) {
+        let (left, right) = (self.node(i).left, self.node(i).right);
+        self.node_mut(left).right = right;
+        self.node_mut(right).left = left;
+        let node = self.node_mut(i);
+        node.left = i;
+        node.right = i;
+    }
+
+    // Adds a circular list of lone nodes to the roots, given the least of
+    // them.
+    fn add_roots(&mut self, i: Ptr<T>) {
+        match self.min {
+            None => self.min = Some(i),
+            Some(min) => {
+                self.splice(min, i);
+                if self.node(i).elem < self.node(min).elem {
+                    self.min = Some(i);
+                }
+            },
+        }
+    }
+
+    pub fn push(&mut self, elem: T) -> Handle {
+        let i = self.pool.alloc(Node { elem, parent: None, child: None, left: NonNull::dangling(), right: NonNull::dangling(), degree: 0, mark: false });
+        // A lone node is a circular list of its own.
+        let node = self.node_mut(i);
+        node.left = i;
+        node.right = i;
+        self.add_roots(i);
+        self.len += 1;
+        Handle(self.pool.token(i))
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        self.min.map(|min| &self.node(min).elem)
+    }
+
+    pub fn get(&self, handle: &Handle) -> Option<&T> {
+        self.pool.resolve(&handle.0).map(|i| &self.node(i).elem)
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        let min = self.min?;
+        // The children become roots.
+        if let Some(child) = self.node_mut(min).child.take() {
+            let mut i = child;
+            loop {
+                let node = self.node_mut(i);
+                node.parent = None;
+                node.mark = false;
+                i = node.right;
+                if i == child {
+                    break;
+                }
+            }
+            self.splice(min, child);
+        }
+        let next = self.node(min).right;
+        self.unlink(min);
+        let node = self.pool.free(min);
+        self.len -= 1;
+        self.min = if next == min { None } else { Some(next) };
+        self.consolidate();
+        Some(node.elem)
+    }
+
+    // Links roots of equal degree until no two roots share a degree, and
+    // finds the least of them.
+    fn consolidate(&mut self) {
+        let start = match self.min {
+            None => return,
+            Some(start) => start,
+        };
+        let mut roots = Vec::new();
+        let mut i = start;
+        loop {
+            roots.push(i);
+            i = self.node(i).right;
+            if i == start {
+                break;
+            }
+        }
+        let mut by_degree: Vec<Option<Ptr<T>>> = Vec::new();
+        for mut root in roots {
+            self.unlink(root);
+            loop {
+                let degree = self.node(root).degree;
+                if degree >= by_degree.len() {
+                    by_degree.resize(degree + 1, None);
+                }
+                match by_degree[degree].take() {
+                    None => {
+                        by_degree[degree] = Some(root);
+                        break;
+                    },
+                    Some(other) => {
+                        let (top, below) = if self.node(other).elem < self.node(root).elem {
+                            (other, root)
+                        } else {
+                            (root, other)
+                        };
+                        self.link(top, below);
+                        root = top;
+                    },
+                }
+            }
+        }
+        self.min = None;
+        for root in by_degree.into_iter().flatten() {
+            self.add_roots(root);
+        }
+    }
+
+    // Makes one lone root a child of another.
+    fn link(&mut self, top: Ptr<T>, below: Ptr<T>) {
+        match self.node(top).child {
+            None => self.node_mut(top).child = Some(below),
+            Some(child) => self.splice(child, below),
+        }
+        let node = self.node_mut(below);
+        node.parent = Some(top);
+        node.mark = false;
+        self.node_mut(top).degree += 1;
+    }
+
+    // Lowers the element behind the handle, which must still be in the
+    // heap, to the given value, and returns the old one.
+    pub fn decrease_key(&mut self, handle: &Handle, elem: T) -> T {
+        let i = self.pool.resolve(&handle.0).expect("decrease_key: handle is not to an element of this heap");
+        assert!(elem <= self.node(i).elem, "decrease_key: new element is greater than the current one");
+        let old = mem::replace(&mut self.node_mut(i).elem, elem);
+        if let Some(parent) = self.node(i).parent {
+            if self.node(i).elem < self.node(parent).elem {
+                self.cut(i);
+                self.cascading_cut(parent);
+            }
+        }
+        let min = self.min.unwrap();
+        if self.node(i).elem < self.node(min).elem {
+            self.min = Some(i);
+        }
+        old
+    }
+
+    // Moves a child, with its subtree, to the roots.
+    fn cut(&mut self, i: Ptr<T>) {
+        let parent = self.node_mut(i).parent.take().unwrap();
+        let right = self.node(i).right;
+        if self.node(parent).child == Some(i) {
+            self.node_mut(parent).child = if right == i { None } else { Some(right) };
+        }
+        self.node_mut(parent).degree -= 1;
+        self.unlink(i);
+        self.node_mut(i).mark = false;
+        self.add_roots(i);
+    }
+
+    // Walks up from a node that has just lost a child, cutting each node
+    // that had already lost one, and marks the first that had not.
+    fn cascading_cut(&mut self, mut i: Ptr<T>) {
+        while let Some(parent) = self.node(i).parent {
+            if !self.node(i).mark {
+                self.node_mut(i).mark = true;
+                return;
+            }
+            self.cut(i);
+            i = parent;
+        }
+    }
+
+    // Splices the roots of the other heap into this heap's list of roots,
+    // once its slots have been taken over. Handles into the other heap name
+    // the same elements in this one.
+    pub fn meld(&mut self, other: FibonacciHeap<T>) {
+        self.pool.absorb(other.pool);
+        if let Some(min) = other.min {
+            self.add_roots(min);
+        }
+        self.len += other.len;
+    }
+}
+
+// A clone holds the same elements, but in nodes of its own, so handles
+// into the original are refused by the clone.
+impl<T: Ord + Clone> Clone for FibonacciHeap<T> {
+    fn clone(&self) -> Self {
+        let mut heap = FibonacciHeap::new();
+        for node in self.pool.iter() {
+            heap.push(node.elem.clone());
+        }
+        heap
+    }
+}
+
+// The nodes are only reached through the heap.
+unsafe impl<T: Send> Send for FibonacciHeap<T> {}
+unsafe impl<T: Sync> Sync for FibonacciHeap<T> {}
+
+// Lists the elements in no particular order.
+impl<T: fmt::Debug> fmt::Debug for FibonacciHeap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.pool.iter().map(|node| &node.elem)).finish()
+    }
+}
+
+impl<T: Ord> Default for FibonacciHeap<T> {
+    fn default() -> Self {
+        FibonacciHeap::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use common::XorShift;
+
+    use super::FibonacciHeap;
+    use PairingHeap;
+
+    #[test]
+    fn matches_pairing_heap() {
+        let mut fibonacci = FibonacciHeap::new();
+        let mut pairing = PairingHeap::new();
+        let mut handles = Vec::new();
+        let mut popped = (Vec::new(), Vec::new());
+        let mut rng = XorShift::new(1);
+        for step in 0..3000u64 {
+            let x = rng.next_u64();
+            let value = 1_000_000 + x % 100_000;
+            match step % 5 {
+                0..=2 => handles.push((fibonacci.push(value), pairing.push(value))),
+                3 => {
+                    // A handle goes stale once its element is popped, and
+                    // the heaps may pop different ones of equal elements,
+                    // so only lower elements that both heaps still hold.
+                    let (ref f, ref p) = handles[(x % handles.len() as u64) as usize];
+                    if let (Some(&a), Some(&b)) = (fibonacci.get(f), pairing.get(p)) {
+                        if a == b {
+                            let lower = a - x % 1000;
+                            fibonacci.decrease_key(f, lower);
+                            pairing.decrease_key(p, lower);
+                        }
+                    }
+                },
+                _ => {
+                    popped.0.push(fibonacci.pop_min());
+                    popped.1.push(pairing.pop_min());
+                },
+            }
+            assert_eq!(fibonacci.peek_min(), pairing.peek_min());
+        }
+        assert_eq!(popped.0, popped.1);
+
+        let mut other = FibonacciHeap::new();
+        let moved: Vec<_> = (0..100).map(|i| other.push(i + 1000)).collect();
+        other.pop_min();
+        fibonacci.meld(other);
+        assert_eq!(fibonacci.len(), pairing.len() + 99);
+        assert_eq!(fibonacci.get(&moved[0]), None);
+        for (i, handle) in moved.iter().enumerate().skip(1) {
+            assert_eq!(fibonacci.decrease_key(handle, i as u64), i as u64 + 1000);
+        }
+        for i in 1..100 {
+            assert_eq!(fibonacci.pop_min(), Some(i));
+        }
+        while let Some(x) = pairing.pop_min() {
+            assert_eq!(fibonacci.pop_min(), Some(x));
+        }
+        assert!(fibonacci.is_empty());
+    }
+}
@@ -0,0 +1,281 @@
+use core::borrow::Borrow;
+use core::mem;
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone)]
+struct Entry<K, P> {
+    key: K,
+    priority: P,
+    slot: usize,
+}
+
+// A min-heap of keys ordered by their priorities, which can be looked up,
+// changed or removed by key. The heap is laid out like binary_heap::Heap.
+// A map gives each key a slot, which stays the same while the key is
+// present, and a table indexed by slot holds the key's position in the
+// heap, so that an entry moving only updates the table. Every operation
+// then takes one map lookup or update besides the O(log n) moves. Keys
+// are stored in both the heap and the map, hence the Clone bound.
+#[derive(Debug, Clone)]
+pub struct IndexedPriorityQueue<K, P> {
+    entries: Vec<Entry<K, P>>,
+    slots: BTreeMap<K, usize>,
+    positions: Vec<usize>,
+    // The slots of removed keys, handed out again before new ones.
+    free: Vec<usize>,
+}
+
+impl<K: Ord + Clone, P: Ord> IndexedPriorityQueue<K, P> {
+    pub fn new() -> Self {
+        IndexedPriorityQueue { entries: Vec::new(), slots: BTreeMap::new(), positions: Vec::new(), free: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.slots.clear();
+        self.positions.clear();
+        self.free.clear();
+    }
+
+    fn position<Q: ?Sized + Ord>(&self, key: &Q) -> Option<usize> where K: Borrow<Q> {
+        self.slots.get(key).map(|&slot| self.positions[slot])
+    }
+
+    pub fn contains_key<Q: ?Sized + Ord>(&self, key: &Q) -> bool where K: Borrow<Q> {
+        self.slots.contains_key(key)
+    }
+
+    pub fn priority<Q: ?Sized + Ord>(&self, key: &Q) -> Option<&P> where K: Borrow<Q> {
+        self.position(key).map(|i| &self.entries[i].priority)
+    }
+
+    pub fn peek_min(&self) -> Option<(&K, &P)> {
+        self.entries.first().map(|entry| (&entry.key, &entry.priority))
+    }
+
+    // Adds the key with the given priority, or changes its priority if it
+    // is already present and returns the old one.
+    pub fn push(&mut self, key: K, priority: P) -> Option<P> {
+        if let Some(i) = self.position(&key) {
+            return Some(self.set_priority(i, priority));
+        }
+        let i = self.entries.len();
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.positions[slot] = i;
+                slot
+            },
+            None => {
+                self.positions.push(i);
+                self.positions.len() - 1
+            },
+        };
+        self.slots.insert(key.clone(), slot);
+        self.entries.push(Entry { key, priority, slot });
+        self.sift_up(i);
+        None
+    }
+
+    // Changes the priority of a key that is present, either way, and
+    // returns the old one.
+    pub fn change_priority<Q: ?Sized + Ord>(&mut self, key: &Q, priority: P) -> Option<P> where K: Borrow<Q> {
+        let i = self.position(key)?;
+        Some(self.set_priority(i, priority))
+    }
+
+    pub fn remove<Q: ?Sized + Ord>(&mut self, key: &Q) -> Option<P> where K: Borrow<Q> {
+        let slot = self.slots.remove(key)?;
+        self.free.push(slot);
+        Some(self.remove_at(self.positions[slot]).priority)
+    }
+
+    pub fn pop_min(&mut self) -> Option<(K, P)> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        let entry = self.remove_at(0);
+        self.slots.remove(&entry.key);
+        self.free.push(entry.slot);
+        Some((entry.key, entry.priority))
+    }
+
+    // Iterates in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &P)> {
+        self.entries.iter().map(|entry| (&entry.key, &entry.priority))
+    }
+
+    fn set_priority(&mut self, i: usize, priority: P) -> P {
+        let old = mem::replace(&mut self.entries[i].priority, priority);
+        if self.entries[i].priority < old {
+            self.sift_up(i);
+        } else {
+            self.sift_down(i);
+        }
+        old
+    }
+
+    // Takes out the entry at i and moves the last entry into its place.
+    // The caller frees the slot of the entry taken out.
+    fn remove_at(&mut self, i: usize) -> Entry<K, P> {
+        let entry = self.entries.swap_remove(i);
+        if i < self.entries.len() {
+            self.positions[self.entries[i].slot] = i;
+            self.sift_down(i);
+            self.sift_up(i);
+        }
+        entry
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.entries.swap(a, b);
+        self.positions[self.entries[a].slot] = a;
+        self.positions[self.entries[b].slot] = b;
+    }
+
+    fn sift_up(&mut self, mut i: usize) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if self.entries[i].priority >= self.entries[parent].priority {
+                break;
+            }
+            self.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut i: usize) {
+        let len = self.entries.len();
+        loop {
+            let left = 2 * i + 1;
+            if left >= len {
+                break;
+            }
+            let right = left + 1;
+            let child = if right < len && self.entries[right].priority < self.entries[left].priority { right } else { left };
+            if self.entries[child].priority >= self.entries[i].priority {
+                break;
+            }
+            self.swap(i, child);
+            i = child;
+        }
+    }
+}
+
+impl<K: Ord + Clone, P: Ord> Default for IndexedPriorityQueue<K, P> {
+    fn default() -> Self {
+        IndexedPriorityQueue::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::vec;
+    use std::vec::Vec;
+
+    use common::XorShift;
+
+    use super::IndexedPriorityQueue;
+
+    #[test]
+    fn dijkstra() {
+        // A grid of 20 by 20 nodes, where stepping right from (x, y) costs
+        // 1 + (x * y) % 7 and stepping down costs 1 + (x + y) % 5.
+        let n = 20;
+        let mut dist = vec![u32::MAX; n * n];
+        let mut queue = IndexedPriorityQueue::new();
+        dist[0] = 0;
+        queue.push(0, 0);
+        while let Some((node, d)) = queue.pop_min() {
+            assert_eq!(d, dist[node]);
+            let (x, y) = (node % n, node / n);
+            let mut steps = Vec::new();
+            if x + 1 < n {
+                steps.push((node + 1, 1 + (x * y) as u32 % 7));
+            }
+            if y + 1 < n {
+                steps.push((node + n, 1 + (x + y) as u32 % 5));
+            }
+            for (next, cost) in steps {
+                if d + cost < dist[next] {
+                    let old = dist[next];
+                    dist[next] = d + cost;
+                    let changed = queue.change_priority(&next, d + cost);
+                    assert_eq!(changed, if old == u32::MAX { None } else { Some(old) });
+                    if changed.is_none() {
+                        queue.push(next, d + cost);
+                    }
+                }
+            }
+        }
+        // Only right and down steps exist, so the distances follow a simple
+        // recurrence to check against.
+        let mut expected = vec![0u32; n * n];
+        for y in 0..n {
+            for x in 0..n {
+                let left = if x > 0 { expected[y * n + x - 1] + 1 + ((x - 1) * y) as u32 % 7 } else { u32::MAX };
+                let up = if y > 0 { expected[(y - 1) * n + x] + 1 + (x + y - 1) as u32 % 5 } else { u32::MAX };
+                if x + y > 0 {
+                    expected[y * n + x] = left.min(up);
+                }
+            }
+        }
+        assert_eq!(dist, expected);
+
+        let mut queue = IndexedPriorityQueue::new();
+        for (key, priority) in [("a", 5), ("b", 3), ("c", 8), ("d", 1)] {
+            queue.push(key, priority);
+        }
+        assert_eq!(queue.push("c", 2), Some(8));
+        assert_eq!(queue.remove("d"), Some(1));
+        assert_eq!(queue.remove("e"), None);
+        assert_eq!(queue.change_priority("b", 9), Some(3));
+        assert_eq!(queue.priority("a"), Some(&5));
+        assert_eq!(queue.peek_min(), Some((&"c", &2)));
+        let popped: Vec<_> = (0..3).filter_map(|_| queue.pop_min()).collect();
+        assert_eq!(popped, vec![("c", 2), ("a", 5), ("b", 9)]);
+        assert!(queue.is_empty() && !queue.contains_key("a"));
+    }
+
+    #[test]
+    fn churn() {
+        // Keys far apart come and go, so slots are freed and handed out
+        // again, and the table of positions stays as small as the queue.
+        let mut rng = XorShift::new(7);
+        let mut queue = IndexedPriorityQueue::new();
+        let mut model = BTreeMap::new();
+        for _ in 0..5000 {
+            let key = rng.next_u64() % 64 * 1_000_000_007;
+            let priority = rng.next_u64() % 100;
+            match rng.next_u64() % 4 {
+                0 => assert_eq!(queue.remove(&key), model.remove(&key)),
+                1 => {
+                    let min = model.iter().min_by_key(|&(&k, &p)| (p, k)).map(|(&k, &p)| (k, p));
+                    match queue.pop_min() {
+                        None => assert_eq!(min, None),
+                        Some((k, p)) => {
+                            assert_eq!(Some(p), min.map(|(_, p)| p));
+                            assert_eq!(model.remove(&k), Some(p));
+                        },
+                    }
+                },
+                _ => assert_eq!(queue.push(key, priority), model.insert(key, priority)),
+            }
+            assert_eq!(queue.len(), model.len());
+        }
+        for (key, priority) in &model {
+            assert_eq!(queue.priority(key), Some(priority));
+        }
+        assert!(queue.positions.len() <= 64);
+    }
+}
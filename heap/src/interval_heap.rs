@@ -0,0 +1,213 @@
+use core::iter::FromIterator;
+use core::slice;
+
+use alloc::vec::Vec;
+
+// A double-ended priority queue laid out as an implicit binary tree in a
+// single Vec, like binary_heap::Heap, but with two elements to a node: the
+// node k holds the interval from elems[2k] to elems[2k + 1], and contains
+// the intervals of its children. The low ends then form a min-heap and the
+// high ends a max-heap, and only the last node may hold a single element,
+// which counts as both its ends. The tree is half as deep as a min-max
+// heap of the same size, and each step down makes fewer comparisons.
+#[derive(Debug, Clone)]
+pub struct IntervalHeap<T> {
+    elems: Vec<T>,
+}
+
+impl<T: Ord> IntervalHeap<T> {
+    pub fn new() -> Self {
+        IntervalHeap { elems: Vec::new() }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        IntervalHeap { elems: Vec::with_capacity(capacity) }
+    }
+
+    // Arranges the elements into a heap in place, in linear time, by
+    // trickling down both ends of every node, from the last up.
+    pub fn from_vec(elems: Vec<T>) -> Self {
+        let mut heap = IntervalHeap { elems };
+        for k in (0..heap.elems.len().div_ceil(2)).rev() {
+            heap.trickle_down_low(k);
+            heap.trickle_down_high(k);
+        }
+        heap
+    }
+
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.elems.clear();
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        self.elems.first()
+    }
+
+    pub fn peek_max(&self) -> Option<&T> {
+        self.elems.get(1).or(self.elems.first())
+    }
+
+    pub fn push(&mut self, elem: T) {
+        self.elems.push(elem);
+        let i = self.elems.len() - 1;
+        if i % 2 == 1 {
+            // The element completes the last node as its high end, unless
+            // it belongs at the low end. Either way, only the end it went
+            // to can be out of its parent's interval.
+            if self.elems[i] < self.elems[i - 1] {
+                self.elems.swap(i, i - 1);
+                self.bubble_up_low(i - 1);
+            } else {
+                self.bubble_up_high(i);
+            }
+        } else if i > 0 {
+            let parent = (i / 2 - 1) / 2;
+            if self.elems[i] < self.elems[2 * parent] {
+                self.bubble_up_low(i);
+            } else {
+                self.bubble_up_high(i);
+            }
+        }
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        if self.elems.len() <= 2 {
+            return if self.elems.is_empty() { None } else { Some(self.elems.remove(0)) };
+        }
+        let min = self.elems.swap_remove(0);
+        self.trickle_down_low(0);
+        Some(min)
+    }
+
+    pub fn pop_max(&mut self) -> Option<T> {
+        if self.elems.len() <= 2 {
+            return self.elems.pop();
+        }
+        let max = self.elems.swap_remove(1);
+        self.trickle_down_high(0);
+        Some(max)
+    }
+
+    // Iterates in no particular order.
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.elems.iter()
+    }
+
+    // Gives up the elements in no particular order.
+    pub fn into_vec(self) -> Vec<T> {
+        self.elems
+    }
+
+    // The index of the high end of node k, which for a lone element is the
+    // element itself.
+    fn high(&self, k: usize) -> usize {
+        (2 * k + 1).min(self.elems.len() - 1)
+    }
+
+    // Moves the low end at i, or a lone element, up past the low ends of
+    // its ancestors that are greater.
+    fn bubble_up_low(&mut self, mut i: usize) {
+        while i > 1 {
+            let parent = 2 * ((i / 2 - 1) / 2);
+            if self.elems[i] >= self.elems[parent] {
+                break;
+            }
+            self.elems.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    // Moves the high end at i, or a lone element, up past the high ends of
+    // its ancestors that are less.
+    fn bubble_up_high(&mut self, mut i: usize) {
+        while i > 1 {
+            let parent = 2 * ((i / 2 - 1) / 2) + 1;
+            if self.elems[i] <= self.elems[parent] {
+                break;
+            }
+            self.elems.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    // Puts the ends of node k in order.
+    fn order_ends(&mut self, k: usize) {
+        if 2 * k + 1 < self.elems.len() && self.elems[2 * k + 1] < self.elems[2 * k] {
+            self.elems.swap(2 * k, 2 * k + 1);
+        }
+    }
+
+    // Moves the low end of node k down the low ends of its descendants. A
+    // low end that arrives at a node may exceed its high end, in which case
+    // the two are swapped before going on.
+    fn trickle_down_low(&mut self, mut k: usize) {
+        let nodes = self.elems.len().div_ceil(2);
+        loop {
+            self.order_ends(k);
+            let first = 2 * k + 1;
+            if first >= nodes {
+                break;
+            }
+            let child = if first + 1 < nodes && self.elems[2 * (first + 1)] < self.elems[2 * first] { first + 1 } else { first };
+            if self.elems[2 * child] >= self.elems[2 * k] {
+                break;
+            }
+            self.elems.swap(2 * k, 2 * child);
+            k = child;
+        }
+    }
+
+    // Moves the high end of node k down the high ends of its descendants,
+    // like trickle_down_low.
+    fn trickle_down_high(&mut self, mut k: usize) {
+        let nodes = self.elems.len().div_ceil(2);
+        loop {
+            self.order_ends(k);
+            let first = 2 * k + 1;
+            if first >= nodes {
+                break;
+            }
+            let child = if first + 1 < nodes && self.elems[self.high(first + 1)] > self.elems[self.high(first)] { first + 1 } else { first };
+            let (high, child_high) = (self.high(k), self.high(child));
+            if self.elems[child_high] <= self.elems[high] {
+                break;
+            }
+            self.elems.swap(high, child_high);
+            k = child;
+        }
+    }
+}
+
+impl<T: Ord> Default for IntervalHeap<T> {
+    fn default() -> Self {
+        IntervalHeap::new()
+    }
+}
+
+impl<T: Ord> From<Vec<T>> for IntervalHeap<T> {
+    fn from(elems: Vec<T>) -> Self {
+        IntervalHeap::from_vec(elems)
+    }
+}
+
+impl<T: Ord> FromIterator<T> for IntervalHeap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        IntervalHeap::from_vec(iter.into_iter().collect())
+    }
+}
+
+impl<T: Ord> Extend<T> for IntervalHeap<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
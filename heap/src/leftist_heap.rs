@@ -0,0 +1,145 @@
+use core::mem;
+
+use alloc::boxed::Box;
+
+use common::{drop_tree, BoxedNode};
+
+type Link<T> = Option<Box<Node<T>>>;
+
+struct Node<T> {
+    elem: T,
+    // The length of the right spine, counting this node.
+    rank: usize,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+impl<T> BoxedNode for Node<T> {
+    fn take_children(&mut self) -> (Link<T>, Link<T>) {
+        (self.left.take(), self.right.take())
+    }
+}
+
+fn rank<T>(link: &Link<T>) -> usize {
+    link.as_ref().map_or(0, |node| node.rank)
+}
+
+// Merges along the right spines, which are at most log(n + 1) long, and
+// swaps children on the way back up wherever the right spine has become
+// the longer.
+fn merge<T: Ord>(a: Link<T>, b: Link<T>) -> Link<T> {
+    let (mut top, below) = match (a, b) {
+        (None, b) => return b,
+        (a, None) => return a,
+        (Some(a), Some(b)) => if b.elem < a.elem { (b, a) } else { (a, b) },
+    };
+    let right = top.right.take();
+    top.right = merge(right, Some(below));
+    if rank(&top.left) < rank(&top.right) {
+        mem::swap(&mut top.left, &mut top.right);
+    }
+    top.rank = rank(&top.right) + 1;
+    Some(top)
+}
+
+// A min-heap as a binary tree in which every node is no greater than its
+// children and, on every node, the right spine is no longer than the left.
+// Two heaps merge along their right spines, so merge, push and pop_min
+// take O(log n) time, and nothing but the spines is touched.
+pub struct LeftistHeap<T> {
+    root: Link<T>,
+    len: usize,
+}
+
+impl<T: Ord> LeftistHeap<T> {
+    pub fn new() -> Self {
+        LeftistHeap { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        self.root.as_ref().map(|node| &node.elem)
+    }
+
+    pub fn push(&mut self, elem: T) {
+        let node = Box::new(Node { elem, rank: 1, left: None, right: None });
+        self.root = merge(self.root.take(), Some(node));
+        self.len += 1;
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        let node = *self.root.take()?;
+        self.root = merge(node.left, node.right);
+        self.len -= 1;
+        Some(node.elem)
+    }
+
+    pub fn merge(&mut self, mut other: LeftistHeap<T>) {
+        self.root = merge(self.root.take(), other.root.take());
+        self.len += other.len;
+    }
+}
+
+impl<T: Ord> Default for LeftistHeap<T> {
+    fn default() -> Self {
+        LeftistHeap::new()
+    }
+}
+
+impl<T> Drop for LeftistHeap<T> {
+    // The left spine can be as long as the heap.
+    fn drop(&mut self) {
+        drop_tree(self.root.take());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::{rank, LeftistHeap, Link, Node};
+
+    // Checks the heap order and the leftist property, and returns the size.
+    fn check<T: Ord>(root: &Link<T>) -> usize {
+        let mut stack: Vec<&Node<T>> = root.iter().map(|node| &**node).collect();
+        let mut size = 0;
+        while let Some(node) = stack.pop() {
+            assert!(rank(&node.left) >= rank(&node.right));
+            assert_eq!(node.rank, rank(&node.right) + 1);
+            for child in node.left.iter().chain(node.right.iter()) {
+                assert!(node.elem <= child.elem);
+                stack.push(child);
+            }
+            size += 1;
+        }
+        size
+    }
+
+    #[test]
+    fn leftist_shape() {
+        // Pushing in decreasing order leaves a left spine as long as the
+        // heap, which dropping must not recurse down.
+        let mut heap = LeftistHeap::new();
+        for i in (0..100_000u32).rev() {
+            heap.push(i);
+        }
+        assert_eq!(rank(&heap.root), 1);
+        let mut other = LeftistHeap::new();
+        for i in 0..1000 {
+            other.push(i * 7919 % 1000);
+        }
+        heap.merge(other);
+        assert_eq!(check(&heap.root), 101_000);
+        assert_eq!(heap.pop_min(), Some(0));
+        assert_eq!(heap.pop_min(), Some(0));
+        assert_eq!(heap.pop_min(), Some(1));
+        assert_eq!(check(&heap.root), heap.len());
+    }
+}
@@ -0,0 +1,164 @@
+#![no_std]
+
+#[cfg(test)]
+extern crate std;
+extern crate alloc;
+extern crate common;
+
+pub mod binary_heap;
+pub mod binomial_heap;
+pub mod dary_heap;
+pub mod fibonacci_heap;
+pub mod indexed;
+pub mod interval_heap;
+pub mod leftist_heap;
+pub mod min_max_heap;
+pub mod pairing_heap;
+mod pool;
+pub mod skew_heap;
+
+pub use binary_heap::{MaxHeap, MinHeap};
+pub use binomial_heap::BinomialHeap;
+pub use dary_heap::DaryHeap;
+pub use fibonacci_heap::FibonacciHeap;
+pub use indexed::IndexedPriorityQueue;
+pub use interval_heap::IntervalHeap;
+pub use leftist_heap::LeftistHeap;
+pub use min_max_heap::MinMaxHeap;
+pub use pairing_heap::PairingHeap;
+pub use skew_heap::SkewHeap;
+
+// Which end of the order a heap keeps on top.
+pub trait Order {
+    // Whether a belongs above b.
+    fn above<T: Ord>(a: &T, b: &T) -> bool;
+}
+
+// Keeps the least element on top.
+#[derive(Debug, Clone, Copy)]
+pub enum Min {}
+
+// Keeps the greatest element on top.
+#[derive(Debug, Clone, Copy)]
+pub enum Max {}
+
+impl Order for Min {
+    fn above<T: Ord>(a: &T, b: &T) -> bool {
+        a < b
+    }
+}
+
+impl Order for Max {
+    fn above<T: Ord>(a: &T, b: &T) -> bool {
+        a > b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use {BinomialHeap, FibonacciHeap, IntervalHeap, LeftistHeap, MinMaxHeap, PairingHeap, SkewHeap};
+
+    // Runs the same pushes, pops and melds against each meldable heap,
+    // which is named along with its method for melding.
+    macro_rules! meldable_heap_test {
+        ($name:ident, $heap:ident, $meld:ident) => {
+            #[test]
+            fn $name() {
+                let mut heaps: Vec<$heap<u32>> = (0..8).map(|_| $heap::new()).collect();
+                let mut expected = Vec::new();
+                for i in 0..4000u32 {
+                    let x = i.wrapping_mul(2654435761) % 10_000;
+                    heaps[i as usize % 8].push(x);
+                    expected.push(x);
+                }
+                for heap in &mut heaps {
+                    let min = heap.pop_min().unwrap();
+                    let at = expected.iter().position(|&x| x == min).unwrap();
+                    expected.swap_remove(at);
+                }
+                // Meld the heaps pairwise, like a tournament, down to one.
+                while heaps.len() > 1 {
+                    let mut melded = Vec::new();
+                    let mut rest = heaps.into_iter();
+                    while let Some(mut heap) = rest.next() {
+                        if let Some(other) = rest.next() {
+                            heap.$meld(other);
+                        }
+                        melded.push(heap);
+                    }
+                    heaps = melded;
+                }
+                let mut heap = heaps.pop().unwrap();
+                heap.$meld($heap::new());
+                assert_eq!(heap.len(), expected.len());
+                expected.sort();
+                let mut popped = Vec::new();
+                while let Some(x) = heap.pop_min() {
+                    popped.push(x);
+                }
+                assert_eq!(popped, expected);
+                assert!(heap.is_empty() && heap.peek_min().is_none());
+            }
+        };
+    }
+
+    meldable_heap_test!(binomial_melds, BinomialHeap, meld);
+    meldable_heap_test!(fibonacci_melds, FibonacciHeap, meld);
+    meldable_heap_test!(leftist_merges, LeftistHeap, merge);
+    meldable_heap_test!(pairing_melds, PairingHeap, meld);
+    meldable_heap_test!(skew_merges, SkewHeap, merge);
+
+    // Runs the same pops from both ends against each double-ended priority
+    // queue, built both by heapifying and by pushing.
+    macro_rules! double_ended_test {
+        ($name:ident, $heap:ident) => {
+            #[test]
+            fn $name() {
+                for len in (0..40).chain(Some(1000)) {
+                    let elems: Vec<u32> = (0..len).map(|i| i * 7919 % (2 * len)).collect();
+                    let mut sorted = elems.clone();
+                    sorted.sort();
+                    let mut heapified: $heap<u32> = elems.iter().cloned().collect();
+                    let mut pushed = $heap::new();
+                    pushed.extend(elems.iter().cloned());
+                    for heap in &mut [&mut heapified, &mut pushed] {
+                        assert_eq!(heap.len(), elems.len());
+                        let mut expected = sorted.clone();
+                        let mut turn = 0;
+                        while !expected.is_empty() {
+                            assert_eq!((heap.peek_min(), heap.peek_max()), (expected.first(), expected.last()));
+                            if turn % 3 == 0 {
+                                assert_eq!(heap.pop_max(), expected.pop());
+                            } else {
+                                assert_eq!(heap.pop_min(), Some(expected.remove(0)));
+                            }
+                            turn += 1;
+                        }
+                        assert_eq!((heap.pop_min(), heap.pop_max()), (None, None));
+                    }
+                }
+
+                // Keeping the largest k of a stream, dropping the least
+                // whenever the buffer overflows.
+                let elems: Vec<u32> = (0..1000).map(|i| i * 7919 % 2000).collect();
+                let mut top = $heap::with_capacity(11);
+                for &x in &elems {
+                    top.push(x);
+                    if top.len() > 10 {
+                        top.pop_min();
+                    }
+                }
+                let mut kept = top.into_vec();
+                kept.sort();
+                let mut sorted = elems;
+                sorted.sort();
+                assert_eq!(kept, &sorted[sorted.len() - 10..]);
+            }
+        };
+    }
+
+    double_ended_test!(interval_ends, IntervalHeap);
+    double_ended_test!(min_max_ends, MinMaxHeap);
+}
@@ -0,0 +1,207 @@
+use core::iter::FromIterator;
+use core::slice;
+
+use alloc::vec::Vec;
+
+use {Max, Min, Order};
+
+// Whether the element at i is on an even level of the tree, counting the
+// root as level 0.
+fn is_min_level(i: usize) -> bool {
+    (i + 1).ilog2() & 1 == 0
+}
+
+// A double-ended priority queue laid out like binary_heap::Heap, but with
+// levels that alternate in order: an element on an even level is no
+// greater than anything below it, and one on an odd level no less. The
+// least element is then the root and the greatest one of its children, so
+// both ends can be peeked in O(1) and pushed or popped in O(log n) without
+// keeping two heaps.
+#[derive(Debug, Clone)]
+pub struct MinMaxHeap<T> {
+    elems: Vec<T>,
+}
+
+impl<T: Ord> MinMaxHeap<T> {
+    pub fn new() -> Self {
+        MinMaxHeap { elems: Vec::new() }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        MinMaxHeap { elems: Vec::with_capacity(capacity) }
+    }
+
+    // Arranges the elements into a heap in place, in linear time, by
+    // trickling down every element that has children, from the last up.
+    pub fn from_vec(elems: Vec<T>) -> Self {
+        let mut heap = MinMaxHeap { elems };
+        for i in (0..heap.elems.len() / 2).rev() {
+            heap.trickle_down(i);
+        }
+        heap
+    }
+
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.elems.clear();
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        self.elems.first()
+    }
+
+    pub fn peek_max(&self) -> Option<&T> {
+        self.max_index().map(|i| &self.elems[i])
+    }
+
+    pub fn push(&mut self, elem: T) {
+        self.elems.push(elem);
+        let last = self.elems.len() - 1;
+        if last > 0 {
+            if is_min_level(last) {
+                self.bubble_up::<Min, Max>(last);
+            } else {
+                self.bubble_up::<Max, Min>(last);
+            }
+        }
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        if self.elems.is_empty() {
+            return None;
+        }
+        Some(self.remove_at(0))
+    }
+
+    pub fn pop_max(&mut self) -> Option<T> {
+        self.max_index().map(|i| self.remove_at(i))
+    }
+
+    // Iterates in no particular order.
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.elems.iter()
+    }
+
+    // Gives up the elements in no particular order.
+    pub fn into_vec(self) -> Vec<T> {
+        self.elems
+    }
+
+    fn max_index(&self) -> Option<usize> {
+        match self.elems.len() {
+            0 => None,
+            1 => Some(0),
+            2 => Some(1),
+            _ => Some(if self.elems[2] > self.elems[1] { 2 } else { 1 }),
+        }
+    }
+
+    // Replaces the element at i, which is the root or one of its children,
+    // with the last one and restores the order below it.
+    fn remove_at(&mut self, i: usize) -> T {
+        let elem = self.elems.swap_remove(i);
+        if i < self.elems.len() {
+            self.trickle_down(i);
+        }
+        elem
+    }
+
+    // Moves a new element at i up, first past its parent if it belongs on
+    // the parent's side, and then by grandparents among the levels of that
+    // side. O is the order of the level of i, and P of its parent's.
+    fn bubble_up<O: Order, P: Order>(&mut self, i: usize) {
+        let parent = (i - 1) / 2;
+        if P::above(&self.elems[i], &self.elems[parent]) {
+            self.elems.swap(i, parent);
+            self.bubble_up_by_grandparents::<P>(parent);
+        } else {
+            self.bubble_up_by_grandparents::<O>(i);
+        }
+    }
+
+    fn bubble_up_by_grandparents<O: Order>(&mut self, mut i: usize) {
+        while i > 2 {
+            let grandparent = (i - 3) / 4;
+            if !O::above(&self.elems[i], &self.elems[grandparent]) {
+                break;
+            }
+            self.elems.swap(i, grandparent);
+            i = grandparent;
+        }
+    }
+
+    fn trickle_down(&mut self, i: usize) {
+        if is_min_level(i) {
+            self.trickle_down_by::<Min>(i);
+        } else {
+            self.trickle_down_by::<Max>(i);
+        }
+    }
+
+    // Moves the element at i down past whichever of its children and
+    // grandchildren belongs furthest above, where O is the order of the
+    // level of i. Passing a grandchild may leave the element on the wrong
+    // side of the child in between, which is then swapped back.
+    fn trickle_down_by<O: Order>(&mut self, mut i: usize) {
+        let len = self.elems.len();
+        loop {
+            let first_child = 2 * i + 1;
+            if first_child >= len {
+                break;
+            }
+            let first_grandchild = 4 * i + 3;
+            let descendants = (first_child..first_child + 2).chain(first_grandchild..first_grandchild + 4);
+            let mut top = first_child;
+            for j in descendants.filter(|&j| j < len) {
+                if O::above(&self.elems[j], &self.elems[top]) {
+                    top = j;
+                }
+            }
+            if !O::above(&self.elems[top], &self.elems[i]) {
+                break;
+            }
+            self.elems.swap(i, top);
+            if top < first_grandchild {
+                break;
+            }
+            let parent = (top - 1) / 2;
+            if O::above(&self.elems[parent], &self.elems[top]) {
+                self.elems.swap(top, parent);
+            }
+            i = top;
+        }
+    }
+}
+
+impl<T: Ord> Default for MinMaxHeap<T> {
+    fn default() -> Self {
+        MinMaxHeap::new()
+    }
+}
+
+impl<T: Ord> From<Vec<T>> for MinMaxHeap<T> {
+    fn from(elems: Vec<T>) -> Self {
+        MinMaxHeap::from_vec(elems)
+    }
+}
+
+impl<T: Ord> FromIterator<T> for MinMaxHeap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        MinMaxHeap::from_vec(iter.into_iter().collect())
+    }
+}
+
+impl<T: Ord> Extend<T> for MinMaxHeap<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use core::fmt;
+use core::mem;
+
+use alloc::vec::Vec;
+
+use pool::{self, Pool, Token};
+
+// A reference to an element of a PairingHeap, which follows the element
+// into any heap that its heap is melded into. Once the element is popped,
+// the handle names nothing, and is refused by every heap.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Handle(Token);
+
+type Ptr<T> = pool::Ptr<Node<T>>;
+
+struct Node<T> {
+    elem: T,
+    // The first of the node's children, which are linked through sibling.
+    child: Option<Ptr<T>>,
+    sibling: Option<Ptr<T>>,
+    // The previous sibling, or the parent of a first child, so that a node
+    // can be cut out of its list in constant time.
+    prev: Option<Ptr<T>>,
+}
+
+// A min-heap as a tree in which every node is no greater than its
+// children, which may be any number. Push, meld and decrease_key just link
+// two trees, by making the greater root the first child of the lesser, and
+// pop_min restores a single tree by linking the children of the old root
+// in two passes. Pop takes O(log n) amortized time and the others O(1),
+// but for following a handle to its heap after melds, which is
+// logarithmic in their number. The nodes live in a Pool, where melding
+// two heaps joins their slots without moving any node.
+pub struct PairingHeap<T> {
+    pool: Pool<Node<T>>,
+    root: Option<Ptr<T>>,
+    len: usize,
+}
+
+impl<T: Ord> PairingHeap<T> {
+    pub fn new() -> Self {
+        PairingHeap { pool: Pool::new(), root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn node(&self, i: Ptr<T>) -> &Node<T> {
+        self.pool.get(i)
+    }
+
+    fn node_mut(&mut self, i: Ptr<T>) -> &mut Node<T> {
+        self.pool.get_mut(i)
+    }
+
+    // Links two trees, whose roots must have no siblings, and returns the
+    // root of the result.
+    fn link(&mut self, a: Option<Ptr<T>>, b: Option<Ptr<T>>) -> Option<Ptr<T>> {
+        let (a, b) = match (a, b) {
+            (None, b) => return b,
+            (a, None) => return a,
+            (Some(a), Some(b)) => (a, b),
+        };
+        let (top, below) = if self.node(b).elem < self.node(a).elem { (b, a) } else { (a, b) };
+        let first = self.node(top).child;
+        if let Some(first) = first {
+            self.node_mut(first).prev = Some(below);
+        }
+        let node = self.node_mut(below);
+        node.sibling = first;
+        node.prev = Some(top);
+        self.node_mut(top).child = Some(below);
+        Some(top)
+    }
+
+    pub fn push(&mut self, elem: T) -> Handle {
+        let i = self.pool.alloc(Node { elem, child: None, sibling: None, prev: None });
+        let root = self.root;
+        self.root = self.link(root, Some(i));
+        self.len += 1;
+        Handle(self.pool.token(i))
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        self.root.map(|root| &self.node(root).elem)
+    }
+
+    pub fn get(&self, handle: &Handle) -> Option<&T> {
+        self.pool.resolve(&handle.0).map(|i| &self.node(i).elem)
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        let root = self.root?;
+        let node = self.pool.free(root);
+        self.len -= 1;
+
+        let mut children = Vec::new();
+        let mut child = node.child;
+        while let Some(i) = child {
+            let n = self.node_mut(i);
+            child = n.sibling.take();
+            n.prev = None;
+            children.push(i);
+        }
+        // Link the children in pairs from the left, then fold the pairs
+        // into one tree from the right.
+        let mut pairs = Vec::with_capacity(children.len().div_ceil(2));
+        for pair in children.chunks(2) {
+            let linked = self.link(Some(pair[0]), pair.get(1).cloned());
+            pairs.push(linked);
+        }
+        self.root = pairs.into_iter().rev().fold(None, |acc, pair| self.link(pair, acc));
+        Some(node.elem)
+    }
+
+    // Lowers the element behind the handle, which must still be in the
+    // heap, to the given value, and returns the old one.
+    pub fn decrease_key(&mut self, handle: &Handle, elem: T) -> T {
+        let i = self.pool.resolve(&handle.0).expect("decrease_key: handle is not to an element of this heap");
+        assert!(elem <= self.node(i).elem, "decrease_key: new element is greater than the current one");
+        let old = mem::replace(&mut self.node_mut(i).elem, elem);
+        if self.root == Some(i) {
+            return old;
+        }
+        // Cut the subtree out of its list of siblings and link it back in
+        // at the root.
+        let (prev, sibling) = {
+            let node = self.node_mut(i);
+            (node.prev.take().unwrap(), node.sibling.take())
+        };
+        if self.node(prev).child == Some(i) {
+            self.node_mut(prev).child = sibling;
+        } else {
+            self.node_mut(prev).sibling = sibling;
+        }
+        if let Some(sibling) = sibling {
+            self.node_mut(sibling).prev = Some(prev);
+        }
+        let root = self.root;
+        self.root = self.link(root, Some(i));
+        old
+    }
+
+    // Moves every element of the other heap into this one, by taking over
+    // its slots and linking the two roots. Handles into the other heap
+    // name the same elements in this one.
+    pub fn meld(&mut self, other: PairingHeap<T>) {
+        self.pool.absorb(other.pool);
+        let root = self.root;
+        self.root = self.link(root, other.root);
+        self.len += other.len;
+    }
+}
+
+// A clone holds the same elements, but in nodes of its own, so handles
+// into the original are refused by the clone.
+impl<T: Ord + Clone> Clone for PairingHeap<T> {
+    fn clone(&self) -> Self {
+        let mut heap = PairingHeap::new();
+        for node in self.pool.iter() {
+            heap.push(node.elem.clone());
+        }
+        heap
+    }
+}
+
+// The nodes are only reached through the heap.
+unsafe impl<T: Send> Send for PairingHeap<T> {}
+unsafe impl<T: Sync> Sync for PairingHeap<T> {}
+
+// Lists the elements in no particular order.
+impl<T: fmt::Debug> fmt::Debug for PairingHeap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.pool.iter().map(|node| &node.elem)).finish()
+    }
+}
+
+impl<T: Ord> Default for PairingHeap<T> {
+    fn default() -> Self {
+        PairingHeap::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::PairingHeap;
+
+    fn drain(heap: &mut PairingHeap<u32>) -> Vec<u32> {
+        let mut popped = Vec::new();
+        while let Some(x) = heap.pop_min() {
+            popped.push(x);
+        }
+        popped
+    }
+
+    #[test]
+    fn decrease_and_meld() {
+        let mut heap = PairingHeap::new();
+        let handles: Vec<_> = (0..500u32).map(|i| heap.push(1000 + i * 7919 % 500)).collect();
+        assert_eq!(heap.peek_min(), Some(&1000));
+        for i in (0..500).step_by(3) {
+            let old = *heap.get(&handles[i]).unwrap();
+            assert_eq!(heap.decrease_key(&handles[i], old - 1000), old);
+        }
+        let mut expected: Vec<u32> = (0..500u32).map(|i| {
+            let x = 1000 + i * 7919 % 500;
+            if i % 3 == 0 { x - 1000 } else { x }
+        }).collect();
+        expected.sort();
+        for _ in 0..100 {
+            heap.pop_min();
+        }
+        let mut other = PairingHeap::new();
+        let moved: Vec<_> = (0..50).map(|i| other.push(100 + i * 2)).collect();
+        other.pop_min();
+        heap.meld(other);
+        assert_eq!(heap.get(&moved[0]), None);
+        for (i, handle) in moved.iter().enumerate().skip(1) {
+            assert_eq!(heap.get(handle), Some(&(100 + i as u32 * 2)));
+            heap.decrease_key(handle, i as u32 * 2);
+        }
+        expected.drain(..100);
+        expected.extend((1..50).map(|i| i * 2));
+        expected.sort();
+        assert_eq!(heap.len(), expected.len());
+        assert_eq!(drain(&mut heap), expected);
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "decrease_key: new element is greater than the current one")]
+    fn increasing_key() {
+        let mut heap = PairingHeap::new();
+        let handle = heap.push(1);
+        heap.decrease_key(&handle, 2);
+    }
+
+    #[test]
+    fn handles_across_melds() {
+        // Meld small heaps into big ones and big ones into small ones, so
+        // that both the handles of the heap melded in and those of the heap
+        // melded into have to be followed to the other heap's owner.
+        let mut heaps: Vec<PairingHeap<u32>> = (0..16).map(|_| PairingHeap::new()).collect();
+        let mut handles = Vec::new();
+        for (i, heap) in heaps.iter_mut().enumerate() {
+            for j in 0..(1 + i as u32 % 4 * 10) {
+                handles.push((heap.push(1000 * i as u32 + j + 500), 1000 * i as u32 + j + 500));
+            }
+        }
+        let mut heap = heaps.pop().unwrap();
+        let stale = heap.push(0);
+        assert_eq!(heap.pop_min(), Some(0));
+        while let Some(mut other) = heaps.pop() {
+            if other.len() % 2 == 0 {
+                heap.meld(other);
+            } else {
+                other.meld(heap);
+                heap = other;
+            }
+        }
+        assert_eq!(heap.get(&stale), None);
+        let other: PairingHeap<u32> = PairingHeap::new();
+        for (handle, elem) in &handles {
+            assert_eq!(other.get(handle), None);
+            assert_eq!(heap.get(handle), Some(elem));
+            assert_eq!(heap.decrease_key(handle, elem - 500), *elem);
+        }
+        let mut expected: Vec<u32> = handles.iter().map(|&(_, elem)| elem - 500).collect();
+        expected.sort();
+        assert_eq!(drain(&mut heap), expected);
+        assert!(handles.iter().all(|(handle, _)| heap.get(handle).is_none()));
+    }
+
+    #[test]
+    #[should_panic(expected = "decrease_key: handle is not to an element of this heap")]
+    fn foreign_handle() {
+        let mut heap = PairingHeap::new();
+        let handle = heap.clone().push(1);
+        heap.push(2);
+        heap.decrease_key(&handle, 0);
+    }
+}
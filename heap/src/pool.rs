@@ -0,0 +1,245 @@
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+
+// The storage behind the heaps that hand out handles and meld in constant
+// time. Each node has a slot of its own on the heap, so that melding two
+// pools only joins their lists of slots, and a node keeps its address, and
+// so its handles, wherever it moves. Slots are only freed along with the
+// pool; until then a popped node leaves its slot on the free list for the
+// next push.
+
+// Identifies a pool to the handles it hands out. When two pools are
+// melded, the owner of one is forwarded to the owner of the other, as in
+// union-find, so that a handle finds the pool its node has moved to at
+// the end of the forwards.
+struct Owner {
+    // Null for the owner of a pool, and otherwise the next owner, of which
+    // it holds a count.
+    forward: AtomicPtr<Owner>,
+}
+
+impl Owner {
+    fn root(&self) -> *const Owner {
+        let mut owner: *const Owner = self;
+        loop {
+            // Each owner holds a count of the next, so the chain lives as
+            // long as its first owner.
+            let next = unsafe { (*owner).forward.load(Ordering::Acquire) };
+            if next.is_null() {
+                return owner;
+            }
+            owner = next;
+        }
+    }
+}
+
+impl Drop for Owner {
+    fn drop(&mut self) {
+        let next = *self.forward.get_mut();
+        if !next.is_null() {
+            // The count was made by Arc::into_raw in Pool::absorb.
+            drop(unsafe { Arc::from_raw(next) });
+        }
+    }
+}
+
+pub(crate) struct Slot<N> {
+    node: Option<N>,
+    // Bumped whenever the slot is freed, so that a handle to its old node
+    // does not match the next one.
+    generation: u64,
+    // Every slot of a pool is on its list of slots, and the empty ones are
+    // also on its free list.
+    next: Option<Ptr<N>>,
+    next_free: Option<Ptr<N>>,
+}
+
+pub(crate) type Ptr<N> = NonNull<Slot<N>>;
+
+// What a heap wraps into the handles it hands out.
+pub(crate) struct Token {
+    owner: Arc<Owner>,
+    slot: NonNull<()>,
+    generation: u64,
+}
+
+// The slot is only read through a pool that has checked that it owns it.
+unsafe impl Send for Token {}
+unsafe impl Sync for Token {}
+
+impl Clone for Token {
+    fn clone(&self) -> Self {
+        Token { owner: Arc::clone(&self.owner), slot: self.slot, generation: self.generation }
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Handle").field("slot", &self.slot).field("generation", &self.generation).finish()
+    }
+}
+
+impl PartialEq for Token {
+    fn eq(&self, other: &Token) -> bool {
+        self.slot == other.slot && self.generation == other.generation && Arc::ptr_eq(&self.owner, &other.owner)
+    }
+}
+
+impl Eq for Token {}
+
+impl Hash for Token {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.slot.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+pub(crate) struct Pool<N> {
+    owner: Arc<Owner>,
+    // The number of owners forwarded to this one, plus one. The lighter
+    // of two melded pools is forwarded to the heavier, which keeps every
+    // chain of forwards logarithmic in the number of melds.
+    weight: usize,
+    // The first and last slots of each list, so that two lists join in
+    // constant time.
+    slots: (Option<Ptr<N>>, Option<Ptr<N>>),
+    free: (Option<Ptr<N>>, Option<Ptr<N>>),
+    marker: PhantomData<Box<Slot<N>>>,
+}
+
+// The methods taking a Ptr require one to a slot of this pool that holds a
+// node, as every link between nodes is.
+impl<N> Pool<N> {
+    pub(crate) fn new() -> Self {
+        Pool {
+            owner: Arc::new(Owner { forward: AtomicPtr::default() }),
+            weight: 1,
+            slots: (None, None),
+            free: (None, None),
+            marker: PhantomData,
+        }
+    }
+
+    fn slot(&self, ptr: Ptr<N>) -> &Slot<N> {
+        // The slot lives as long as the pool.
+        unsafe { ptr.as_ref() }
+    }
+
+    fn slot_mut(&mut self, mut ptr: Ptr<N>) -> &mut Slot<N> {
+        // The pool is borrowed mutably, so nothing else refers to the slot.
+        unsafe { ptr.as_mut() }
+    }
+
+    pub(crate) fn get(&self, ptr: Ptr<N>) -> &N {
+        self.slot(ptr).node.as_ref().expect("heap refers to a free slot")
+    }
+
+    pub(crate) fn get_mut(&mut self, ptr: Ptr<N>) -> &mut N {
+        self.slot_mut(ptr).node.as_mut().expect("heap refers to a free slot")
+    }
+
+    pub(crate) fn alloc(&mut self, node: N) -> Ptr<N> {
+        if let Some(ptr) = self.free.0 {
+            let slot = self.slot_mut(ptr);
+            slot.node = Some(node);
+            let next = slot.next_free.take();
+            self.free.0 = next;
+            if next.is_none() {
+                self.free.1 = None;
+            }
+            return ptr;
+        }
+        let slot = Box::new(Slot { node: Some(node), generation: 0, next: self.slots.0, next_free: None });
+        let ptr = NonNull::from(Box::leak(slot));
+        self.slots.0 = Some(ptr);
+        if self.slots.1.is_none() {
+            self.slots.1 = Some(ptr);
+        }
+        ptr
+    }
+
+    pub(crate) fn free(&mut self, ptr: Ptr<N>) -> N {
+        let head = self.free.0;
+        let slot = self.slot_mut(ptr);
+        let node = slot.node.take().expect("heap frees a free slot");
+        slot.generation += 1;
+        slot.next_free = head;
+        self.free.0 = Some(ptr);
+        if head.is_none() {
+            self.free.1 = Some(ptr);
+        }
+        node
+    }
+
+    pub(crate) fn token(&self, ptr: Ptr<N>) -> Token {
+        Token { owner: Arc::clone(&self.owner), slot: ptr.cast(), generation: self.slot(ptr).generation }
+    }
+
+    // Finds the slot of a handle, if the node it was handed out for is
+    // still in this pool.
+    pub(crate) fn resolve(&self, token: &Token) -> Option<Ptr<N>> {
+        if token.owner.root() != Arc::as_ptr(&self.owner) {
+            return None;
+        }
+        // The handle was handed out by this pool or one melded into it, so
+        // the slot is one of ours.
+        let ptr = token.slot.cast();
+        let slot = self.slot(ptr);
+        if slot.generation == token.generation && slot.node.is_some() { Some(ptr) } else { None }
+    }
+
+    // Takes over the slots of the other pool, and its handles.
+    pub(crate) fn absorb(&mut self, mut other: Pool<N>) {
+        if other.weight > self.weight {
+            mem::swap(&mut self.owner, &mut other.owner);
+        }
+        let forward = Arc::into_raw(Arc::clone(&self.owner)) as *mut Owner;
+        other.owner.forward.store(forward, Ordering::Release);
+        self.weight += other.weight;
+
+        if let (Some(first), Some(last)) = (other.slots.0.take(), other.slots.1.take()) {
+            self.slot_mut(last).next = self.slots.0;
+            self.slots.0 = Some(first);
+            if self.slots.1.is_none() {
+                self.slots.1 = Some(last);
+            }
+        }
+        if let (Some(first), Some(last)) = (other.free.0.take(), other.free.1.take()) {
+            self.slot_mut(last).next_free = self.free.0;
+            self.free.0 = Some(first);
+            if self.free.1.is_none() {
+                self.free.1 = Some(last);
+            }
+        }
+    }
+
+    // The nodes in no particular order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &N> + '_ {
+        let mut next = self.slots.0;
+        iter::from_fn(move || {
+            let slot = self.slot(next?);
+            next = slot.next;
+            Some(slot)
+        }).filter_map(|slot| slot.node.as_ref())
+    }
+}
+
+impl<N> Drop for Pool<N> {
+    fn drop(&mut self) {
+        let mut next = self.slots.0;
+        while let Some(ptr) = next {
+            // Every slot was leaked from a Box by alloc and is on the list
+            // once.
+            let slot = unsafe { Box::from_raw(ptr.as_ptr()) };
+            next = slot.next;
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use common::{drop_tree, BoxedNode};
+
+type Link<T> = Option<Box<Node<T>>>;
+
+struct Node<T> {
+    elem: T,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+impl<T> BoxedNode for Node<T> {
+    fn take_children(&mut self) -> (Link<T>, Link<T>) {
+        (self.left.take(), self.right.take())
+    }
+}
+
+// Merges along the right spines and swaps the children of every node on
+// the way. The spines can be as long as the heaps, if only for a while, so
+// the nodes taken off them are kept in a Vec rather than on the call stack.
+fn merge<T: Ord>(mut a: Link<T>, mut b: Link<T>) -> Link<T> {
+    let mut spine = Vec::new();
+    let mut merged = loop {
+        match (a, b) {
+            (None, rest) | (rest, None) => break rest,
+            (Some(x), Some(y)) => {
+                let (mut top, below) = if y.elem < x.elem { (y, x) } else { (x, y) };
+                a = top.right.take();
+                b = Some(below);
+                spine.push(top);
+            },
+        }
+    };
+    while let Some(mut top) = spine.pop() {
+        top.right = top.left.take();
+        top.left = merged;
+        merged = Some(top);
+    }
+    merged
+}
+
+// A min-heap as a binary tree in which every node is no greater than its
+// children. It merges like the leftist heap but keeps no ranks: swapping
+// the children of every node on the merge path is enough to keep the right
+// spines short on average, so merge, push and pop_min take O(log n)
+// amortized time.
+pub struct SkewHeap<T> {
+    root: Link<T>,
+    len: usize,
+}
+
+impl<T: Ord> SkewHeap<T> {
+    pub fn new() -> Self {
+        SkewHeap { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        self.root.as_ref().map(|node| &node.elem)
+    }
+
+    pub fn push(&mut self, elem: T) {
+        let node = Box::new(Node { elem, left: None, right: None });
+        self.root = merge(self.root.take(), Some(node));
+        self.len += 1;
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        let node = *self.root.take()?;
+        self.root = merge(node.left, node.right);
+        self.len -= 1;
+        Some(node.elem)
+    }
+
+    pub fn merge(&mut self, mut other: SkewHeap<T>) {
+        self.root = merge(self.root.take(), other.root.take());
+        self.len += other.len;
+    }
+}
+
+impl<T: Ord> Default for SkewHeap<T> {
+    fn default() -> Self {
+        SkewHeap::new()
+    }
+}
+
+impl<T> Drop for SkewHeap<T> {
+    // A left spine can grow as long as the heap.
+    fn drop(&mut self) {
+        drop_tree(self.root.take());
+    }
+}
@@ -0,0 +1,320 @@
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Bound, RangeBounds};
+
+use super::{check_range_bounds, BST, Ptr};
+use iter;
+
+// Describes a summary kept for every subtree, such as a sum or a maximum.
+// combine must be associative, since rebalancing regroups the summaries of
+// neighbouring subtrees, but it need not be commutative: the left argument
+// always summarizes the elements ordered first.
+pub trait Augment<T> {
+    type Summary: Clone;
+
+    fn summarize(elem: &T) -> Self::Summary;
+
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary;
+}
+
+// An element along with the summary of its subtree. Entries are ordered by
+// element alone.
+#[derive(Clone)]
+struct Annotated<T, S> {
+    elem: T,
+    summary: S,
+}
+
+impl<T: Ord, S> PartialEq for Annotated<T, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.elem == other.elem
+    }
+}
+
+impl<T: Ord, S> Eq for Annotated<T, S> {}
+
+impl<T: Ord, S> PartialOrd for Annotated<T, S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord, S> Ord for Annotated<T, S> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.elem.cmp(&other.elem)
+    }
+}
+
+fn update_summary<T, A: Augment<T>>(tree: &mut BST<Annotated<T, A::Summary>>, ptr: Ptr) {
+    let node = tree.deref(&ptr);
+    let left = node.left.map(|left| &tree.deref(&left).elem.summary);
+    let right = node.right.map(|right| &tree.deref(&right).elem.summary);
+    let mut summary = A::summarize(&node.elem.elem);
+    if let Some(left) = left {
+        summary = A::combine(left, &summary);
+    }
+    if let Some(right) = right {
+        summary = A::combine(&summary, right);
+    }
+    tree.deref_mut(&ptr).elem.summary = summary;
+}
+
+// Summarizes the elements of a subtree, an element and another subtree,
+// where either subtree may be empty.
+fn combine_around<T, A: Augment<T>>(left: Option<A::Summary>, elem: &T, right: Option<A::Summary>) -> A::Summary {
+    let mut summary = A::summarize(elem);
+    if let Some(left) = left {
+        summary = A::combine(&left, &summary);
+    }
+    if let Some(right) = right {
+        summary = A::combine(&summary, &right);
+    }
+    summary
+}
+
+// A tree that keeps the summary given by A for every subtree up to date
+// through insertions, deletions and the rotations they cause.
+pub struct AugmentedBST<T, A: Augment<T>> {
+    tree: BST<Annotated<T, A::Summary>>,
+    marker: PhantomData<A>,
+}
+
+#[allow(clippy::new_without_default)]
+impl<T: Ord, A: Augment<T>> AugmentedBST<T, A> {
+    pub fn new() -> Self {
+        let mut tree = BST::new();
+        tree.augment = Some(update_summary::<T, A>);
+        AugmentedBST { tree, marker: PhantomData }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear()
+    }
+
+    pub fn contains<Q: ?Sized + Ord>(&self, elem: &Q) -> bool where T: Borrow<Q> {
+        self.get(elem).is_some()
+    }
+
+    pub fn get<Q: ?Sized + Ord>(&self, elem: &Q) -> Option<&T> where T: Borrow<Q> {
+        self.tree.find_by(|e| elem.cmp(e.elem.borrow())).map(|ptr| &self.tree.deref(&ptr).elem.elem)
+    }
+
+    // Inserts the element, replacing and returning any equal element.
+    pub fn insert(&mut self, elem: T) -> Option<T> {
+        let summary = A::summarize(&elem);
+        self.tree.insert(Annotated { elem, summary }).map(|old| old.elem)
+    }
+
+    pub fn remove<Q: ?Sized + Ord>(&mut self, elem: &Q) -> Option<T> where T: Borrow<Q> {
+        self.tree.delete_by(|e| elem.cmp(e.elem.borrow())).map(|old| old.elem)
+    }
+
+    pub fn iter(&self) -> Iter<'_, T, A::Summary> {
+        Iter { inner: self.tree.iter() }
+    }
+
+    // The summary of the whole tree, or None if it is empty.
+    pub fn summary(&self) -> Option<&A::Summary> {
+        self.tree.root.map(|root| &self.tree.deref(&root).elem.summary)
+    }
+
+    // The summary of the elements within the range, computed from the
+    // summaries of O(log n) subtrees.
+    pub fn range_summary<Q: ?Sized + Ord, R: RangeBounds<Q>>(&self, range: R) -> Option<A::Summary> where T: Borrow<Q> {
+        check_range_bounds(&range);
+        let (start, end) = (range.start_bound(), range.end_bound());
+        // Find the topmost node within the range; the range is then split
+        // between a suffix of its left subtree and a prefix of its right one.
+        let mut node = self.tree.root;
+        while let Some(ptr) = node {
+            let n = self.tree.deref(&ptr);
+            let elem = n.elem.elem.borrow();
+            if !iter::above_lower(start, elem) {
+                node = n.right;
+            } else if !iter::below_upper(end, elem) {
+                node = n.left;
+            } else {
+                let left = self.suffix_summary(n.left, start);
+                let right = self.prefix_summary(n.right, end);
+                return Some(combine_around::<T, A>(left, &n.elem.elem, right));
+            }
+        }
+        None
+    }
+
+    fn subtree_summary(&self, node: Option<Ptr>) -> Option<A::Summary> {
+        node.map(|ptr| self.tree.deref(&ptr).elem.summary.clone())
+    }
+
+    // Summarizes the elements of the subtree above the lower bound.
+    fn suffix_summary<Q: ?Sized + Ord>(&self, node: Option<Ptr>, start: Bound<&Q>) -> Option<A::Summary> where T: Borrow<Q> {
+        let n = self.tree.deref(&node?);
+        if iter::above_lower(start, n.elem.elem.borrow()) {
+            let left = self.suffix_summary(n.left, start);
+            Some(combine_around::<T, A>(left, &n.elem.elem, self.subtree_summary(n.right)))
+        } else {
+            self.suffix_summary(n.right, start)
+        }
+    }
+
+    // Summarizes the elements of the subtree below the upper bound.
+    fn prefix_summary<Q: ?Sized + Ord>(&self, node: Option<Ptr>, end: Bound<&Q>) -> Option<A::Summary> where T: Borrow<Q> {
+        let n = self.tree.deref(&node?);
+        if iter::below_upper(end, n.elem.elem.borrow()) {
+            let right = self.prefix_summary(n.right, end);
+            Some(combine_around::<T, A>(self.subtree_summary(n.left), &n.elem.elem, right))
+        } else {
+            self.prefix_summary(n.left, end)
+        }
+    }
+}
+
+impl<T: Clone, A: Augment<T>> Clone for AugmentedBST<T, A> {
+    fn clone(&self) -> Self {
+        AugmentedBST { tree: self.tree.clone(), marker: PhantomData }
+    }
+}
+
+impl<T: Ord + fmt::Debug, A: Augment<T>> fmt::Debug for AugmentedBST<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+pub struct Iter<'a, T: 'a, S: 'a> {
+    inner: iter::Iter<'a, Annotated<T, S>>,
+}
+
+impl<'a, T, S> Iterator for Iter<'a, T, S> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next().map(|e| &e.elem)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T, S> DoubleEndedIterator for Iter<'a, T, S> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.inner.next_back().map(|e| &e.elem)
+    }
+}
+
+impl<'a, T, S> ExactSizeIterator for Iter<'a, T, S> {}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Borrow;
+    use std::cmp::Ordering;
+    use std::vec::Vec;
+
+    use super::{Augment, AugmentedBST};
+
+    // Ordered by key alone, so that inserting an entry with a known key
+    // replaces its value.
+    #[derive(Debug, Clone, Copy)]
+    struct Entry {
+        key: u32,
+        value: i64,
+    }
+
+    impl PartialEq for Entry {
+        fn eq(&self, other: &Self) -> bool {
+            self.key == other.key
+        }
+    }
+
+    impl Eq for Entry {}
+
+    impl PartialOrd for Entry {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Entry {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.key.cmp(&other.key)
+        }
+    }
+
+    impl Borrow<u32> for Entry {
+        fn borrow(&self) -> &u32 {
+            &self.key
+        }
+    }
+
+    // Sums the values and concatenates the keys, so that any misordered
+    // combination shows up.
+    struct SumAndKeys;
+
+    impl Augment<Entry> for SumAndKeys {
+        type Summary = (i64, Vec<u32>);
+
+        fn summarize(elem: &Entry) -> (i64, Vec<u32>) {
+            (elem.value, vec![elem.key])
+        }
+
+        fn combine(left: &(i64, Vec<u32>), right: &(i64, Vec<u32>)) -> (i64, Vec<u32>) {
+            (left.0 + right.0, left.1.iter().chain(&right.1).copied().collect())
+        }
+    }
+
+    #[test]
+    fn summaries() {
+        let mut tree: AugmentedBST<Entry, SumAndKeys> = AugmentedBST::new();
+        let mut seed = 3u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) % 200
+        };
+        for _ in 0..300 {
+            let key = next();
+            let value = next() as i64 - 100;
+            tree.insert(Entry { key, value });
+            if next() % 4 == 0 {
+                let key = next();
+                let present = tree.contains(&key);
+                assert_eq!(tree.remove(&key).is_some(), present);
+            }
+        }
+        let mut elems: Vec<Entry> = tree.iter().copied().collect();
+        assert_eq!(elems.len(), tree.len());
+        let expected = |elems: &[Entry], lo: u32, hi: u32| {
+            let within: Vec<_> = elems.iter().filter(|e| lo <= e.key && e.key < hi).collect();
+            if within.is_empty() {
+                None
+            } else {
+                Some((within.iter().map(|e| e.value).sum(), within.iter().map(|e| e.key).collect()))
+            }
+        };
+        assert_eq!(tree.summary().cloned(), expected(&elems, 0, 200));
+        for &(lo, hi) in &[(0, 200), (10, 20), (50, 51), (199, 300), (30, 30)] {
+            assert_eq!(tree.range_summary(lo..hi), expected(&elems, lo, hi));
+        }
+
+        // Replacing an element must refresh the summaries above it.
+        let middle = elems.len() / 2;
+        elems[middle].value += 1000;
+        assert!(tree.insert(elems[middle]).is_some());
+        assert_eq!(tree.summary().cloned(), expected(&elems, 0, 200));
+        assert_eq!(tree.range_summary::<u32, _>(..), expected(&elems, 0, 200));
+
+        tree.clear();
+        assert_eq!(tree.summary(), None);
+        assert_eq!(tree.range_summary::<u32, _>(..), None);
+    }
+}
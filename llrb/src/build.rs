@@ -0,0 +1,200 @@
+use core::cmp::Reverse;
+
+use alloc::collections::{BTreeSet, BinaryHeap};
+use alloc::vec::Vec;
+
+use super::{BST, Color, Node, Ptr};
+use iter::IntoIter;
+
+impl<T: Ord> BST<T> {
+    pub fn from_sorted_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut elems: Vec<T> = iter.into_iter().collect();
+        assert!(elems.windows(2).all(|w| w[0] <= w[1]), "from_sorted_iter: input is not sorted");
+        elems.dedup();
+        BST::from_sorted_vec(elems)
+    }
+
+    // Merges many trees into one, such as per-shard indexes, by a k-way
+    // merge of their elements through a heap holding the next element of
+    // each, then building the result bottom up, in O(n log k) time. Of
+    // several equal elements, the one from the earliest tree is kept.
+    pub fn merge_all<I: IntoIterator<Item = BST<T>>>(trees: I) -> Self {
+        let mut streams: Vec<IntoIter<T>> = trees.into_iter().map(BST::into_iter).collect();
+        let mut elems = Vec::with_capacity(streams.iter().map(|s| s.len()).sum());
+        let mut heap = BinaryHeap::with_capacity(streams.len());
+        for (i, stream) in streams.iter_mut().enumerate() {
+            heap.extend(stream.next().map(|elem| Reverse((elem, i))));
+        }
+        while let Some(Reverse((elem, i))) = heap.pop() {
+            heap.extend(streams[i].next().map(|elem| Reverse((elem, i))));
+            if elems.last() != Some(&elem) {
+                elems.push(elem);
+            }
+        }
+        BST::from_sorted_vec(elems)
+    }
+
+    // Builds a tree from strictly increasing elements in linear time.
+    pub(crate) fn from_sorted_vec(elems: Vec<T>) -> Self {
+        let len = elems.len();
+        let mut tree = BST::new();
+        tree.nodes = elems.into_iter().map(|elem| Some(Node::new(elem, Color::Black))).collect();
+        tree.root = tree.link_sorted(&mut (0..len).map(Ptr::new), len);
+        tree
+    }
+
+    // Links existing nodes, given in order, into a balanced tree and returns
+    // its root. Any previous links between the nodes are overwritten.
+    pub(crate) fn link_sorted<I: Iterator<Item = Ptr>>(&mut self, nodes: &mut I, len: usize) -> Option<Ptr> {
+        // The largest black height for which a tree of all 2-nodes would
+        // not exceed the number of elements.
+        let mut height = 0;
+        while (2usize << height) - 1 <= len {
+            height += 1;
+        }
+        self.build(nodes, len, height)
+    }
+
+    fn link_node(&mut self, ptr: Ptr, color: Color, left: Option<Ptr>, right: Option<Ptr>) -> Ptr {
+        let node = self.deref_mut(&ptr);
+        node.color = color;
+        node.left = left;
+        node.right = right;
+        self.update_size(ptr);
+        ptr
+    }
+
+    // Builds a subtree with the given number of elements and black height.
+    // Such a subtree holds between 2^height - 1 elements (all 2-nodes) and
+    // 3^height - 1 elements (all 3-nodes). Nodes are made 2-nodes whenever
+    // possible, so a red node only appears where the size demands it.
+    fn build<I: Iterator<Item = Ptr>>(&mut self, nodes: &mut I, size: usize, height: usize) -> Option<Ptr> {
+        if size == 0 {
+            return None;
+        }
+        let max_child = 3usize.saturating_pow(height as u32 - 1) - 1;
+        if size - 1 <= 2 * max_child {
+            let left_size = size / 2;
+            let left = self.build(nodes, left_size, height - 1);
+            let node = nodes.next().expect("build runs out of nodes");
+            let right = self.build(nodes, size - 1 - left_size, height - 1);
+            Some(self.link_node(node, Color::Black, left, right))
+        } else {
+            let rest = size - 2;
+            let (a, b) = (rest.div_ceil(3), (rest + 1) / 3);
+            let first = self.build(nodes, a, height - 1);
+            let red_node = nodes.next().expect("build runs out of nodes");
+            let second = self.build(nodes, b, height - 1);
+            let red = self.link_node(red_node, Color::Red, first, second);
+            let black_node = nodes.next().expect("build runs out of nodes");
+            let third = self.build(nodes, rest - a - b, height - 1);
+            Some(self.link_node(black_node, Color::Black, Some(red), third))
+        }
+    }
+
+    // Rebuilds the tree into the shape from_sorted_iter gives it, with the
+    // least black height possible and red nodes only where the number of
+    // elements demands them, undoing whatever an unlucky order of updates
+    // left behind. The nodes are laid out in order in an arena with no free
+    // slots or spare capacity. Takes linear time and invalidates handles.
+    pub fn rebuild(&mut self) {
+        let len = self.len();
+        let mut elems = Vec::with_capacity(len);
+        let root = self.root;
+        self.free_into(root, &mut elems);
+        self.nodes.clear();
+        self.deleted_indices.clear();
+        self.deleted_indices.shrink_to_fit();
+        self.nodes.shrink_to(len);
+        self.nodes.extend(elems.into_iter().map(|elem| Some(Node::new(elem, Color::Black))));
+        self.root = self.link_sorted(&mut (0..len).map(Ptr::new), len);
+    }
+
+    // Builds a tree of the images of the elements. Elements that map to
+    // equal values collapse into one, the image of the smallest.
+    pub fn map<U: Ord, F: FnMut(&T) -> U>(&self, f: F) -> BST<U> {
+        BST::from(self.iter().map(f).collect::<Vec<U>>())
+    }
+
+    // Like map, but for a strictly increasing f, so that the images are in
+    // the same order and the node structure can be copied verbatim without
+    // sorting or rebalancing. Debug builds check that f is increasing.
+    pub fn map_monotone<U: Ord, F: FnMut(&T) -> U>(&self, mut f: F) -> BST<U> {
+        let mut mapped = BST::new();
+        mapped.nodes = self.nodes.iter().map(|node| node.as_ref().map(|node| node.with_elem(f(&node.elem)))).collect();
+        mapped.root = self.root;
+        mapped.deleted_indices = self.deleted_indices.clone();
+        debug_assert!(mapped.iter().zip(mapped.iter().skip(1)).all(|(a, b)| a < b), "map_monotone: f is not strictly increasing");
+        mapped
+    }
+
+    // Splits the tree into the elements for which pred holds and the rest.
+    // The first tree keeps this tree's arena and the second gets a fresh
+    // one; either way the nodes are relinked rather than reinserted, so the
+    // whole split takes linear time.
+    pub fn partition<F: FnMut(&T) -> bool>(mut self, mut pred: F) -> (BST<T>, BST<T>) {
+        let mut matching = Vec::new();
+        let mut rest = BST::new();
+        let mut stack = Vec::new();
+        let mut node = self.root;
+        loop {
+            while let Some(ptr) = node {
+                stack.push(ptr);
+                node = self.deref(&ptr).left;
+            }
+            let ptr = match stack.pop() {
+                Some(ptr) => ptr,
+                None => break,
+            };
+            node = self.deref(&ptr).right;
+            if pred(&self.deref(&ptr).elem) {
+                matching.push(ptr);
+            } else {
+                self.deleted_indices.push(ptr);
+                let mut moved = self.nodes[ptr.index()].take().expect("partition encounters a reference to a deleted node");
+                moved.stamp(Ptr::new(rest.nodes.len()));
+                rest.nodes.push(Some(moved));
+            }
+        }
+        let len = matching.len();
+        self.root = self.link_sorted(&mut matching.into_iter(), len);
+        let len = rest.nodes.len();
+        rest.root = rest.link_sorted(&mut (0..len).map(Ptr::new), len);
+        (self, rest)
+    }
+}
+
+// Sorting is linear on already sorted input, so every conversion here takes
+// linear time when the source is ordered. Of several equal elements, the
+// first is kept, as with from_sorted_iter.
+impl<T: Ord> From<Vec<T>> for BST<T> {
+    fn from(mut elems: Vec<T>) -> Self {
+        elems.sort();
+        elems.dedup();
+        BST::from_sorted_vec(elems)
+    }
+}
+
+impl<T: Ord, const N: usize> From<[T; N]> for BST<T> {
+    fn from(elems: [T; N]) -> Self {
+        BST::from(Vec::from(elems))
+    }
+}
+
+impl<T: Ord> From<BTreeSet<T>> for BST<T> {
+    fn from(set: BTreeSet<T>) -> Self {
+        BST::from_sorted_vec(set.into_iter().collect())
+    }
+}
+
+impl<T: Ord> From<BST<T>> for Vec<T> {
+    fn from(tree: BST<T>) -> Self {
+        tree.into_sorted_vec()
+    }
+}
+
+impl<T: Ord> From<BST<T>> for BTreeSet<T> {
+    fn from(tree: BST<T>) -> Self {
+        tree.into_iter().collect()
+    }
+}
@@ -0,0 +1,84 @@
+use core::cmp::Ordering;
+use core::fmt;
+
+use allocator_api2::vec::Vec as ArenaVec;
+
+use super::BST;
+use iter::Iter;
+
+// A tree ordered by a comparator supplied at construction rather than by
+// Ord, for elements that have no natural order or need a different one.
+#[derive(Clone)]
+pub struct BSTBy<T, F> {
+    tree: BST<T>,
+    cmp: F,
+}
+
+impl<T, F: Fn(&T, &T) -> Ordering> BSTBy<T, F> {
+    pub fn new(cmp: F) -> Self {
+        BSTBy { tree: BST { nodes: ArenaVec::new(), root: None, deleted_indices: ArenaVec::new(), augment: None }, cmp }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear()
+    }
+
+    pub fn contains(&self, elem: &T) -> bool {
+        self.get(elem).is_some()
+    }
+
+    pub fn get(&self, elem: &T) -> Option<&T> {
+        let cmp = &self.cmp;
+        self.tree.find_by(|e| cmp(elem, e)).map(|ptr| &self.tree.deref(&ptr).elem)
+    }
+
+    // Inserts the element, replacing and returning any element that
+    // compares equal.
+    pub fn insert(&mut self, elem: T) -> Option<T> {
+        self.tree.insert_by(elem, &self.cmp)
+    }
+
+    pub fn remove(&mut self, elem: &T) -> Option<T> {
+        let cmp = &self.cmp;
+        self.tree.delete_by(|e| cmp(elem, e))
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.iter().next()
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        let mut node = self.tree.root?;
+        while let Some(right) = self.tree.deref(&node).right {
+            node = right;
+        }
+        Some(&self.tree.deref(&node).elem)
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter::new(&self.tree)
+    }
+}
+
+impl<T: fmt::Debug, F: Fn(&T, &T) -> Ordering> fmt::Debug for BSTBy<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T, F: Fn(&T, &T) -> Ordering> IntoIterator for &'a BSTBy<T, F> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
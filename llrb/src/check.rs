@@ -0,0 +1,149 @@
+use core::fmt;
+
+use alloc::vec::Vec;
+
+use allocator_api2::alloc::Allocator;
+
+use super::{BST, Color, NodeIndex, Ptr};
+
+// Describes the first inconsistency found in a tree. The slot of the
+// offending node is included where there is one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CorruptionError {
+    // A link refers to a slot past the end of the arena.
+    OutOfBounds(usize),
+    // A link refers to a slot whose node has been deleted.
+    Deleted(usize),
+    // A link refers to a slot that has been reused since the link was made.
+    // Only debug builds can tell.
+    Stale(usize),
+    // A node is reachable along more than one path.
+    Shared(usize),
+    // A child does not point back to its parent. Only checked with the
+    // parent-pointers feature.
+    WrongParent(usize),
+    // A rotation or color flip finds a child missing that the balance of
+    // the tree says is there.
+    MissingChild(usize),
+    OutOfOrder(usize),
+    RedRoot,
+    RedRightChild(usize),
+    ConsecutiveReds(usize),
+    UnequalBlackHeights(usize),
+    WrongSize(usize),
+    // The number of live slots disagrees with the size of the tree, or the
+    // free list disagrees with the empty slots.
+    WrongLength,
+}
+
+impl fmt::Display for CorruptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CorruptionError::OutOfBounds(i) => write!(f, "a reference past the end of the arena at slot {}", i),
+            CorruptionError::Deleted(i) => write!(f, "a reference to a deleted node at slot {}", i),
+            CorruptionError::Stale(i) => write!(f, "a stale reference to a reused node at slot {}", i),
+            CorruptionError::Shared(i) => write!(f, "a node reachable along two paths at slot {}", i),
+            CorruptionError::WrongParent(i) => write!(f, "a wrong parent pointer at slot {}", i),
+            CorruptionError::MissingChild(i) => write!(f, "a missing child below slot {}", i),
+            CorruptionError::OutOfOrder(i) => write!(f, "an element out of order at slot {}", i),
+            CorruptionError::RedRoot => write!(f, "a red root"),
+            CorruptionError::RedRightChild(i) => write!(f, "a red right child at slot {}", i),
+            CorruptionError::ConsecutiveReds(i) => write!(f, "two red nodes in a row at slot {}", i),
+            CorruptionError::UnequalBlackHeights(i) => write!(f, "unequal black heights below slot {}", i),
+            CorruptionError::WrongSize(i) => write!(f, "a stale subtree size at slot {}", i),
+            CorruptionError::WrongLength => write!(f, "a length that disagrees with the arena"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for CorruptionError {}
+
+impl<T: Ord, A: Allocator, I: NodeIndex> BST<T, A, I> {
+    // Verifies every invariant of the tree without panicking, so that a
+    // corrupted tree can be reported and discarded instead of aborting on
+    // the next operation that runs into the corruption.
+    pub fn try_self_check(&self) -> Result<(), CorruptionError> {
+        let mut seen = vec![false; self.nodes.len()];
+        let len = self.check_subtree(self.root, &mut seen)?;
+        if len != self.len() {
+            return Err(CorruptionError::WrongLength);
+        }
+        self.check_arena(len)
+    }
+
+    // Checks the tree rooted at the given node, marking its slots as seen,
+    // and returns its number of nodes. Slots already marked count as shared
+    // with another tree.
+    pub(crate) fn check_subtree(&self, root: Option<Ptr<I>>, seen: &mut [bool]) -> Result<usize, CorruptionError> {
+        if self.is_red(&root.filter(|root| self.try_deref(root).is_ok())) {
+            return Err(CorruptionError::RedRoot);
+        }
+
+        // Walk down from the root, checking links, order and colors. Each
+        // slot is visited at most once, so a cycle cannot trap the walk.
+        let mut preorder = Vec::new();
+        let mut stack: Vec<_> = root.map(|root| (root, None, None)).into_iter().collect();
+        while let Some((ptr, lo, hi)) = stack.pop() {
+            let node = self.try_deref(&ptr)?;
+            if seen[ptr.index()] {
+                return Err(CorruptionError::Shared(ptr.index()));
+            }
+            seen[ptr.index()] = true;
+            preorder.push(ptr);
+            if lo.is_some_and(|lo| self.deref(&lo).elem >= node.elem) || hi.is_some_and(|hi| node.elem >= self.deref(&hi).elem) {
+                return Err(CorruptionError::OutOfOrder(ptr.index()));
+            }
+            for child in node.left.iter().chain(&node.right) {
+                self.try_deref(child)?;
+            }
+            #[cfg(feature = "parent-pointers")]
+            for child in node.left.iter().chain(&node.right) {
+                if self.deref(child).parent != Some(ptr) {
+                    return Err(CorruptionError::WrongParent(child.index()));
+                }
+            }
+            if self.is_red(&node.right) {
+                return Err(CorruptionError::RedRightChild(ptr.index()));
+            }
+            if node.color == Color::Red && self.is_red(&node.left) {
+                return Err(CorruptionError::ConsecutiveReds(ptr.index()));
+            }
+            stack.extend(node.right.map(|right| (right, Some(ptr), hi)));
+            stack.extend(node.left.map(|left| (left, lo, Some(ptr))));
+        }
+
+        // Every child comes after its parent in preorder, so going backwards
+        // settles the children first.
+        let mut black_heights = vec![0; self.nodes.len()];
+        for ptr in preorder.iter().rev() {
+            let node = self.deref(ptr);
+            let height = |child: Option<Ptr<I>>| child.map_or(0, |child| black_heights[child.index()]);
+            let (left, right) = (height(node.left), height(node.right));
+            if left != right {
+                return Err(CorruptionError::UnequalBlackHeights(ptr.index()));
+            }
+            if node.size != 1 + self.size(&node.left) + self.size(&node.right) {
+                return Err(CorruptionError::WrongSize(ptr.index()));
+            }
+            black_heights[ptr.index()] = if node.color == Color::Black { left + 1 } else { left };
+        }
+        Ok(preorder.len())
+    }
+
+    // Checks that the free list and the occupied slots agree with each
+    // other and with the number of nodes that the trees hold.
+    pub(crate) fn check_arena(&self, live: usize) -> Result<(), CorruptionError> {
+        let mut freed = vec![false; self.nodes.len()];
+        for ptr in &self.deleted_indices {
+            match self.nodes.get(ptr.index()) {
+                Some(None) if !freed[ptr.index()] => freed[ptr.index()] = true,
+                _ => return Err(CorruptionError::WrongLength),
+            }
+        }
+        if self.nodes.iter().filter(|node| node.is_some()).count() != live {
+            return Err(CorruptionError::WrongLength);
+        }
+        Ok(())
+    }
+}
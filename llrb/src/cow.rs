@@ -0,0 +1,393 @@
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt;
+use core::iter;
+use core::mem;
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use allocator_api2::vec::Vec as ArenaVec;
+
+use super::{BST, Color, Ptr, Side};
+
+// A persistent left-leaning red-black tree whose nodes are shared between
+// clones behind Arcs, so that a clone is constant time. A mutation copies
+// only the nodes that it walks through or rotates and that a clone still
+// shares, which is a logarithmic number of them; the rest of the tree
+// stays shared. That suits a series of versions each differing from the
+// last by a few elements, such as snapshots taken for consistent reads
+// while the tree keeps changing. Copying a node clones its element, hence
+// the Clone bound.
+pub struct SharedBST<T> {
+    root: Link<T>,
+    len: usize,
+}
+
+type Link<T> = Option<Arc<Node<T>>>;
+
+#[derive(Clone)]
+struct Node<T> {
+    elem: T,
+    color: Color,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+fn is_red<T>(link: &Link<T>) -> bool {
+    link.as_ref().is_some_and(|node| node.color == Color::Red)
+}
+
+// The functions below take nodes by value and return the node now at the
+// top of their subtree. Arc::make_mut copies a node only if a clone
+// still shares it.
+
+fn rotate_left<T: Clone>(mut h: Arc<Node<T>>) -> Arc<Node<T>> {
+    let n = Arc::make_mut(&mut h);
+    let mut x = n.right.take().expect("rotate_left: right child is missing");
+    let m = Arc::make_mut(&mut x);
+    n.right = m.left.take();
+    m.color = n.color;
+    n.color = Color::Red;
+    m.left = Some(h);
+    x
+}
+
+fn rotate_right<T: Clone>(mut h: Arc<Node<T>>) -> Arc<Node<T>> {
+    let n = Arc::make_mut(&mut h);
+    let mut x = n.left.take().expect("rotate_right: left child is missing");
+    let m = Arc::make_mut(&mut x);
+    n.left = m.right.take();
+    m.color = n.color;
+    n.color = Color::Red;
+    m.right = Some(h);
+    x
+}
+
+fn recolor<T: Clone>(node: &mut Arc<Node<T>>) {
+    let n = Arc::make_mut(node);
+    n.color = !n.color;
+}
+
+fn move_red_up_or_down<T: Clone>(h: &mut Arc<Node<T>>) {
+    let n = Arc::make_mut(h);
+    n.color = !n.color;
+    recolor(n.left.as_mut().expect("move_red_up_or_down: left child is missing"));
+    recolor(n.right.as_mut().expect("move_red_up_or_down: right child is missing"));
+}
+
+fn fixup<T: Clone>(mut node: Arc<Node<T>>) -> Arc<Node<T>> {
+    if is_red(&node.right) && !is_red(&node.left) {
+        node = rotate_left(node);
+    }
+    if is_red(&node.left) && is_red(&node.left.as_ref().unwrap().left) {
+        node = rotate_right(node);
+    }
+    if is_red(&node.left) && is_red(&node.right) {
+        move_red_up_or_down(&mut node);
+    }
+    node
+}
+
+fn move_red_left<T: Clone>(mut h: Arc<Node<T>>) -> Arc<Node<T>> {
+    move_red_up_or_down(&mut h);
+    if is_red(&h.right.as_ref().unwrap().left) {
+        let n = Arc::make_mut(&mut h);
+        let right = n.right.take().unwrap();
+        n.right = Some(rotate_right(right));
+        h = rotate_left(h);
+        move_red_up_or_down(&mut h);
+    }
+    h
+}
+
+fn move_red_right<T: Clone>(mut h: Arc<Node<T>>) -> Arc<Node<T>> {
+    move_red_up_or_down(&mut h);
+    if is_red(&h.left.as_ref().unwrap().left) {
+        h = rotate_right(h);
+        move_red_up_or_down(&mut h);
+    }
+    h
+}
+
+// Walks back up a path recorded during a descent, as BST::fixup_path
+// does. The nodes on the path are no longer shared, since the descent
+// detached each of them from its child.
+fn fixup_path<T: Clone>(path: Vec<(Arc<Node<T>>, Side)>, mut subtree: Link<T>) -> Link<T> {
+    for (mut parent, side) in path.into_iter().rev() {
+        let n = Arc::make_mut(&mut parent);
+        match side {
+            Side::Left => n.left = subtree,
+            Side::Right => n.right = subtree,
+        }
+        subtree = Some(fixup(parent));
+    }
+    subtree
+}
+
+// Takes the element out of a node that has been unlinked from the tree,
+// cloning it if a clone still holds the node.
+fn into_elem<T: Clone>(node: Arc<Node<T>>) -> T {
+    Arc::try_unwrap(node).map(|n| n.elem).unwrap_or_else(|node| node.elem.clone())
+}
+
+fn take_min<T: Clone>(mut node: Arc<Node<T>>) -> (T, Link<T>) {
+    let mut path = Vec::new();
+    loop {
+        let two_node = match node.left {
+            None => break,
+            Some(ref left) => left.color == Color::Black && !is_red(&left.left),
+        };
+        if two_node {
+            node = move_red_left(node);
+        }
+        let left = Arc::make_mut(&mut node).left.take().unwrap();
+        path.push((node, Side::Left));
+        node = left;
+    }
+    // The minimum has no left child, and so no right child either.
+    (into_elem(node), fixup_path(path, None))
+}
+
+// Moves the nodes of a tree over without changing its shape.
+fn adopt<T>(nodes: &mut ArenaVec<Option<super::Node<T>>>, ptr: Option<Ptr>) -> Link<T> {
+    ptr.map(|ptr| {
+        let node = nodes[ptr.index()].take().expect("adopt encounters a reference to a deleted node");
+        let (left, right) = (adopt(nodes, node.left), adopt(nodes, node.right));
+        Arc::new(Node { elem: node.elem, color: node.color, left, right })
+    })
+}
+
+#[allow(clippy::new_without_default)]
+impl<T: Ord + Clone> SharedBST<T> {
+    pub fn new() -> Self {
+        SharedBST { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn contains<Q: ?Sized + Ord>(&self, elem: &Q) -> bool where T: Borrow<Q> {
+        let mut node = &self.root;
+        while let Some(ref n) = *node {
+            node = match elem.cmp(n.elem.borrow()) {
+                Ordering::Less => &n.left,
+                Ordering::Greater => &n.right,
+                Ordering::Equal => return true,
+            };
+        }
+        false
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        let mut stack: Vec<&Arc<Node<T>>> = Vec::new();
+        let mut node = self.root.as_ref();
+        iter::from_fn(move || {
+            while let Some(n) = node {
+                stack.push(n);
+                node = n.left.as_ref();
+            }
+            let n = stack.pop()?;
+            node = n.right.as_ref();
+            Some(&n.elem)
+        })
+    }
+
+    // Copies the elements into a tree of its own.
+    pub fn to_tree(&self) -> BST<T> {
+        BST::from_sorted_iter(self.iter().cloned())
+    }
+
+    fn set_root(&mut self, root: Link<T>) {
+        self.root = root.map(|mut root| {
+            if root.color == Color::Red {
+                Arc::make_mut(&mut root).color = Color::Black;
+            }
+            root
+        });
+    }
+
+    pub fn insert(&mut self, elem: T) -> Option<T> {
+        let mut path = Vec::new();
+        let mut link = self.root.take();
+        while let Some(mut node) = link {
+            let n = Arc::make_mut(&mut node);
+            let side = match elem.cmp(&n.elem) {
+                Ordering::Less => Side::Left,
+                Ordering::Greater => Side::Right,
+                Ordering::Equal => {
+                    let old = mem::replace(&mut n.elem, elem);
+                    self.set_root(fixup_path(path, Some(node)));
+                    return Some(old);
+                },
+            };
+            link = match side {
+                Side::Left => n.left.take(),
+                Side::Right => n.right.take(),
+            };
+            path.push((node, side));
+        }
+        let new = Arc::new(Node { elem, color: Color::Red, left: None, right: None });
+        self.set_root(fixup_path(path, Some(new)));
+        self.len += 1;
+        None
+    }
+
+    pub fn delete<Q: ?Sized + Ord>(&mut self, elem: &Q) -> Option<T> where T: Borrow<Q> {
+        // An absent element leaves the tree alone, so there is no need to
+        // copy any of it.
+        if !self.contains(elem) {
+            return None;
+        }
+        let mut path = Vec::new();
+        let mut node = self.root.take().unwrap();
+        let (removed, subtree) = loop {
+            if elem.cmp(node.elem.borrow()) == Ordering::Less {
+                let left = node.left.as_ref().expect("delete: element is missing from the tree");
+                if left.color == Color::Black && !is_red(&left.left) {
+                    node = move_red_left(node);
+                }
+                let left = Arc::make_mut(&mut node).left.take().unwrap();
+                path.push((node, Side::Left));
+                node = left;
+                continue;
+            }
+            if is_red(&node.left) {
+                node = rotate_right(node);
+            }
+            if elem.cmp(node.elem.borrow()) == Ordering::Equal && node.right.is_none() {
+                // A leaf, as in BST::delete_impl.
+                break (into_elem(node), None);
+            }
+            let right = node.right.as_ref().expect("delete: element is missing from the tree");
+            if right.color == Color::Black && !is_red(&right.left) {
+                node = move_red_right(node);
+            }
+            let n = Arc::make_mut(&mut node);
+            let right = n.right.take().unwrap();
+            if elem.cmp(n.elem.borrow()) == Ordering::Equal {
+                // Replace the element with its successor, which is taken
+                // out of the right subtree.
+                let (successor, rest) = take_min(right);
+                let removed = mem::replace(&mut n.elem, successor);
+                path.push((node, Side::Right));
+                break (removed, rest);
+            }
+            path.push((node, Side::Right));
+            node = right;
+        };
+        self.set_root(fixup_path(path, subtree));
+        self.len -= 1;
+        Some(removed)
+    }
+}
+
+impl<T> Clone for SharedBST<T> {
+    fn clone(&self) -> Self {
+        SharedBST { root: self.root.clone(), len: self.len }
+    }
+}
+
+impl<T: Ord + Clone + fmt::Debug> fmt::Debug for SharedBST<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+// Keeps the shape of the tree, so that no rebalancing is needed.
+impl<T: Ord + Clone> From<BST<T>> for SharedBST<T> {
+    fn from(mut tree: BST<T>) -> Self {
+        let len = tree.len();
+        let root = tree.root.take();
+        SharedBST { root: adopt(&mut tree.nodes, root), len }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use std::collections::BTreeSet;
+    use std::vec::Vec;
+
+    use super::{is_red, Link, SharedBST};
+    use {BST, Color};
+
+    // Checks the order and the balance of a subtree, returning its black
+    // height.
+    fn check<T: Ord>(link: &Link<T>, lower: Option<&T>, upper: Option<&T>) -> usize {
+        let n = match *link {
+            None => return 1,
+            Some(ref n) => n,
+        };
+        assert!(lower.is_none_or(|lower| *lower < n.elem) && upper.is_none_or(|upper| n.elem < *upper));
+        assert!(!is_red(&n.right));
+        assert!(!(n.color == Color::Red && is_red(&n.left)));
+        let left = check(&n.left, lower, Some(&n.elem));
+        assert_eq!(left, check(&n.right, Some(&n.elem), upper));
+        left + (n.color == Color::Black) as usize
+    }
+
+    // Counts the nodes that no clone shares, stopping at shared ones since
+    // everything under them is shared as well.
+    fn unshared<T>(link: &Link<T>) -> usize {
+        match *link {
+            Some(ref n) if Arc::strong_count(n) == 1 => 1 + unshared(&n.left) + unshared(&n.right),
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn copy_on_write() {
+        let mut tree = SharedBST::from(BST::from_sorted_iter(0..1000u32));
+        check(&tree.root, None, None);
+        let snapshot = tree.clone();
+        assert_eq!(unshared(&tree.root), 0);
+        assert_eq!(tree.delete(&1000), None);
+        assert_eq!(unshared(&tree.root), 0);
+
+        assert_eq!(tree.insert(1000), None);
+        assert!(unshared(&tree.root) <= 30);
+        assert_eq!(tree.delete(&0), Some(0));
+        assert_eq!(tree.insert(500), Some(500));
+        assert!(unshared(&tree.root) <= 90);
+        assert_eq!((tree.len(), snapshot.len()), (1000, 1000));
+        assert!(snapshot.contains(&0) && !snapshot.contains(&1000));
+        check(&tree.root, None, None);
+        assert!(snapshot.iter().cloned().eq(0..1000));
+        assert!(tree.to_tree().into_iter().eq((1..1000).chain(Some(1000))));
+    }
+
+    #[test]
+    fn versions() {
+        let mut tree = SharedBST::new();
+        let mut model = BTreeSet::new();
+        let mut versions = Vec::new();
+        for i in 0..4000u32 {
+            let x = i.wrapping_mul(2654435761) % 700;
+            if i % 3 == 0 {
+                assert_eq!(tree.delete(&x), model.take(&x));
+            } else {
+                assert_eq!(tree.insert(x), model.replace(x));
+            }
+            if i % 400 == 0 {
+                versions.push((tree.clone(), model.clone()));
+            }
+        }
+        versions.push((tree, model));
+        for (tree, model) in &versions {
+            check(&tree.root, None, None);
+            assert_eq!(tree.len(), model.len());
+            assert!(tree.iter().eq(model.iter()));
+        }
+        let (mut tree, model) = versions.pop().unwrap();
+        for x in model {
+            assert_eq!(tree.delete(&x), Some(x));
+        }
+        assert!(tree.is_empty() && tree.root.is_none());
+    }
+}
@@ -0,0 +1,208 @@
+use core::cmp::Ordering;
+
+use alloc::vec::Vec;
+
+use super::{BST, Ptr};
+
+// A cursor is either at an element or at the "ghost" position, which sits
+// past the last element and before the first one.
+pub struct CursorMut<'a, T> {
+    tree: &'a mut BST<T>,
+    // Path from the root down to the current node; empty at the ghost.
+    path: Vec<Ptr>,
+    // Rank of the current element, or the length of the tree at the ghost.
+    index: usize,
+}
+
+impl<'a, T: Ord> CursorMut<'a, T> {
+    pub(crate) fn new(tree: &'a mut BST<T>, index: usize) -> Self {
+        let mut cursor = CursorMut { tree, path: Vec::new(), index: 0 };
+        cursor.seek(index);
+        cursor
+    }
+
+    // Rebuilds the path to the element with the given rank. This is needed
+    // after every modification since rebalancing reshapes the tree.
+    fn seek(&mut self, index: usize) {
+        self.path.clear();
+        self.index = index;
+        let mut k = index;
+        let mut node = self.tree.root;
+        while let Some(ptr) = node {
+            self.path.push(ptr);
+            let n = self.tree.deref(&ptr);
+            let left_size = self.tree.size(&n.left);
+            match k.cmp(&left_size) {
+                Ordering::Less => node = n.left,
+                Ordering::Greater => {
+                    k -= left_size + 1;
+                    node = n.right;
+                },
+                Ordering::Equal => return,
+            }
+        }
+        self.path.clear();
+        self.index = self.tree.len();
+    }
+
+    pub fn current(&self) -> Option<&T> {
+        self.path.last().map(|ptr| &self.tree.deref(ptr).elem)
+    }
+
+    // Only for wrappers whose elements carry data that takes no part in
+    // their order, and that must not change the order of the element.
+    pub(crate) fn current_mut(&mut self) -> Option<&mut T> {
+        let ptr = *self.path.last()?;
+        Some(&mut self.tree.deref_mut(&ptr).elem)
+    }
+
+    pub fn index(&self) -> Option<usize> {
+        self.path.last().map(|_| self.index)
+    }
+
+    pub fn peek_next(&self) -> Option<&T> {
+        match self.path.last() {
+            None => self.tree.select(0),
+            Some(_) => self.tree.select(self.index + 1),
+        }
+    }
+
+    pub fn peek_prev(&self) -> Option<&T> {
+        match self.path.last() {
+            None => self.tree.len().checked_sub(1).and_then(|i| self.tree.select(i)),
+            Some(_) => self.index.checked_sub(1).and_then(|i| self.tree.select(i)),
+        }
+    }
+
+    pub fn move_next(&mut self) {
+        let ptr = match self.path.last() {
+            None => return self.seek(0),
+            Some(&ptr) => ptr,
+        };
+        self.index += 1;
+        match self.tree.deref(&ptr).right {
+            Some(right) => {
+                let mut node = Some(right);
+                while let Some(p) = node {
+                    self.path.push(p);
+                    node = self.tree.deref(&p).left;
+                }
+            },
+            None => {
+                // Climb until arriving from a left child; running out of
+                // ancestors means that the last element has been passed.
+                while let Some(child) = self.path.pop() {
+                    if let Some(parent) = self.path.last() {
+                        if self.tree.deref(parent).left == Some(child) {
+                            break;
+                        }
+                    }
+                }
+            },
+        }
+    }
+
+    pub fn move_prev(&mut self) {
+        let ptr = match self.path.last() {
+            None => {
+                let len = self.tree.len();
+                return self.seek(len.wrapping_sub(1));
+            },
+            Some(&ptr) => ptr,
+        };
+        match self.tree.deref(&ptr).left {
+            Some(left) => {
+                let mut node = Some(left);
+                while let Some(p) = node {
+                    self.path.push(p);
+                    node = self.tree.deref(&p).right;
+                }
+            },
+            None => {
+                while let Some(child) = self.path.pop() {
+                    if let Some(parent) = self.path.last() {
+                        if self.tree.deref(parent).right == Some(child) {
+                            break;
+                        }
+                    }
+                }
+            },
+        }
+        if self.path.is_empty() {
+            self.index = self.tree.len();
+        } else {
+            self.index -= 1;
+        }
+    }
+
+    // Removes the current element, moving the cursor to the next one.
+    pub fn remove_current(&mut self) -> Option<T> {
+        self.path.last()?;
+        let removed = self.tree.delete_at(self.index);
+        let index = self.index;
+        self.seek(index);
+        removed
+    }
+
+    pub fn insert_before(&mut self, elem: T) {
+        assert!(self.peek_prev().is_none_or(|prev| *prev < elem), "insert_before: element is not ordered after the previous element");
+        assert!(self.current().is_none_or(|current| elem < *current), "insert_before: element is not ordered before the current element");
+        self.tree.insert(elem);
+        let index = self.index + 1;
+        self.seek(index);
+    }
+
+    pub fn insert_after(&mut self, elem: T) {
+        assert!(self.current().is_none_or(|current| *current < elem), "insert_after: element is not ordered after the current element");
+        assert!(self.peek_next().is_none_or(|next| elem < *next), "insert_after: element is not ordered before the next element");
+        self.tree.insert(elem);
+        let index = if self.path.is_empty() { self.tree.len() } else { self.index };
+        self.seek(index);
+    }
+}
+
+// A read-only cursor, which shares the positions of CursorMut but tracks
+// only the rank of the current element since the tree cannot change.
+pub struct Cursor<'a, T> {
+    tree: &'a BST<T>,
+    // Rank of the current element, or the length of the tree at the ghost.
+    index: usize,
+}
+
+impl<'a, T> Clone for Cursor<'a, T> {
+    fn clone(&self) -> Self {
+        Cursor { tree: self.tree, index: self.index }
+    }
+}
+
+impl<'a, T: Ord> Cursor<'a, T> {
+    pub(crate) fn new(tree: &'a BST<T>, index: usize) -> Self {
+        Cursor { tree, index: index.min(tree.len()) }
+    }
+
+    pub fn current(&self) -> Option<&'a T> {
+        self.tree.select(self.index)
+    }
+
+    pub fn index(&self) -> Option<usize> {
+        if self.index < self.tree.len() { Some(self.index) } else { None }
+    }
+
+    pub fn peek_next(&self) -> Option<&'a T> {
+        if self.index == self.tree.len() { self.tree.select(0) } else { self.tree.select(self.index + 1) }
+    }
+
+    pub fn peek_prev(&self) -> Option<&'a T> {
+        self.index.checked_sub(1).and_then(|i| self.tree.select(i))
+    }
+
+    pub fn move_next(&mut self) {
+        let len = self.tree.len();
+        self.index = if self.index == len { 0 } else { self.index + 1 };
+    }
+
+    pub fn move_prev(&mut self) {
+        let len = self.tree.len();
+        self.index = if self.index == 0 { len } else { self.index - 1 };
+    }
+}
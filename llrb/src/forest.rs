@@ -0,0 +1,257 @@
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt;
+
+use alloc::vec::Vec;
+
+use super::{BST, Color, CorruptionError, Ptr};
+use iter::Iter;
+
+// Names one of the trees of a forest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TreeId(usize);
+
+// Many trees sharing a single arena, for applications that keep thousands
+// of small ordered sets and would rather not pay for an arena per set.
+// Since all the nodes live in one arena, whole trees can be merged and
+// split by relinking nodes, without moving any elements. A tree id is
+// only meaningful for the forest that handed it out, and only until the
+// tree is removed, after which the id may name a new tree.
+#[derive(Clone)]
+pub struct Forest<T> {
+    // The arena's own root is unused; each tree's root is kept in roots.
+    arena: BST<T>,
+    roots: Vec<Option<Ptr>>,
+    // The ids of removed trees, handed out again before new ones.
+    deleted_ids: Vec<TreeId>,
+}
+
+#[allow(clippy::new_without_default)]
+impl<T: Ord> Forest<T> {
+    pub fn new() -> Self {
+        Forest { arena: BST::new(), roots: Vec::new(), deleted_ids: Vec::new() }
+    }
+
+    // Reserves room for the given number of nodes across all the trees.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Forest { arena: BST::with_capacity(capacity), roots: Vec::new(), deleted_ids: Vec::new() }
+    }
+
+    // Adds an empty tree, which costs no allocation beyond its root.
+    pub fn add_tree(&mut self) -> TreeId {
+        if let Some(id) = self.deleted_ids.pop() {
+            return id;
+        }
+        self.roots.push(None);
+        TreeId(self.roots.len() - 1)
+    }
+
+    // Frees the tree's slots for other trees and its id for the next call
+    // to add_tree.
+    pub fn remove_tree(&mut self, tree: TreeId) {
+        debug_assert!(!self.deleted_ids.contains(&tree), "tree already removed");
+        self.clear(tree);
+        self.deleted_ids.push(tree);
+    }
+
+    pub fn tree_count(&self) -> usize {
+        self.roots.len() - self.deleted_ids.len()
+    }
+
+    // The ids of the trees that have not been removed, in increasing order.
+    pub fn tree_ids(&self) -> impl Iterator<Item = TreeId> + '_ {
+        let mut removed = vec![false; self.roots.len()];
+        for id in &self.deleted_ids {
+            removed[id.0] = true;
+        }
+        (0..self.roots.len()).filter(move |&i| !removed[i]).map(TreeId)
+    }
+
+    // The number of elements across all the trees.
+    pub fn total_len(&self) -> usize {
+        self.roots.iter().map(|root| self.arena.size(root)).sum()
+    }
+
+    pub fn len(&self, tree: TreeId) -> usize {
+        self.arena.size(&self.roots[tree.0])
+    }
+
+    pub fn is_empty(&self, tree: TreeId) -> bool {
+        self.roots[tree.0].is_none()
+    }
+
+    fn find<Q: ?Sized + Ord>(&self, tree: TreeId, elem: &Q) -> Option<Ptr> where T: Borrow<Q> {
+        let mut node = self.roots[tree.0];
+        while let Some(ptr) = node {
+            let n = self.arena.deref(&ptr);
+            match elem.cmp(n.elem.borrow()) {
+                Ordering::Less => node = n.left,
+                Ordering::Greater => node = n.right,
+                Ordering::Equal => return Some(ptr),
+            }
+        }
+        None
+    }
+
+    pub fn contains<Q: ?Sized + Ord>(&self, tree: TreeId, elem: &Q) -> bool where T: Borrow<Q> {
+        self.find(tree, elem).is_some()
+    }
+
+    pub fn get<Q: ?Sized + Ord>(&self, tree: TreeId, elem: &Q) -> Option<&T> where T: Borrow<Q> {
+        self.find(tree, elem).map(|ptr| &self.arena.deref(&ptr).elem)
+    }
+
+    fn set_root(&mut self, tree: TreeId, root: Option<Ptr>) {
+        if let Some(root) = root {
+            self.arena.deref_mut(&root).color = Color::Black;
+        }
+        self.roots[tree.0] = root;
+    }
+
+    // Inserts the element into the tree, replacing and returning any equal
+    // element.
+    pub fn insert(&mut self, tree: TreeId, elem: T) -> Option<T> {
+        let (root, _, replaced) = self.arena.insert_impl(self.roots[tree.0], elem, T::cmp);
+        self.set_root(tree, Some(root));
+        replaced
+    }
+
+    pub fn delete<Q: ?Sized + Ord>(&mut self, tree: TreeId, elem: &Q) -> Option<T> where T: Borrow<Q> {
+        self.find(tree, elem)?;
+        let root = self.roots[tree.0].unwrap();
+        let (removed, root) = self.arena.delete_impl(root, 0, &|arena: &BST<T>, ptr, _| elem.cmp(arena.deref(&ptr).elem.borrow()));
+        self.set_root(tree, root);
+        Some(removed)
+    }
+
+    pub fn iter(&self, tree: TreeId) -> Iter<'_, T> {
+        Iter::subtree(&self.arena, self.roots[tree.0])
+    }
+
+    // Empties the tree, returning its slots to the arena for other trees.
+    pub fn clear(&mut self, tree: TreeId) {
+        let root = self.roots[tree.0].take();
+        self.arena.free_subtree(root);
+    }
+
+    // Moves every element of one tree into another, leaving the first
+    // empty. Elements from the tree moved from take precedence, as with
+    // BST::append.
+    pub fn append(&mut self, into: TreeId, from: TreeId) {
+        if into == from {
+            return;
+        }
+        let theirs = self.roots[from.0].take();
+        let root = self.arena.union_impl(theirs, self.roots[into.0]);
+        self.set_root(into, root);
+    }
+
+    // Moves the elements not less than the key into a new tree.
+    pub fn split_off<Q: ?Sized + Ord>(&mut self, tree: TreeId, key: &Q) -> TreeId where T: Borrow<Q> {
+        let (before, found, after) = self.arena.split_by(self.roots[tree.0], &|e: &T| key.cmp(e.borrow()));
+        let after = match found {
+            Some(found) => Some(self.arena.join(None, found, after)),
+            None => after,
+        };
+        self.set_root(tree, before);
+        let new = self.add_tree();
+        self.set_root(new, after);
+        new
+    }
+
+    // Verifies every tree and that no two trees share a node.
+    pub fn try_self_check(&self) -> Result<(), CorruptionError> {
+        let mut seen = vec![false; self.arena.nodes.len()];
+        let mut live = 0;
+        for &root in &self.roots {
+            live += self.arena.check_subtree(root, &mut seen)?;
+        }
+        self.arena.check_arena(live)
+    }
+}
+
+// Lists each tree by its id, rather than the arena with its unused root.
+impl<T: Ord + fmt::Debug> fmt::Debug for Forest<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.tree_ids().map(|id| (id, DebugTree(self, id)))).finish()
+    }
+}
+
+struct DebugTree<'a, T: 'a>(&'a Forest<T>, TreeId);
+
+impl<'a, T: Ord + fmt::Debug> fmt::Debug for DebugTree<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.0.iter(self.1)).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::vec::Vec;
+
+    use super::Forest;
+
+    #[test]
+    fn many_trees() {
+        let mut forest = Forest::new();
+        let trees: Vec<_> = (0..50).map(|_| forest.add_tree()).collect();
+        let mut sets = vec![BTreeSet::new(); trees.len()];
+        for i in 0..2000u32 {
+            let t = (i * 7 % 50) as usize;
+            assert_eq!(forest.insert(trees[t], i % 97), sets[t].replace(i % 97));
+        }
+        for i in 0..1000u32 {
+            let t = (i * 11 % 50) as usize;
+            assert_eq!(forest.delete(trees[t], &(i % 89)), sets[t].take(&(i % 89)));
+        }
+        assert_eq!(forest.try_self_check(), Ok(()));
+        for (&tree, set) in trees.iter().zip(&sets) {
+            assert!(forest.iter(tree).eq(set.iter()));
+            assert_eq!(forest.len(tree), set.len());
+        }
+        assert_eq!(forest.total_len(), sets.iter().map(|set| set.len()).sum::<usize>());
+
+        forest.append(trees[0], trees[1]);
+        let merged: BTreeSet<u32> = sets[0].union(&sets[1]).cloned().collect();
+        assert!(forest.iter(trees[0]).eq(merged.iter()));
+        assert!(forest.is_empty(trees[1]));
+
+        let upper = forest.split_off(trees[0], &40);
+        assert!(forest.iter(trees[0]).cloned().eq(merged.range(..40).cloned()));
+        assert!(forest.iter(upper).cloned().eq(merged.range(40..).cloned()));
+        assert_eq!(forest.tree_count(), 51);
+
+        // Cleared slots are reused by other trees.
+        let before = forest.arena.nodes.len();
+        forest.clear(trees[2]);
+        for i in 0..sets[2].len() as u32 {
+            forest.insert(trees[3], 1000 + i);
+        }
+        assert_eq!(forest.arena.nodes.len(), before);
+        assert_eq!(forest.try_self_check(), Ok(()));
+    }
+
+    #[test]
+    fn removed_ids_are_reused() {
+        let mut forest = Forest::new();
+        let (a, b, c) = (forest.add_tree(), forest.add_tree(), forest.add_tree());
+        forest.insert(a, 1);
+        forest.insert(b, 2);
+        forest.insert(b, 3);
+        forest.remove_tree(b);
+        assert_eq!(forest.tree_count(), 2);
+        assert_eq!(forest.total_len(), 1);
+        assert_eq!(format!("{:?}", forest), "{TreeId(0): {1}, TreeId(2): {}}");
+
+        // Splitting takes the freed id instead of growing the forest.
+        forest.insert(c, 4);
+        forest.insert(c, 5);
+        let upper = forest.split_off(c, &5);
+        assert_eq!(upper, b);
+        assert!(!forest.is_empty(a) && forest.len(upper) == 1);
+        assert_eq!(forest.tree_count(), 3);
+        assert_eq!(format!("{:?}", forest), "{TreeId(0): {1}, TreeId(1): {5}, TreeId(2): {4}}");
+        assert_eq!(forest.try_self_check(), Ok(()));
+    }
+}
@@ -0,0 +1,104 @@
+use core::borrow::Borrow;
+use core::ops::RangeBounds;
+use core::slice;
+
+use alloc::boxed::Box;
+
+use super::{BST, check_range_bounds};
+use iter::{above_lower, below_upper};
+
+// A read-only snapshot of a tree, with the elements packed in order into a
+// single allocation. Queries binary search the slice, so there are no links
+// to follow, and the snapshot is Send and Sync whenever T is, which makes it
+// cheap to share behind an Arc.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FrozenBST<T> {
+    elems: Box<[T]>,
+}
+
+impl<T: Ord> BST<T> {
+    pub fn freeze(self) -> FrozenBST<T> {
+        FrozenBST { elems: self.into_sorted_vec().into_boxed_slice() }
+    }
+}
+
+impl<T: Ord> FrozenBST<T> {
+    // Turns the snapshot back into a tree in linear time.
+    pub fn thaw(self) -> BST<T> {
+        BST::from_sorted_vec(self.elems.into_vec())
+    }
+
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elems.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        &self.elems
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.elems.iter()
+    }
+
+    pub fn member<Q: ?Sized + Ord>(&self, elem: &Q) -> bool where T: Borrow<Q> {
+        self.get(elem).is_some()
+    }
+
+    pub fn get<Q: ?Sized + Ord>(&self, elem: &Q) -> Option<&T> where T: Borrow<Q> {
+        self.elems.binary_search_by(|e| e.borrow().cmp(elem)).ok().map(|i| &self.elems[i])
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.elems.first()
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.elems.last()
+    }
+
+    // The number of elements less than the given one, as with BST::rank.
+    pub fn rank<Q: ?Sized + Ord>(&self, elem: &Q) -> usize where T: Borrow<Q> {
+        self.elems.partition_point(|e| e.borrow() < elem)
+    }
+
+    pub fn select(&self, k: usize) -> Option<&T> {
+        self.elems.get(k)
+    }
+
+    pub fn floor<Q: ?Sized + Ord>(&self, elem: &Q) -> Option<&T> where T: Borrow<Q> {
+        let i = self.elems.partition_point(|e| e.borrow() <= elem);
+        i.checked_sub(1).map(|i| &self.elems[i])
+    }
+
+    pub fn ceiling<Q: ?Sized + Ord>(&self, elem: &Q) -> Option<&T> where T: Borrow<Q> {
+        self.elems.get(self.rank(elem))
+    }
+
+    pub fn predecessor<Q: ?Sized + Ord>(&self, elem: &Q) -> Option<&T> where T: Borrow<Q> {
+        self.rank(elem).checked_sub(1).map(|i| &self.elems[i])
+    }
+
+    pub fn successor<Q: ?Sized + Ord>(&self, elem: &Q) -> Option<&T> where T: Borrow<Q> {
+        self.elems.get(self.elems.partition_point(|e| e.borrow() <= elem))
+    }
+
+    pub fn range<Q: ?Sized + Ord, R: RangeBounds<Q>>(&self, range: R) -> slice::Iter<'_, T> where T: Borrow<Q> {
+        check_range_bounds(&range);
+        let start = self.elems.partition_point(|e| !above_lower(range.start_bound(), e.borrow()));
+        let end = self.elems.partition_point(|e| below_upper(range.end_bound(), e.borrow()));
+        self.elems[start..end.max(start)].iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a FrozenBST<T> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> slice::Iter<'a, T> {
+        self.elems.iter()
+    }
+}
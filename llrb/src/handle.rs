@@ -0,0 +1,41 @@
+use super::{BST, Ptr};
+
+// An opaque reference to an element, which stays valid until that element is
+// removed. Neither rotations nor deletions move elements between nodes, so
+// a handle is simply the slot of the element's node. Operations that move
+// nodes to other slots or arenas (compact, append, split_off) invalidate all
+// handles.
+//
+// Using a handle after its element has been removed is a logic error: the
+// slot may since hold another element. Debug builds catch this through the
+// slot generation, in which case the handle finds nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handle(Ptr);
+
+impl<T: Ord> BST<T> {
+    // Like insert, but also returns a handle to the inserted element.
+    // Replacing an equal element keeps its node, so existing handles to it
+    // now refer to the new element.
+    pub fn insert_with_handle(&mut self, elem: T) -> (Handle, Option<T>) {
+        let (node, replaced) = self.insert_node_by(elem, T::cmp);
+        (Handle(node), replaced)
+    }
+
+    pub fn get_by_handle(&self, handle: Handle) -> Option<&T> {
+        match self.nodes.get(handle.0.index()) {
+            Some(Some(node)) if node.is_current(&handle.0) => Some(&node.elem),
+            _ => None,
+        }
+    }
+
+    // Only for parts of the element that do not take part in the ordering.
+    pub(crate) fn get_mut_by_handle(&mut self, handle: Handle) -> Option<&mut T> {
+        self.get_by_handle(handle)?;
+        Some(&mut self.deref_mut(&handle.0).elem)
+    }
+
+    pub fn remove_by_handle(&mut self, handle: Handle) -> Option<T> {
+        self.get_by_handle(handle)?;
+        Some(self.delete_node(handle.0))
+    }
+}
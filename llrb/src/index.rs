@@ -0,0 +1,52 @@
+use core::convert::TryFrom;
+use core::fmt;
+use core::num::{NonZeroU16, NonZeroU32, NonZeroU64, NonZeroUsize};
+
+// The unsigned integer types that a BST can store its node indices as. A
+// narrower type makes every link smaller, and lowers the number of nodes
+// the tree can hold to the largest value of the type.
+pub trait NodeIndex: Copy + Eq + fmt::Debug {
+    // The index offset by one, so that Option<Ptr> takes no more room
+    // than Ptr.
+    type NonZero: Copy + Eq + fmt::Debug;
+
+    // Returns None if the slot is past the largest index of the type.
+    fn encode(index: usize) -> Option<Self::NonZero>;
+
+    fn decode(n: Self::NonZero) -> usize;
+}
+
+macro_rules! node_index {
+    ($int:ty, $nonzero:ty) => {
+        impl NodeIndex for $int {
+            type NonZero = $nonzero;
+
+            fn encode(index: usize) -> Option<$nonzero> {
+                index.checked_add(1).and_then(|n| <$int>::try_from(n).ok()).and_then(<$nonzero>::new)
+            }
+
+            fn decode(n: $nonzero) -> usize {
+                n.get() as usize - 1
+            }
+        }
+    };
+}
+
+node_index!(u16, NonZeroU16);
+node_index!(u32, NonZeroU32);
+node_index!(u64, NonZeroU64);
+node_index!(usize, NonZeroUsize);
+
+// Returned by try_insert when the new element would need a slot past the
+// largest index the tree can store. The element is handed back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityError<T>(pub T);
+
+impl<T> fmt::Display for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no room for another node in the arena")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: fmt::Debug> ::std::error::Error for CapacityError<T> {}
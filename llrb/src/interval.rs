@@ -0,0 +1,217 @@
+use core::cmp::Ordering;
+use core::ops::{Bound, RangeBounds};
+
+use alloc::vec::Vec;
+
+use super::{BST, Ptr};
+
+// A closed interval along with the largest upper endpoint in its subtree.
+// Entries are ordered by their endpoints alone.
+#[derive(Debug, Clone)]
+struct IntervalEntry<T> {
+    lo: T,
+    hi: T,
+    max: T,
+}
+
+impl<T: Ord> PartialEq for IntervalEntry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.lo == other.lo && self.hi == other.hi
+    }
+}
+
+impl<T: Ord> Eq for IntervalEntry<T> {}
+
+impl<T: Ord> PartialOrd for IntervalEntry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for IntervalEntry<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.lo, &self.hi).cmp(&(&other.lo, &other.hi))
+    }
+}
+
+fn update_max<T: Ord + Clone>(tree: &mut BST<IntervalEntry<T>>, ptr: Ptr) {
+    let node = tree.deref(&ptr);
+    let mut max = &node.elem.hi;
+    for child in node.left.into_iter().chain(node.right) {
+        let child_max = &tree.deref(&child).elem.max;
+        if child_max > max {
+            max = child_max;
+        }
+    }
+    let max = max.clone();
+    tree.deref_mut(&ptr).elem.max = max;
+}
+
+#[derive(Debug, Clone)]
+pub struct IntervalTree<T> {
+    tree: BST<IntervalEntry<T>>,
+}
+
+#[allow(clippy::new_without_default)]
+impl<T: Ord + Clone> IntervalTree<T> {
+    pub fn new() -> Self {
+        let mut tree = BST::new();
+        tree.augment = Some(update_max::<T>);
+        IntervalTree { tree }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear()
+    }
+
+    pub fn contains(&self, lo: &T, hi: &T) -> bool {
+        self.tree.find_by(|e| (lo, hi).cmp(&(&e.lo, &e.hi))).is_some()
+    }
+
+    // Adds the closed interval [lo, hi] and returns whether it was absent.
+    pub fn insert(&mut self, lo: T, hi: T) -> bool {
+        assert!(lo <= hi, "IntervalTree::insert: lower endpoint is greater than upper endpoint");
+        let max = hi.clone();
+        self.tree.insert(IntervalEntry { lo, hi, max }).is_none()
+    }
+
+    pub fn remove(&mut self, lo: &T, hi: &T) -> bool {
+        self.tree.delete_by(|e| (lo, hi).cmp(&(&e.lo, &e.hi))).is_some()
+    }
+
+    // Iterates in order over the intervals that share at least one point
+    // with the range.
+    pub fn find_overlapping<R: RangeBounds<T>>(&self, range: R) -> Overlapping<'_, T, R> {
+        let mut iter = Overlapping { tree: &self.tree, stack: Vec::new(), range };
+        let root = self.tree.root;
+        iter.push_left_spine(root);
+        iter
+    }
+}
+
+pub struct Overlapping<'a, T: 'a, R> {
+    tree: &'a BST<IntervalEntry<T>>,
+    // Nodes whose left subtrees have been handled, as in Iter, except that
+    // subtrees without any overlapping interval are never entered.
+    stack: Vec<Ptr>,
+    range: R,
+}
+
+impl<'a, T: Ord, R: RangeBounds<T>> Overlapping<'a, T, R> {
+    // Whether some interval ending at hi or later can reach the range.
+    fn reaches_start(&self, hi: &T) -> bool {
+        match self.range.start_bound() {
+            Bound::Included(start) => hi >= start,
+            Bound::Excluded(start) => hi > start,
+            Bound::Unbounded => true,
+        }
+    }
+
+    // Whether an interval starting at lo begins before the range ends.
+    fn precedes_end(&self, lo: &T) -> bool {
+        match self.range.end_bound() {
+            Bound::Included(end) => lo <= end,
+            Bound::Excluded(end) => lo < end,
+            Bound::Unbounded => true,
+        }
+    }
+
+    fn push_left_spine(&mut self, mut node: Option<Ptr>) {
+        while let Some(ptr) = node {
+            let n = self.tree.deref(&ptr);
+            if !self.reaches_start(&n.elem.max) {
+                return;
+            }
+            self.stack.push(ptr);
+            node = n.left;
+        }
+    }
+}
+
+impl<'a, T: Ord, R: RangeBounds<T>> Iterator for Overlapping<'a, T, R> {
+    type Item = (&'a T, &'a T);
+
+    fn next(&mut self) -> Option<(&'a T, &'a T)> {
+        while let Some(ptr) = self.stack.pop() {
+            let tree = self.tree;
+            let node = tree.deref(&ptr);
+            // Every interval further on starts no earlier than this one.
+            if !self.precedes_end(&node.elem.lo) {
+                self.stack.clear();
+                return None;
+            }
+            self.push_left_spine(node.right);
+            if self.reaches_start(&node.elem.hi) {
+                return Some((&node.elem.lo, &node.elem.hi));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::{BST, IntervalEntry, IntervalTree, Ptr};
+
+    fn check_max(tree: &BST<IntervalEntry<u32>>, node: Option<Ptr>) -> Option<u32> {
+        let ptr = node?;
+        let n = tree.deref(&ptr);
+        let max = [Some(n.elem.hi), check_max(tree, n.left), check_max(tree, n.right)].iter().flatten().copied().max();
+        assert_eq!(max, Some(n.elem.max));
+        max
+    }
+
+    #[test]
+    fn overlapping() {
+        let mut tree = IntervalTree::new();
+        let mut intervals = Vec::new();
+        let mut seed = 7u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) % 1000
+        };
+        for _ in 0..400 {
+            let lo = next();
+            let hi = lo + next() % 50;
+            if tree.insert(lo, hi) {
+                intervals.push((lo, hi));
+            }
+            if next() % 3 == 0 {
+                let (lo, hi) = intervals.swap_remove(next() as usize % intervals.len());
+                assert!(tree.remove(&lo, &hi));
+                assert!(!tree.contains(&lo, &hi));
+            }
+            check_max(&tree.tree, tree.tree.root);
+        }
+        // Inserting an interval that is already there replaces its entry,
+        // which must not lose the maximum of the subtree below it.
+        for &(lo, hi) in &intervals {
+            assert!(!tree.insert(lo, hi));
+            check_max(&tree.tree, tree.tree.root);
+        }
+        assert_eq!(tree.len(), intervals.len());
+        intervals.sort();
+
+        for &(start, end) in &[(0, 1000), (100, 120), (500, 500), (990, 2000), (30, 10)] {
+            let expected: Vec<_> = intervals.iter().filter(|&&(lo, hi)| lo <= end && start <= hi).map(|(lo, hi)| (lo, hi)).collect();
+            assert_eq!(tree.find_overlapping(start..=end).collect::<Vec<_>>(), expected);
+            let expected: Vec<_> = intervals.iter().filter(|&&(lo, hi)| lo < end && start <= hi).map(|(lo, hi)| (lo, hi)).collect();
+            assert_eq!(tree.find_overlapping(start..end).collect::<Vec<_>>(), expected);
+        }
+        assert_eq!(tree.find_overlapping(..).count(), intervals.len());
+
+        tree.clear();
+        assert!(tree.is_empty());
+        assert_eq!(tree.find_overlapping(..).next(), None);
+    }
+}
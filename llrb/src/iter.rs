@@ -0,0 +1,478 @@
+use core::borrow::Borrow;
+use core::mem;
+use core::ops::Bound;
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::vec::Vec as ArenaVec;
+
+use super::{BST, Color, Node, NodeIndex, Ptr};
+
+// The iterator walks inward from both ends, and the count of remaining
+// elements tells when the two ends have met. Without parent pointers each
+// end keeps a stack of the nodes whose left (or right) subtree it is in.
+// With them, each end only holds its next node and climbs back up through
+// the parents, never past the root of the subtree being walked.
+pub struct Iter<'a, T, A: Allocator = Global, I: NodeIndex = u32> {
+    tree: &'a BST<T, A, I>,
+    #[cfg(not(feature = "parent-pointers"))]
+    stack: Vec<Ptr<I>>,
+    #[cfg(not(feature = "parent-pointers"))]
+    back: Vec<Ptr<I>>,
+    #[cfg(feature = "parent-pointers")]
+    root: Option<Ptr<I>>,
+    #[cfg(feature = "parent-pointers")]
+    front: Option<Ptr<I>>,
+    #[cfg(feature = "parent-pointers")]
+    back: Option<Ptr<I>>,
+    remaining: usize,
+}
+
+impl<'a, T, A: Allocator, I: NodeIndex> Iter<'a, T, A, I> {
+    pub(crate) fn new(tree: &'a BST<T, A, I>) -> Self {
+        Iter::subtree(tree, tree.root)
+    }
+
+    #[cfg(not(feature = "parent-pointers"))]
+    pub(crate) fn subtree(tree: &'a BST<T, A, I>, root: Option<Ptr<I>>) -> Self {
+        let mut iter = Iter { tree, stack: Vec::new(), back: Vec::new(), remaining: tree.size(&root) };
+        iter.push_left_spine(root);
+        iter.push_right_spine(root);
+        iter
+    }
+
+    #[cfg(not(feature = "parent-pointers"))]
+    fn push_left_spine(&mut self, mut node: Option<Ptr<I>>) {
+        while let Some(ptr) = node {
+            self.stack.push(ptr);
+            node = self.tree.deref(&ptr).left;
+        }
+    }
+
+    #[cfg(not(feature = "parent-pointers"))]
+    fn push_right_spine(&mut self, mut node: Option<Ptr<I>>) {
+        while let Some(ptr) = node {
+            self.back.push(ptr);
+            node = self.tree.deref(&ptr).right;
+        }
+    }
+
+    #[cfg(not(feature = "parent-pointers"))]
+    fn step_front(&mut self) -> Option<&'a Node<T, I>> {
+        let ptr = self.stack.pop()?;
+        let tree = self.tree;
+        let node = tree.deref(&ptr);
+        self.push_left_spine(node.right);
+        Some(node)
+    }
+
+    #[cfg(not(feature = "parent-pointers"))]
+    fn step_back(&mut self) -> Option<&'a Node<T, I>> {
+        let ptr = self.back.pop()?;
+        let tree = self.tree;
+        let node = tree.deref(&ptr);
+        self.push_right_spine(node.left);
+        Some(node)
+    }
+
+    #[cfg(feature = "parent-pointers")]
+    pub(crate) fn subtree(tree: &'a BST<T, A, I>, root: Option<Ptr<I>>) -> Self {
+        let front = root.map(|root| Iter::leftmost(tree, root));
+        let back = root.map(|root| Iter::rightmost(tree, root));
+        Iter { tree, root, front, back, remaining: tree.size(&root) }
+    }
+
+    #[cfg(feature = "parent-pointers")]
+    fn leftmost(tree: &BST<T, A, I>, mut ptr: Ptr<I>) -> Ptr<I> {
+        while let Some(left) = tree.deref(&ptr).left {
+            ptr = left;
+        }
+        ptr
+    }
+
+    #[cfg(feature = "parent-pointers")]
+    fn rightmost(tree: &BST<T, A, I>, mut ptr: Ptr<I>) -> Ptr<I> {
+        while let Some(right) = tree.deref(&ptr).right {
+            ptr = right;
+        }
+        ptr
+    }
+
+    // Climbs from ptr until it is reached from the given side, returning
+    // that ancestor.
+    #[cfg(feature = "parent-pointers")]
+    fn climb(&self, mut ptr: Ptr<I>, from_left: bool) -> Option<Ptr<I>> {
+        while Some(ptr) != self.root {
+            let parent = self.tree.deref(&ptr).parent.expect("Iter encounters a node without a parent");
+            let node = self.tree.deref(&parent);
+            if (if from_left { node.left } else { node.right }) == Some(ptr) {
+                return Some(parent);
+            }
+            ptr = parent;
+        }
+        None
+    }
+
+    #[cfg(feature = "parent-pointers")]
+    fn step_front(&mut self) -> Option<&'a Node<T, I>> {
+        let ptr = self.front?;
+        let tree = self.tree;
+        let node = tree.deref(&ptr);
+        self.front = match node.right {
+            Some(right) => Some(Iter::leftmost(tree, right)),
+            None => self.climb(ptr, true),
+        };
+        Some(node)
+    }
+
+    #[cfg(feature = "parent-pointers")]
+    fn step_back(&mut self) -> Option<&'a Node<T, I>> {
+        let ptr = self.back?;
+        let tree = self.tree;
+        let node = tree.deref(&ptr);
+        self.back = match node.left {
+            Some(left) => Some(Iter::rightmost(tree, left)),
+            None => self.climb(ptr, false),
+        };
+        Some(node)
+    }
+}
+
+impl<'a, T, A: Allocator, I: NodeIndex> Iterator for Iter<'a, T, A, I> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = self.step_front()?;
+        self.remaining -= 1;
+        Some(&node.elem)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T, A: Allocator, I: NodeIndex> DoubleEndedIterator for Iter<'a, T, A, I> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = self.step_back()?;
+        self.remaining -= 1;
+        Some(&node.elem)
+    }
+}
+
+impl<'a, T, A: Allocator, I: NodeIndex> ExactSizeIterator for Iter<'a, T, A, I> {}
+
+impl<'a, T, A: Allocator, I: NodeIndex> Clone for Iter<'a, T, A, I> {
+    fn clone(&self) -> Self {
+        Iter {
+            tree: self.tree,
+            #[cfg(not(feature = "parent-pointers"))]
+            stack: self.stack.clone(),
+            #[cfg(not(feature = "parent-pointers"))]
+            back: self.back.clone(),
+            #[cfg(feature = "parent-pointers")]
+            root: self.root,
+            #[cfg(feature = "parent-pointers")]
+            front: self.front,
+            #[cfg(feature = "parent-pointers")]
+            back: self.back,
+            remaining: self.remaining,
+        }
+    }
+}
+
+pub struct IntoIter<T, A: Allocator = Global, I: NodeIndex = u32> {
+    nodes: ArenaVec<Option<Node<T, I>>, A>,
+    stack: Vec<Ptr<I>>,
+    remaining: usize,
+}
+
+impl<T, A: Allocator, I: NodeIndex> IntoIter<T, A, I> {
+    pub(crate) fn new(tree: BST<T, A, I>) -> Self {
+        let remaining = tree.nodes.len() - tree.deleted_indices.len();
+        let mut iter = IntoIter { nodes: tree.nodes, stack: Vec::new(), remaining };
+        iter.push_left_spine(tree.root);
+        iter
+    }
+
+    fn push_left_spine(&mut self, mut node: Option<Ptr<I>>) {
+        while let Some(ptr) = node {
+            self.stack.push(ptr);
+            node = self.nodes[ptr.index()].as_ref().expect("IntoIter encounters a reference to a deleted node").left;
+        }
+    }
+}
+
+impl<T, A: Allocator, I: NodeIndex> Iterator for IntoIter<T, A, I> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let ptr = self.stack.pop()?;
+        let node = self.nodes[ptr.index()].take().expect("IntoIter encounters a reference to a deleted node");
+        self.push_left_spine(node.right);
+        self.remaining -= 1;
+        Some(node.elem)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T, A: Allocator, I: NodeIndex> ExactSizeIterator for IntoIter<T, A, I> {}
+
+impl<T: Ord, A: Allocator, I: NodeIndex> IntoIterator for BST<T, A, I> {
+    type Item = T;
+    type IntoIter = IntoIter<T, A, I>;
+
+    fn into_iter(self) -> IntoIter<T, A, I> {
+        IntoIter::new(self)
+    }
+}
+
+impl<'a, T: Ord, A: Allocator, I: NodeIndex> IntoIterator for &'a BST<T, A, I> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, A, I>;
+
+    fn into_iter(self) -> Iter<'a, T, A, I> {
+        self.iter()
+    }
+}
+
+pub struct Drain<'a, T> {
+    tree: &'a mut BST<T>,
+    inner: IntoIter<T>,
+    // The slots to free once the drain is done, which keep their
+    // generations as they do after BST::clear.
+    #[cfg(debug_assertions)]
+    stale: Vec<Ptr>,
+}
+
+impl<'a, T: Ord> Drain<'a, T> {
+    pub(crate) fn new(tree: &'a mut BST<T>) -> Self {
+        // The tree is emptied up front, so leaking the Drain leaves it
+        // empty rather than inconsistent.
+        #[cfg(debug_assertions)]
+        let stale = tree.stale_slots();
+        let inner = IntoIter::new(mem::take(tree));
+        Drain {
+            tree, inner,
+            #[cfg(debug_assertions)]
+            stale,
+        }
+    }
+}
+
+impl<'a, T> Iterator for Drain<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Drain<'a, T> {}
+
+impl<'a, T> Drop for Drain<'a, T> {
+    fn drop(&mut self) {
+        // Hand the arena back so that its allocation is reused.
+        #[cfg(debug_assertions)]
+        {
+            self.inner.nodes.iter_mut().for_each(|node| *node = None);
+            self.tree.deleted_indices.extend(mem::take(&mut self.stale));
+        }
+        #[cfg(not(debug_assertions))]
+        self.inner.nodes.clear();
+        self.tree.nodes = mem::take(&mut self.inner.nodes);
+    }
+}
+
+pub(crate) fn above_lower<Q: ?Sized + Ord>(lower: Bound<&Q>, elem: &Q) -> bool {
+    match lower {
+        Bound::Included(bound) => bound <= elem,
+        Bound::Excluded(bound) => bound < elem,
+        Bound::Unbounded => true,
+    }
+}
+
+pub(crate) fn below_upper<Q: ?Sized + Ord>(upper: Bound<&Q>, elem: &Q) -> bool {
+    match upper {
+        Bound::Included(bound) => elem <= bound,
+        Bound::Excluded(bound) => elem < bound,
+        Bound::Unbounded => true,
+    }
+}
+
+// Like Iter, a range walks inward from both ends until they meet.
+pub struct Range<'a, T, A: Allocator = Global, I: NodeIndex = u32> {
+    tree: &'a BST<T, A, I>,
+    stack: Vec<Ptr<I>>,
+    back: Vec<Ptr<I>>,
+    remaining: usize,
+}
+
+impl<'a, T: Ord, A: Allocator, I: NodeIndex> Range<'a, T, A, I> {
+    pub(crate) fn new<Q: ?Sized + Ord>(tree: &'a BST<T, A, I>, lower: Bound<&Q>, upper: Bound<&Q>) -> Self where T: Borrow<Q> {
+        Range::between(tree, |e| above_lower(lower, e.borrow()), |e| below_upper(upper, e.borrow()))
+    }
+
+    // The elements for which both predicates hold, given that the first
+    // holds for a suffix of the elements in order and the second for a
+    // prefix.
+    pub(crate) fn between<S: Fn(&T) -> bool, E: Fn(&T) -> bool>(tree: &'a BST<T, A, I>, after_start: S, before_end: E) -> Self {
+        let below_end = tree.count_prefix(&before_end);
+        let before_start = tree.count_prefix(|e| !after_start(e));
+        let mut range = Range { tree, stack: Vec::new(), back: Vec::new(), remaining: below_end.saturating_sub(before_start) };
+
+        // Only the nodes on the search path for the lower bound that are
+        // themselves within the bound need to be visited later, and
+        // likewise for the upper bound.
+        let mut node = tree.root;
+        while let Some(ptr) = node {
+            let n = tree.deref(&ptr);
+            if after_start(&n.elem) {
+                range.stack.push(ptr);
+                node = n.left;
+            } else {
+                node = n.right;
+            }
+        }
+
+        let mut node = tree.root;
+        while let Some(ptr) = node {
+            let n = tree.deref(&ptr);
+            if before_end(&n.elem) {
+                range.back.push(ptr);
+                node = n.right;
+            } else {
+                node = n.left;
+            }
+        }
+        range
+    }
+
+    fn push_left_spine(&mut self, mut node: Option<Ptr<I>>) {
+        while let Some(ptr) = node {
+            self.stack.push(ptr);
+            node = self.tree.deref(&ptr).left;
+        }
+    }
+
+    fn push_right_spine(&mut self, mut node: Option<Ptr<I>>) {
+        while let Some(ptr) = node {
+            self.back.push(ptr);
+            node = self.tree.deref(&ptr).right;
+        }
+    }
+}
+
+impl<'a, T: Ord, A: Allocator, I: NodeIndex> Iterator for Range<'a, T, A, I> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let ptr = self.stack.pop()?;
+        let tree = self.tree;
+        let node = tree.deref(&ptr);
+        self.push_left_spine(node.right);
+        self.remaining -= 1;
+        Some(&node.elem)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T: Ord, A: Allocator, I: NodeIndex> DoubleEndedIterator for Range<'a, T, A, I> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let ptr = self.back.pop()?;
+        let tree = self.tree;
+        let node = tree.deref(&ptr);
+        self.push_right_spine(node.left);
+        self.remaining -= 1;
+        Some(&node.elem)
+    }
+}
+
+impl<'a, T: Ord, A: Allocator, I: NodeIndex> ExactSizeIterator for Range<'a, T, A, I> {}
+
+impl<'a, T, A: Allocator, I: NodeIndex> Clone for Range<'a, T, A, I> {
+    fn clone(&self) -> Self {
+        Range { tree: self.tree, stack: self.stack.clone(), back: self.back.clone(), remaining: self.remaining }
+    }
+}
+
+// Walks the elements in order by rank, removing those that match. The rank
+// only advances past elements that are kept.
+pub struct ExtractIf<'a, T, F> {
+    tree: &'a mut BST<T>,
+    index: usize,
+    pred: F,
+}
+
+impl<'a, T: Ord, F: FnMut(&T) -> bool> ExtractIf<'a, T, F> {
+    pub(crate) fn new(tree: &'a mut BST<T>, pred: F) -> Self {
+        ExtractIf { tree, index: 0, pred }
+    }
+}
+
+impl<'a, T: Ord, F: FnMut(&T) -> bool> Iterator for ExtractIf<'a, T, F> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        while let Some(elem) = self.tree.select(self.index) {
+            if (self.pred)(elem) {
+                return self.tree.delete_at(self.index);
+            }
+            self.index += 1;
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.tree.len() - self.index))
+    }
+}
+
+// Breadth-first traversal, yielding each element with its depth (the root
+// being at depth 0) and the color of its node.
+pub struct Levels<'a, T> {
+    tree: &'a BST<T>,
+    queue: VecDeque<(Ptr, usize)>,
+}
+
+impl<'a, T> Levels<'a, T> {
+    pub(crate) fn new(tree: &'a BST<T>) -> Self {
+        Levels { tree, queue: tree.root.map(|root| (root, 0)).into_iter().collect() }
+    }
+}
+
+impl<'a, T> Iterator for Levels<'a, T> {
+    type Item = (usize, &'a T, Color);
+
+    fn next(&mut self) -> Option<(usize, &'a T, Color)> {
+        let (ptr, depth) = self.queue.pop_front()?;
+        let tree = self.tree;
+        let node = tree.deref(&ptr);
+        self.queue.extend(node.left.into_iter().chain(node.right).map(|child| (child, depth + 1)));
+        Some((depth, &node.elem, node.color))
+    }
+}
@@ -0,0 +1,420 @@
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::mem;
+use core::ops::RangeBounds;
+
+use alloc::vec::{self, Vec};
+
+use super::{check_range_bounds, BST, Color, Ptr};
+use iter;
+
+// Split and join on subtrees living in the same arena. A subtree handed to
+// or returned from these functions is a standalone LLRB tree except that
+// its root may be red.
+impl<T: Ord> BST<T> {
+    pub(crate) fn subtree_black_height(&self, mut node: Option<Ptr>) -> usize {
+        let mut height = 0;
+        while let Some(ptr) = node {
+            if !self.is_red(&node) {
+                height += 1;
+            }
+            node = self.deref(&ptr).left;
+        }
+        height
+    }
+
+    fn detach(&mut self, node: Option<Ptr>) -> Option<Ptr> {
+        if let Some(ptr) = node {
+            self.deref_mut(&ptr).color = Color::Black;
+        }
+        node
+    }
+
+    pub(crate) fn free_subtree(&mut self, node: Option<Ptr>) {
+        if let Some(ptr) = node {
+            let (left, right) = (self.deref(&ptr).left, self.deref(&ptr).right);
+            self.free_subtree(left);
+            self.free_subtree(right);
+            self.free(ptr);
+        }
+    }
+
+    // Moves every node of the other tree into this arena, returning the
+    // root of the moved tree.
+    pub(crate) fn absorb(&mut self, other: BST<T>) -> Option<Ptr> {
+        let mut remap: Vec<Option<Ptr>> = vec![None; other.nodes.len()];
+        let mut next = self.nodes.len();
+        for (i, node) in other.nodes.iter().enumerate() {
+            if node.is_some() {
+                remap[i] = Some(self.deleted_indices.pop().map(Ptr::reused).unwrap_or_else(|| {
+                    next += 1;
+                    Ptr::new(next - 1)
+                }));
+            }
+        }
+        self.nodes.resize_with(next, || None);
+        for (i, node) in other.nodes.into_iter().enumerate() {
+            if let Some(mut node) = node {
+                node.left = node.left.map(|p| remap[p.index()].unwrap());
+                node.right = node.right.map(|p| remap[p.index()].unwrap());
+                node.stamp(remap[i].unwrap());
+                self.nodes[remap[i].unwrap().index()] = Some(node);
+            }
+        }
+        for &ptr in remap.iter().flatten() {
+            self.adopt_children(ptr);
+        }
+        other.root.map(|p| remap[p.index()].unwrap())
+    }
+
+    fn join_right(&mut self, node: Option<Ptr>, height: usize, mid: Ptr, right: Option<Ptr>, right_height: usize) -> Ptr {
+        if height == right_height {
+            let m = self.deref_mut(&mid);
+            m.left = node;
+            m.right = right;
+            m.color = Color::Red;
+            return self.fixup(mid);
+        }
+        // Right links are always black, so every step down the right spine
+        // decreases the black height by one.
+        let node = node.expect("join_right runs off the right spine");
+        let next = self.deref(&node).right;
+        let new_right = self.join_right(next, height - 1, mid, right, right_height);
+        self.deref_mut(&node).right = Some(new_right);
+        self.fixup(node)
+    }
+
+    fn join_left(&mut self, left: Option<Ptr>, left_height: usize, mid: Ptr, node: Option<Ptr>, height: usize) -> Ptr {
+        if height == left_height && !self.is_red(&node) {
+            let m = self.deref_mut(&mid);
+            m.left = left;
+            m.right = node;
+            m.color = Color::Red;
+            return self.fixup(mid);
+        }
+        let next_height = if self.is_red(&node) { height } else { height - 1 };
+        let node = node.expect("join_left runs off the left spine");
+        let next = self.deref(&node).left;
+        let new_left = self.join_left(left, left_height, mid, next, next_height);
+        self.deref_mut(&node).left = Some(new_left);
+        self.fixup(node)
+    }
+
+    // Joins two trees with the node mid, whose element must be greater than
+    // every element of left and smaller than every element of right.
+    pub(crate) fn join(&mut self, left: Option<Ptr>, mid: Ptr, right: Option<Ptr>) -> Ptr {
+        let left = self.detach(left);
+        let right = self.detach(right);
+        let left_height = self.subtree_black_height(left);
+        let right_height = self.subtree_black_height(right);
+        let root = match left_height.cmp(&right_height) {
+            Ordering::Greater => self.join_right(left, left_height, mid, right, right_height),
+            Ordering::Less => self.join_left(left, left_height, mid, right, right_height),
+            Ordering::Equal => {
+                let m = self.deref_mut(&mid);
+                m.left = left;
+                m.right = right;
+                self.fixup(mid)
+            }
+        };
+        self.deref_mut(&root).color = Color::Black;
+        root
+    }
+
+    // Joins two trees where every element of left is smaller than every
+    // element of right.
+    pub(crate) fn concat(&mut self, left: Option<Ptr>, right: Option<Ptr>) -> Option<Ptr> {
+        match self.detach(right) {
+            None => self.detach(left),
+            Some(right) => {
+                let (mid, rest) = self.detach_min_impl(right);
+                self.deref_mut(&mid).color = Color::Red;
+                self.deref_mut(&mid).size = 1;
+                Some(self.join(left, mid, rest))
+            }
+        }
+    }
+
+    // Splits a tree into the parts ordered before and after the element
+    // located by cmp, which is returned as a lone node if present.
+    pub(crate) fn split_by<F: Fn(&T) -> Ordering>(&mut self, node: Option<Ptr>, cmp: &F) -> (Option<Ptr>, Option<Ptr>, Option<Ptr>) {
+        match node {
+            None => (None, None, None),
+            Some(ptr) => {
+                let (left, right) = (self.deref(&ptr).left, self.deref(&ptr).right);
+                match cmp(&self.deref(&ptr).elem) {
+                    Ordering::Less => {
+                        let (l, found, r) = self.split_by(left, cmp);
+                        (l, found, Some(self.join(r, ptr, right)))
+                    },
+                    Ordering::Greater => {
+                        let (l, found, r) = self.split_by(right, cmp);
+                        (Some(self.join(left, ptr, l)), found, r)
+                    },
+                    Ordering::Equal => {
+                        let n = self.deref_mut(&ptr);
+                        n.left = None;
+                        n.right = None;
+                        n.size = 1;
+                        (self.detach(left), Some(ptr), self.detach(right))
+                    },
+                }
+            }
+        }
+    }
+
+    // Splits off the first k elements of a tree, in the manner of split_by.
+    fn split_at_rank(&mut self, node: Option<Ptr>, k: usize) -> (Option<Ptr>, Option<Ptr>) {
+        match node {
+            None => (None, None),
+            Some(ptr) => {
+                let (left, right) = (self.deref(&ptr).left, self.deref(&ptr).right);
+                let left_size = self.size(&left);
+                if k <= left_size {
+                    let (l, r) = self.split_at_rank(left, k);
+                    (l, Some(self.join(r, ptr, right)))
+                } else {
+                    let (l, r) = self.split_at_rank(right, k - left_size - 1);
+                    (Some(self.join(left, ptr, l)), r)
+                }
+            }
+        }
+    }
+
+    // Frees a subtree, collecting its elements in order.
+    pub(crate) fn free_into(&mut self, node: Option<Ptr>, elems: &mut Vec<T>) {
+        if let Some(ptr) = node {
+            let (left, right) = (self.deref(&ptr).left, self.deref(&ptr).right);
+            self.free_into(left, elems);
+            elems.push(self.free(ptr));
+            self.free_into(right, elems);
+        }
+    }
+
+    // Splits a tree around the element of the pivot node, which must not
+    // be part of that tree. This lets the pivot be moved out of the arena
+    // while the tree is being restructured.
+    fn split_around(&mut self, node: Option<Ptr>, pivot: Ptr) -> (Option<Ptr>, Option<Ptr>, Option<Ptr>) {
+        let pivot_node = self.nodes[pivot.index()].take().expect("split_around encounters a reference to a deleted node");
+        let parts = self.split_by(node, &|e| pivot_node.elem.cmp(e));
+        self.nodes[pivot.index()] = Some(pivot_node);
+        parts
+    }
+
+    pub(crate) fn union_impl(&mut self, a: Option<Ptr>, b: Option<Ptr>) -> Option<Ptr> {
+        let root = match (a, b) {
+            (None, _) => return self.detach(b),
+            (_, None) => return self.detach(a),
+            (Some(root), Some(_)) => root,
+        };
+        let (left, right) = (self.deref(&root).left, self.deref(&root).right);
+        let (b_left, found, b_right) = self.split_around(b, root);
+        if let Some(found) = found {
+            self.free(found);
+        }
+        let new_left = self.union_impl(left, b_left);
+        let new_right = self.union_impl(right, b_right);
+        Some(self.join(new_left, root, new_right))
+    }
+
+    fn intersection_impl(&mut self, a: Option<Ptr>, b: Option<Ptr>) -> Option<Ptr> {
+        let root = match (a, b) {
+            (Some(root), Some(_)) => root,
+            _ => {
+                self.free_subtree(a);
+                self.free_subtree(b);
+                return None;
+            },
+        };
+        let (left, right) = (self.deref(&root).left, self.deref(&root).right);
+        let (b_left, found, b_right) = self.split_around(b, root);
+        let new_left = self.intersection_impl(left, b_left);
+        let new_right = self.intersection_impl(right, b_right);
+        match found {
+            Some(found) => {
+                self.free(found);
+                Some(self.join(new_left, root, new_right))
+            },
+            None => {
+                self.free(root);
+                self.concat(new_left, new_right)
+            },
+        }
+    }
+
+    fn difference_impl(&mut self, a: Option<Ptr>, b: Option<Ptr>) -> Option<Ptr> {
+        let root = match (a, b) {
+            (None, _) => {
+                self.free_subtree(b);
+                return None;
+            },
+            (_, None) => return self.detach(a),
+            (Some(_), Some(root)) => root,
+        };
+        let (left, right) = (self.deref(&root).left, self.deref(&root).right);
+        let (a_left, found, a_right) = self.split_around(a, root);
+        let new_left = self.difference_impl(a_left, left);
+        let new_right = self.difference_impl(a_right, right);
+        if let Some(found) = found {
+            self.free(found);
+        }
+        self.free(root);
+        self.concat(new_left, new_right)
+    }
+
+    fn set_root(&mut self, root: Option<Ptr>) {
+        self.root = root;
+        match root {
+            None => self.clear(),
+            Some(root) => self.deref_mut(&root).color = Color::Black,
+        }
+    }
+
+    // Moves a subtree into the given tree's arena, returning its new root.
+    fn extract_into(&mut self, node: Option<Ptr>, dest: &mut BST<T>) -> Option<Ptr> {
+        node.map(|ptr| {
+            let (left, right) = (self.deref(&ptr).left, self.deref(&ptr).right);
+            let new_left = self.extract_into(left, dest);
+            let new_right = self.extract_into(right, dest);
+            self.deleted_indices.push(ptr);
+            let mut node = self.nodes[ptr.index()].take().expect("extract_into encounters a reference to a deleted node");
+            node.left = new_left;
+            node.right = new_right;
+            let new = Ptr::new(dest.nodes.len());
+            node.stamp(new);
+            dest.nodes.push(Some(node));
+            dest.adopt_children(new);
+            new
+        })
+    }
+
+    pub fn append(&mut self, other: &mut BST<T>) {
+        let other = mem::take(other);
+        let disjoint_after = match (self.last(), other.first()) {
+            (Some(max), Some(min)) => max < min,
+            _ => true,
+        };
+        let disjoint_before = match (other.last(), self.first()) {
+            (Some(max), Some(min)) => max < min,
+            _ => true,
+        };
+        let mine = self.root;
+        let theirs = self.absorb(other);
+        let root = if disjoint_after {
+            self.concat(mine, theirs)
+        } else if disjoint_before {
+            self.concat(theirs, mine)
+        } else {
+            // Elements from the other tree take precedence, as with
+            // BTreeSet::append.
+            self.union_impl(theirs, mine)
+        };
+        self.set_root(root);
+    }
+
+    // Removes every element within the range by splitting off the subtree
+    // that holds them, and returns how many there were.
+    pub fn remove_range<Q: ?Sized + Ord, R: RangeBounds<Q>>(&mut self, range: R) -> usize where T: Borrow<Q> {
+        check_range_bounds(&range);
+        let (start, end) = (range.start_bound(), range.end_bound());
+        // Neither comparator ever reports Equal, so the splits partition
+        // the elements without singling one out.
+        let root = self.root;
+        let (before, _, rest) = self.split_by(root, &|e| if iter::above_lower(start, e.borrow()) { Ordering::Less } else { Ordering::Greater });
+        let (within, _, after) = self.split_by(rest, &|e| if iter::below_upper(end, e.borrow()) { Ordering::Greater } else { Ordering::Less });
+        let removed = self.size(&within);
+        self.free_subtree(within);
+        let root = self.concat(before, after);
+        self.set_root(root);
+        removed
+    }
+
+    // Inserts a batch of elements by building them into a tree of their
+    // own and taking its union with this one, which for a large batch is
+    // much cheaper than inserting them one at a time. As with insert, a
+    // later element replaces an equal earlier one.
+    pub fn insert_many<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let mut batch: Vec<T> = iter.into_iter().collect();
+        // The sort is stable, so after reversing, the latest of several
+        // equal elements comes first and is the one dedup keeps.
+        batch.reverse();
+        batch.sort();
+        batch.dedup();
+        let len = batch.len();
+        let nodes: Vec<Ptr> = batch.into_iter().map(|elem| self.alloc(elem)).collect();
+        let batch_root = self.link_sorted(&mut nodes.into_iter(), len);
+        // The union keeps the elements of its first argument on ties.
+        let root = self.root;
+        let root = self.union_impl(batch_root, root);
+        self.set_root(root);
+    }
+
+    // Removes the k smallest elements, or all of them if there are fewer,
+    // and returns them in order. Splitting by rank takes logarithmic time,
+    // so beyond that the cost is only in moving out the elements taken.
+    pub fn take_min_n(&mut self, k: usize) -> Vec<T> {
+        let root = self.root;
+        let (smallest, rest) = self.split_at_rank(root, k);
+        let mut taken = Vec::with_capacity(self.size(&smallest));
+        self.free_into(smallest, &mut taken);
+        self.set_root(rest);
+        taken
+    }
+
+    // Removes every element less than the bound and returns them in order,
+    // with a single split rather than one deletion per element.
+    pub fn drain_below<Q: ?Sized + Ord>(&mut self, bound: &Q) -> vec::IntoIter<T> where T: Borrow<Q> {
+        let below = self.rank(bound);
+        self.take_min_n(below).into_iter()
+    }
+
+    pub fn split_off<Q: ?Sized + Ord>(&mut self, key: &Q) -> BST<T> where T: Borrow<Q> {
+        let root = self.root;
+        let (left, found, right) = self.split_by(root, &|e| key.cmp(e.borrow()));
+        let right = match found {
+            Some(found) => Some(self.join(None, found, right)),
+            None => right,
+        };
+
+        // Each tree owns its own arena, so one of the halves has to be moved
+        // node by node. Moving the smaller one bounds that work.
+        let mut other = BST::new();
+        if self.size(&left) < self.size(&right) {
+            mem::swap(self, &mut other);
+            let left = other.extract_into(left, self);
+            self.set_root(left);
+            other.set_root(right);
+        } else {
+            let right = self.extract_into(right, &mut other);
+            other.set_root(right);
+            self.set_root(left);
+        }
+        other
+    }
+}
+
+impl<T: Ord + Clone> BST<T> {
+    pub fn union(&self, other: &BST<T>) -> BST<T> {
+        let mut result = self.clone();
+        let other_root = result.absorb(other.clone());
+        let root = result.union_impl(result.root, other_root);
+        result.set_root(root);
+        result
+    }
+
+    pub fn intersection(&self, other: &BST<T>) -> BST<T> {
+        let mut result = self.clone();
+        let other_root = result.absorb(other.clone());
+        let root = result.intersection_impl(result.root, other_root);
+        result.set_root(root);
+        result
+    }
+
+    pub fn difference(&self, other: &BST<T>) -> BST<T> {
+        let mut result = self.clone();
+        let other_root = result.absorb(other.clone());
+        let root = result.difference_impl(result.root, other_root);
+        result.set_root(root);
+        result
+    }
+}
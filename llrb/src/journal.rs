@@ -0,0 +1,206 @@
+use core::borrow::Borrow;
+
+use alloc::vec::Vec;
+
+use super::BST;
+
+// How to undo one mutation. Rollback undoes mutations in reverse, so each
+// one is undone against exactly the tree it left behind, and an element
+// can be found again by its rank at the time.
+#[derive(Debug, Clone)]
+enum Undo<T> {
+    // An element was inserted at this rank.
+    Inserted(usize),
+    // The element at this rank replaced an equal one.
+    Replaced(usize, T),
+    Removed(T),
+}
+
+// A point in the history of a JournaledBST that it can be rolled back to.
+// It records the serial number of the last entry in the log, which tells
+// whether the log has since been rolled back past that entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    len: usize,
+    last: u64,
+}
+
+// A tree that keeps an undo log of its mutations, so that a batch of them
+// can be reverted without cloning the whole tree. Elements that are
+// replaced or removed move into the log, which is why the mutators do not
+// hand them back, and stay there until the log is committed.
+#[derive(Debug, Clone)]
+pub struct JournaledBST<T> {
+    tree: BST<T>,
+    log: Vec<(u64, Undo<T>)>,
+    // Serial numbers are never reused, even after a rollback.
+    serial: u64,
+    // The serial number of the last entry committed, which stands in for
+    // the last entry when the log is empty.
+    committed: u64,
+}
+
+#[allow(clippy::new_without_default)]
+impl<T: Ord> JournaledBST<T> {
+    pub fn new() -> Self {
+        JournaledBST::from(BST::new())
+    }
+
+    pub fn as_tree(&self) -> &BST<T> {
+        &self.tree
+    }
+
+    // Gives up the tree along with the log.
+    pub fn into_inner(self) -> BST<T> {
+        self.tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn contains<Q: ?Sized + Ord>(&self, elem: &Q) -> bool where T: Borrow<Q> {
+        self.tree.member(elem)
+    }
+
+    fn record(&mut self, undo: Undo<T>) {
+        self.serial += 1;
+        self.log.push((self.serial, undo));
+    }
+
+    // Inserts the element, replacing any equal one, and returns whether it
+    // was not already present.
+    pub fn insert(&mut self, elem: T) -> bool {
+        let rank = self.tree.rank(&elem);
+        match self.tree.insert(elem) {
+            None => {
+                self.record(Undo::Inserted(rank));
+                true
+            },
+            Some(old) => {
+                self.record(Undo::Replaced(rank, old));
+                false
+            },
+        }
+    }
+
+    pub fn remove<Q: ?Sized + Ord>(&mut self, elem: &Q) -> bool where T: Borrow<Q> {
+        match self.tree.delete(elem) {
+            None => false,
+            Some(old) => {
+                self.record(Undo::Removed(old));
+                true
+            },
+        }
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint { len: self.log.len(), last: self.log.last().map_or(self.committed, |entry| entry.0) }
+    }
+
+    // Undoes every mutation since the checkpoint. Checkpoints taken after
+    // it can no longer be rolled back to, and neither can checkpoints
+    // taken before the log was last committed.
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        let last = match checkpoint.len {
+            0 => Some(self.committed),
+            len => self.log.get(len - 1).map(|entry| entry.0),
+        };
+        let valid = last == Some(checkpoint.last);
+        assert!(valid, "rollback to a checkpoint that is no longer in the log");
+        while self.log.len() > checkpoint.len {
+            let (_, undo) = self.log.pop().unwrap();
+            match undo {
+                Undo::Inserted(rank) => {
+                    let ptr = self.tree.select_node(rank).expect("rollback finds no element at the inserted rank");
+                    self.tree.delete_node(ptr);
+                },
+                Undo::Replaced(rank, old) => {
+                    let ptr = self.tree.select_node(rank).expect("rollback finds no element at the replaced rank");
+                    self.tree.deref_mut(&ptr).elem = old;
+                },
+                Undo::Removed(old) => {
+                    self.tree.insert_absent(old);
+                },
+            }
+        }
+    }
+
+    // Empties the log, dropping the elements kept for undoing.
+    pub fn commit(&mut self) {
+        self.log.clear();
+        self.committed = self.serial;
+    }
+}
+
+impl<T: Ord> From<BST<T>> for JournaledBST<T> {
+    fn from(tree: BST<T>) -> Self {
+        JournaledBST { tree, log: Vec::new(), serial: 0, committed: 0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::JournaledBST;
+    use BST;
+
+    #[test]
+    fn rollback() {
+        let mut tree = JournaledBST::from(BST::from_sorted_iter(0..10u32));
+        let start = tree.checkpoint();
+        assert!(tree.insert(20));
+        assert!(!tree.insert(5));
+        assert!(tree.remove(&3));
+        assert!(!tree.remove(&30));
+        let middle = tree.checkpoint();
+        for i in 10..100 {
+            tree.insert(i);
+        }
+        for i in 0..50 {
+            tree.remove(&(i * 2));
+        }
+        tree.rollback(middle);
+        assert!(tree.as_tree().iter().cloned().eq((0..10).filter(|&x| x != 3).chain(Some(20))));
+        assert_eq!(tree.as_tree().try_self_check(), Ok(()));
+        tree.rollback(start);
+        assert!(tree.as_tree().iter().cloned().eq(0..10));
+
+        tree.insert(42);
+        tree.commit();
+        let committed = tree.checkpoint();
+        tree.remove(&42);
+        tree.rollback(committed);
+        assert!(tree.contains(&42));
+        assert_eq!(tree.len(), 11);
+        let elems: Vec<u32> = tree.into_inner().into_iter().collect();
+        assert_eq!(elems.len(), 11);
+    }
+
+    #[test]
+    #[should_panic(expected = "rollback to a checkpoint that is no longer in the log")]
+    fn stale_checkpoint() {
+        let mut tree = JournaledBST::new();
+        let start = tree.checkpoint();
+        tree.insert(1);
+        let later = tree.checkpoint();
+        tree.rollback(start);
+        tree.insert(2);
+        tree.rollback(later);
+    }
+
+    #[test]
+    #[should_panic(expected = "rollback to a checkpoint that is no longer in the log")]
+    fn committed_checkpoint() {
+        let mut tree = JournaledBST::new();
+        let start = tree.checkpoint();
+        tree.insert(1);
+        tree.commit();
+        tree.rollback(start);
+    }
+}
@@ -1,30 +1,219 @@
-use std::cmp::Ordering;
-use std::ops::Not;
+#![no_std]
+#![cfg_attr(feature = "nightly", feature(allocator_api))]
 
-#[derive(Debug, Clone)]
-pub struct BST<T> {
-    nodes: Vec<Option<Node<T>>>,
-    root: Option<Ptr>,
-    deleted_indices: Vec<Ptr>
+#[cfg(any(feature = "std", test))]
+extern crate std;
+#[macro_use]
+extern crate alloc;
+extern crate allocator_api2;
+#[cfg(feature = "rand")]
+extern crate rand;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
+use core::mem;
+use core::ops::{self, Bound, Index, Not, RangeBounds};
+
+use alloc::vec::Vec;
+
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::vec::Vec as ArenaVec;
+
+pub mod augment;
+mod build;
+mod by;
+mod check;
+mod cow;
+mod cursor;
+mod forest;
+mod frozen;
+mod handle;
+mod index;
+pub mod interval;
+mod iter;
+mod join;
+mod journal;
+#[cfg(feature = "rayon")]
+mod par;
+#[cfg(feature = "rand")]
+mod random;
+pub mod map;
+mod merge;
+pub mod multiset;
+pub mod queue;
+mod render;
+#[cfg(feature = "std")]
+mod serialize;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod stats;
+pub mod tagged;
+mod traverse;
+
+pub use augment::{Augment, AugmentedBST};
+pub use by::BSTBy;
+pub use check::CorruptionError;
+pub use cow::SharedBST;
+pub use cursor::{Cursor, CursorMut};
+pub use forest::{Forest, TreeId};
+pub use frozen::FrozenBST;
+pub use handle::Handle;
+pub use index::{CapacityError, NodeIndex};
+pub use interval::IntervalTree;
+pub use iter::{Drain, ExtractIf, IntoIter, Iter, Levels, Range};
+pub use journal::{Checkpoint, JournaledBST};
+pub use map::LLRBMap;
+pub use merge::{Diff, DiffEntry, Difference, Intersection, Merge, MergePolicy, SymmetricDifference, Union};
+pub use multiset::BSTMultiSet;
+pub use queue::TreePriorityQueue;
+#[cfg(feature = "rayon")]
+pub use par::ParIter;
+#[cfg(feature = "std")]
+pub use serialize::Codec;
+#[cfg(feature = "serde")]
+pub use serde_impl::Strict;
+pub use render::{TikzLabel, TikzOptions};
+pub use stats::{MemoryReport, ShapeStats};
+pub use tagged::TaggedBST;
+pub use traverse::Traversal;
+
+// The arena and free list are allocated with A, which is the global
+// allocator unless the tree is created with new_in. The parameter is there
+// on stable too, as the Allocator polyfill from allocator-api2; the nightly
+// feature makes the polyfill the Allocator of core.
+//
+// Node indices are stored as I, which is u32 unless the tree is declared
+// with another NodeIndex. Not every API is generic over A and I yet; see
+// new_in for the ones that are.
+#[derive(Clone)]
+pub struct BST<T, A: Allocator = Global, I: NodeIndex = u32> {
+    nodes: ArenaVec<Option<Node<T, I>>, A>,
+    root: Option<Ptr<I>>,
+    deleted_indices: ArenaVec<Ptr<I>, A>,
+    // Recomputes any per-subtree data kept in the element of a node, given
+    // that its children are up to date. It runs along with update_size.
+    augment: Option<AugmentFn<T, A, I>>,
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Ptr(usize);
+type AugmentFn<T, A, I> = fn(&mut BST<T, A, I>, Ptr<I>);
+
+// Node indices are stored offset by one, so that Option<Ptr> takes no more
+// room than Ptr: four bytes with the default u32 indices.
+//
+// In debug builds a Ptr also carries the generation of its slot, which is
+// bumped whenever the slot is reused, so that a stale Ptr is caught by deref
+// instead of silently reading another node. A free slot's generation lives
+// in its entry in deleted_indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Ptr<I: NodeIndex = u32> {
+    index: I::NonZero,
+    #[cfg(debug_assertions)]
+    generation: u32,
+}
+
+impl<I: NodeIndex> Ptr<I> {
+    // Returns None for a slot past the largest index that I can store.
+    fn try_new(index: usize) -> Option<Self> {
+        I::encode(index).map(|index| Ptr {
+            index,
+            #[cfg(debug_assertions)]
+            generation: 0,
+        })
+    }
+
+    // For slots known to fit, such as those of nodes already in a tree.
+    fn new(index: usize) -> Self {
+        match Ptr::try_new(index) {
+            Some(ptr) => ptr,
+            None => panic!("BST cannot hold more than {} nodes", index),
+        }
+    }
+
+    fn index(self) -> usize {
+        I::decode(self.index)
+    }
+
+    // Returns the Ptr for a freed slot once it is reused.
+    #[cfg(debug_assertions)]
+    fn reused(self) -> Self {
+        Ptr { generation: self.generation.wrapping_add(1), ..self }
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn reused(self) -> Self {
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {Red, Black}
 
 #[derive(Debug, Clone, Copy)]
-enum Color {Red, Black}
+enum Side {Left, Right}
 
 #[derive(Debug, Clone)]
-struct Node<T> {
+struct Node<T, I: NodeIndex = u32> {
     elem: T,
     color: Color,
-    left: Option<Ptr>,
-    right: Option<Ptr>,
+    left: Option<Ptr<I>>,
+    right: Option<Ptr<I>>,
+    // Number of nodes in the subtree rooted at this node.
+    size: usize,
+    // Kept up to date by update_size, which every change of links is
+    // followed by. The parent of the root is left stale, so walks upwards
+    // must stop at the root rather than at a missing parent.
+    #[cfg(feature = "parent-pointers")]
+    parent: Option<Ptr<I>>,
+    #[cfg(debug_assertions)]
+    generation: u32,
 }
 
-impl<T: Ord> Node<T> {
+impl<T, I: NodeIndex> Node<T, I> {
     fn new(elem: T, color: Color) -> Self {
-        Node { elem: elem, color: color, left: None, right: None }
+        Node {
+            elem, color, left: None, right: None, size: 1,
+            #[cfg(feature = "parent-pointers")]
+            parent: None,
+            #[cfg(debug_assertions)]
+            generation: 0,
+        }
+    }
+
+    // A node in the same place and with the same links, holding another
+    // element.
+    fn with_elem<U>(&self, elem: U) -> Node<U, I> {
+        Node {
+            elem, color: self.color, left: self.left, right: self.right, size: self.size,
+            #[cfg(feature = "parent-pointers")]
+            parent: self.parent,
+            #[cfg(debug_assertions)]
+            generation: self.generation,
+        }
+    }
+
+    // Records the Ptr through which the node is now reachable, which must
+    // be done whenever a node is placed in a slot other than a fresh one.
+    #[cfg(debug_assertions)]
+    fn stamp(&mut self, ptr: Ptr<I>) {
+        self.generation = ptr.generation;
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn stamp(&mut self, _: Ptr<I>) {}
+
+    #[cfg(debug_assertions)]
+    fn is_current(&self, ptr: &Ptr<I>) -> bool {
+        self.generation == ptr.generation
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn is_current(&self, _: &Ptr<I>) -> bool {
+        true
     }
 }
 
@@ -38,21 +227,41 @@ impl Not for Color {
     }
 }
 
-impl<T: Ord> BST<T> {
-    fn deref(&self, i: &Ptr) -> &Node<T> {
-        self.nodes[i.0].as_ref().expect("deref encounters a reference to a deleted node")
+// The structural operations only rely on the order of elements through
+// the comparators they are given, so they are available for any T.
+impl<T, A: Allocator, I: NodeIndex> BST<T, A, I> {
+    // The fallible counterparts of deref and deref_mut. Stale references
+    // are only detected in debug builds, where Ptr carries a generation.
+    fn try_deref(&self, i: &Ptr<I>) -> Result<&Node<T, I>, CorruptionError> {
+        match self.nodes.get(i.index()) {
+            None => Err(CorruptionError::OutOfBounds(i.index())),
+            Some(None) => Err(CorruptionError::Deleted(i.index())),
+            Some(Some(node)) if !node.is_current(i) => Err(CorruptionError::Stale(i.index())),
+            Some(Some(node)) => Ok(node),
+        }
     }
 
-    fn deref_mut(&mut self, i: &Ptr) -> &mut Node<T> {
-        self.nodes[i.0].as_mut().expect("deref_mut encounters a reference to a deleted node")
+    fn try_deref_mut(&mut self, i: &Ptr<I>) -> Result<&mut Node<T, I>, CorruptionError> {
+        match self.nodes.get_mut(i.index()) {
+            None => Err(CorruptionError::OutOfBounds(i.index())),
+            Some(None) => Err(CorruptionError::Deleted(i.index())),
+            Some(Some(node)) if !node.is_current(i) => Err(CorruptionError::Stale(i.index())),
+            Some(Some(node)) => Ok(node),
+        }
     }
 
-    pub fn new() -> Self {
-        BST{ nodes: Vec::new(), root: None, deleted_indices: Vec::new() }
+    fn deref(&self, i: &Ptr<I>) -> &Node<T, I> {
+        match self.try_deref(i) {
+            Ok(node) => node,
+            Err(err) => panic!("deref encounters {}", err),
+        }
     }
 
-    pub fn singleton(elem: T) -> Self {
-        BST{ nodes: vec![Some(Node::new(elem, Color::Black))], root: Some(Ptr(0)), deleted_indices: Vec::new() }
+    fn deref_mut(&mut self, i: &Ptr<I>) -> &mut Node<T, I> {
+        match self.try_deref_mut(i) {
+            Ok(node) => node,
+            Err(err) => panic!("deref_mut encounters {}", err),
+        }
     }
 
     pub fn len(&self) -> usize {
@@ -63,55 +272,129 @@ impl<T: Ord> BST<T> {
         self.root.is_none()
     }
 
-    fn member_impl(&self, ptr: &Option<Ptr>, elem: &T) -> bool {
-        match *ptr {
-            None => false,
-            Some(ref ptr) => {
-                let node = self.deref(ptr);
-                match node.elem.cmp(elem) {
-                    Ordering::Less => self.member_impl(&node.right, elem),
-                    Ordering::Greater => self.member_impl(&node.left, elem),
-                    Ordering::Equal => true,
-                }
+    // Looks up the node for which cmp returns Equal. The comparator gives
+    // the ordering of the sought element relative to the one passed in.
+    pub(crate) fn find_by<F: Fn(&T) -> Ordering>(&self, cmp: F) -> Option<Ptr<I>> {
+        let mut node = self.root;
+        while let Some(ptr) = node {
+            let n = self.deref(&ptr);
+            match cmp(&n.elem) {
+                Ordering::Less => node = n.left,
+                Ordering::Greater => node = n.right,
+                Ordering::Equal => return Some(ptr),
             }
         }
+        None
+    }
+
+    fn is_red(&self, ptr: &Option<Ptr<I>>) -> bool {
+        ptr.as_ref().is_some_and(|p| match self.deref(p).color { Color::Red => true, Color::Black => false })
+    }
+
+    fn size(&self, ptr: &Option<Ptr<I>>) -> usize {
+        ptr.as_ref().map_or(0, |p| self.deref(p).size)
+    }
+
+    // Points the children of h back at it. Code that relinks nodes without
+    // going through update_size, such as when moving them to other slots,
+    // must call this itself.
+    #[cfg(feature = "parent-pointers")]
+    fn adopt_children(&mut self, h: Ptr<I>) {
+        let node = self.deref(&h);
+        for child in node.left.into_iter().chain(node.right) {
+            self.deref_mut(&child).parent = Some(h);
+        }
+    }
+
+    #[cfg(not(feature = "parent-pointers"))]
+    fn adopt_children(&mut self, _: Ptr<I>) {}
+
+    fn update_size(&mut self, h: Ptr<I>) {
+        let size = 1 + self.size(&self.deref(&h).left) + self.size(&self.deref(&h).right);
+        self.deref_mut(&h).size = size;
+        self.adopt_children(h);
+        if let Some(augment) = self.augment {
+            augment(self, h);
+        }
     }
 
-    pub fn member(&self, elem: &T) -> bool {
-        self.member_impl(&self.root, elem)
+    // The child on the given side, which the caller relies on being there.
+    fn try_child(&self, h: Ptr<I>, side: Side) -> Result<Ptr<I>, CorruptionError> {
+        let node = self.try_deref(&h)?;
+        let child = match side {
+            Side::Left => node.left,
+            Side::Right => node.right,
+        };
+        child.ok_or(CorruptionError::MissingChild(h.index()))
     }
 
-    fn is_red(&self, ptr: &Option<Ptr>) -> bool {
-        ptr.as_ref().map_or(false, |p| match self.deref(p).color { Color::Red => true, Color::Black => false })
+    // Checks the links that a rotation about h reads before it changes
+    // anything, so that it either fails untouched or succeeds.
+    fn try_check_rotation(&self, h: Ptr<I>, x: Ptr<I>) -> Result<(), CorruptionError> {
+        let (h, x) = (self.try_deref(&h)?, self.try_deref(&x)?);
+        for child in h.left.iter().chain(h.right.iter()).chain(x.left.iter()).chain(x.right.iter()) {
+            self.try_deref(child)?;
+        }
+        Ok(())
     }
 
-    fn rotate_left(&mut self, h: Ptr) -> Ptr {
-        let x : Ptr = self.deref(&h).right.expect("rotate left on node whose left child is nil");
+    fn try_rotate_left(&mut self, h: Ptr<I>) -> Result<Ptr<I>, CorruptionError> {
+        let x = self.try_child(h, Side::Right)?;
+        self.try_check_rotation(h, x)?;
         self.deref_mut(&h).right = self.deref(&x).left;
         self.deref_mut(&x).left = Some(h);
         self.deref_mut(&x).color = self.deref(&h).color;
         self.deref_mut(&h).color = Color::Red;
-        x
+        self.update_size(h);
+        self.update_size(x);
+        Ok(x)
     }
 
-    fn rotate_right(&mut self, h: Ptr) -> Ptr {
-        let x : Ptr = self.deref(&h).left.expect("rotate right on node whose left child is nil");
+    fn try_rotate_right(&mut self, h: Ptr<I>) -> Result<Ptr<I>, CorruptionError> {
+        let x = self.try_child(h, Side::Left)?;
+        self.try_check_rotation(h, x)?;
         self.deref_mut(&h).left = self.deref(&x).right;
         self.deref_mut(&x).right = Some(h);
         self.deref_mut(&x).color = self.deref(&h).color;
         self.deref_mut(&h).color = Color::Red;
-        x
+        self.update_size(h);
+        self.update_size(x);
+        Ok(x)
+    }
+
+    fn try_move_red_up_or_down(&mut self, h: Ptr<I>) -> Result<(), CorruptionError> {
+        let left = self.try_child(h, Side::Left)?;
+        let right = self.try_child(h, Side::Right)?;
+        self.try_deref(&left)?;
+        self.try_deref(&right)?;
+        for ptr in &[h, left, right] {
+            self.deref_mut(ptr).color = !self.deref(ptr).color;
+        }
+        Ok(())
+    }
+
+    fn rotate_left(&mut self, h: Ptr<I>) -> Ptr<I> {
+        match self.try_rotate_left(h) {
+            Ok(x) => x,
+            Err(err) => panic!("rotate_left encounters {}", err),
+        }
     }
 
-    fn move_red_up_or_down(&mut self, h: Ptr) {
-        self.deref_mut(&h).color = !self.deref(&h).color;
-        let left : Ptr = self.deref(&h).left.expect("move red up/down on node whose left child is nil");
-        self.deref_mut(&left).color = !self.deref(&left).color;
-        let right: Ptr = self.deref(&h).right.expect("move red up/down on node whose right child is nil");
-        self.deref_mut(&right).color = !self.deref(&right).color;
+    fn rotate_right(&mut self, h: Ptr<I>) -> Ptr<I> {
+        match self.try_rotate_right(h) {
+            Ok(x) => x,
+            Err(err) => panic!("rotate_right encounters {}", err),
+        }
+    }
+
+    fn move_red_up_or_down(&mut self, h: Ptr<I>) {
+        if let Err(err) = self.try_move_red_up_or_down(h) {
+            panic!("move_red_up_or_down encounters {}", err);
+        }
     }
 
-    fn fixup(&mut self, mut node: Ptr) -> Ptr {
+    fn fixup(&mut self, mut node: Ptr<I>) -> Ptr<I> {
+        self.update_size(node);
         if self.is_red(&self.deref(&node).right) && !self.is_red(&self.deref(&node).left) {
             node = self.rotate_left(node);
         }
@@ -124,262 +407,2911 @@ impl<T: Ord> BST<T> {
         node
     }
 
-    fn insert_impl(&mut self, node: Option<Ptr>, elem: T) -> Ptr {
-        match node {
-            None => {
-                let new = Some(Node::new(elem, Color::Red));
-                if let Some(index) = self.deleted_indices.pop() {
-                    self.nodes[index.0] = new;
-                    index
-                } else {
-                    self.nodes.push(new);
-                    Ptr(self.nodes.len() - 1)
-                }
-            },
-            Some(node) => {
-                match self.deref(&node).elem.cmp(&elem) {
-                    Ordering::Less => {
-                        let right : Option<Ptr> = self.deref(&node).right;
-                        let new_right : Ptr = self.insert_impl(right, elem);
-                        self.deref_mut(&node).right = Some(new_right);
-                    },
-                    Ordering::Greater => {
-                        let left : Option<Ptr> = self.deref(&node).left;
-                        let new_left : Ptr = self.insert_impl(left, elem);
-                        self.deref_mut(&node).left = Some(new_left);
-                    },
-                    Ordering::Equal => self.deref_mut(&node).elem = elem,
-                }
-                self.fixup(node)
+    pub(crate) fn alloc(&mut self, elem: T) -> Ptr<I> {
+        let mut new = Node::new(elem, Color::Red);
+        if let Some(freed) = self.deleted_indices.pop() {
+            let ptr = freed.reused();
+            new.stamp(ptr);
+            self.nodes[ptr.index()] = Some(new);
+            ptr
+        } else {
+            let ptr = Ptr::new(self.nodes.len());
+            self.nodes.push(Some(new));
+            ptr
+        }
+    }
+
+    // Whether alloc can find a slot, either a freed one or one that I can
+    // still index.
+    fn has_room(&self) -> bool {
+        !self.deleted_indices.is_empty() || Ptr::<I>::try_new(self.nodes.len()).is_some()
+    }
+
+    // Every slot of the arena as deleted_indices would list it once the
+    // slot is freed, with the generation of the node it holds, if any.
+    #[cfg(debug_assertions)]
+    fn stale_slots(&self) -> Vec<Ptr<I>> {
+        let live = self.nodes.iter().enumerate().filter_map(|(i, node)| node.as_ref().map(|node| Ptr { generation: node.generation, ..Ptr::new(i) }));
+        self.deleted_indices.iter().cloned().chain(live).collect()
+    }
+
+    pub(crate) fn free(&mut self, ptr: Ptr<I>) -> T {
+        self.deleted_indices.push(ptr);
+        self.nodes[ptr.index()].take().expect("free encounters a reference to a deleted node").elem
+    }
+
+    // Walks back up a path recorded during a descent, hanging each new
+    // subtree off its parent on the side it was reached from and fixing up
+    // the parent. Returns the new root of the whole path.
+    fn fixup_path(&mut self, path: Vec<(Ptr<I>, Side)>, mut subtree: Option<Ptr<I>>) -> Option<Ptr<I>> {
+        for (parent, side) in path.into_iter().rev() {
+            match side {
+                Side::Left => self.deref_mut(&parent).left = subtree,
+                Side::Right => self.deref_mut(&parent).right = subtree,
+            }
+            subtree = Some(self.fixup(parent));
+        }
+        subtree
+    }
+
+    // The comparator gives the ordering of the new element relative to an
+    // element already in the tree. Returns the new root and the node now
+    // holding the element, along with the element that was replaced, if any.
+    fn insert_impl<F: Fn(&T, &T) -> Ordering>(&mut self, mut node: Option<Ptr<I>>, elem: T, cmp: F) -> (Ptr<I>, Ptr<I>, Option<T>) {
+        let mut path = Vec::new();
+        while let Some(ptr) = node {
+            match cmp(&elem, &self.deref(&ptr).elem) {
+                Ordering::Greater => {
+                    path.push((ptr, Side::Right));
+                    node = self.deref(&ptr).right;
+                },
+                Ordering::Less => {
+                    path.push((ptr, Side::Left));
+                    node = self.deref(&ptr).left;
+                },
+                Ordering::Equal => {
+                    // Replacing an element leaves the shape of the tree
+                    // untouched, but the new element may change what the
+                    // augmentation derives from it.
+                    let old = mem::replace(&mut self.deref_mut(&ptr).elem, elem);
+                    if let Some(augment) = self.augment {
+                        augment(self, ptr);
+                        for &(ancestor, _) in path.iter().rev() {
+                            augment(self, ancestor);
+                        }
+                    }
+                    return (path.first().map_or(ptr, |&(root, _)| root), ptr, Some(old));
+                },
             }
         }
+        let new = self.alloc(elem);
+        (self.fixup_path(path, Some(new)).unwrap(), new, None)
     }
 
-    pub fn insert(&mut self, elem: T) {
-        let old_root : Option<Ptr> = self.root;
-        let new_root : Ptr = self.insert_impl(old_root, elem);
+    // Rotations never move elements between nodes, so the node returned
+    // here keeps holding the element until it is removed.
+    pub(crate) fn insert_node_by<F: Fn(&T, &T) -> Ordering>(&mut self, elem: T, cmp: F) -> (Ptr<I>, Option<T>) {
+        let old_root : Option<Ptr<I>> = self.root;
+        let (new_root, node, replaced) = self.insert_impl(old_root, elem, cmp);
         self.root = Some(new_root);
         self.deref_mut(&new_root).color = Color::Black;
+        (node, replaced)
+    }
+
+    pub(crate) fn insert_by<F: Fn(&T, &T) -> Ordering>(&mut self, elem: T, cmp: F) -> Option<T> {
+        self.insert_node_by(elem, cmp).1
     }
 
     pub fn clear(&mut self) {
         self.root = None;
-        self.nodes.clear();
-        self.deleted_indices.clear();
+        // Debug builds keep the slots, all of them free, so that their
+        // generations survive and handles from before the clear are still
+        // caught as stale.
+        #[cfg(debug_assertions)]
+        {
+            let stale = self.stale_slots();
+            self.nodes.iter_mut().for_each(|node| *node = None);
+            self.deleted_indices.clear();
+            self.deleted_indices.extend(stale);
+        }
+        #[cfg(not(debug_assertions))]
+        {
+            self.nodes.clear();
+            self.deleted_indices.clear();
+        }
     }
 
-    fn move_red_left(&mut self, mut h: Ptr) -> Ptr {
-        self.move_red_up_or_down(h);
-        if self.is_red(&self.deref(&self.deref(&h).right.unwrap()).left) {
-            self.deref_mut(&h).right = self.deref(&h).right.map(|right| self.rotate_right(right));
-            h = self.rotate_left(h);
-            self.move_red_up_or_down(h);
+    fn try_move_red_left(&mut self, mut h: Ptr<I>) -> Result<Ptr<I>, CorruptionError> {
+        self.try_move_red_up_or_down(h)?;
+        let right = self.try_child(h, Side::Right)?;
+        if self.is_red(&self.try_deref(&right)?.left) {
+            let right = self.try_rotate_right(right)?;
+            self.deref_mut(&h).right = Some(right);
+            h = self.try_rotate_left(h)?;
+            self.try_move_red_up_or_down(h)?;
         }
-        h
+        Ok(h)
     }
 
-    fn take_min_impl(&mut self, mut node: Ptr) -> (T, Option<Ptr>) {
-        match self.deref(&node).left {
-            None => {
-                // The current node is the minimum in the tree.
-                self.deleted_indices.push(node);
-                (self.nodes[node.0].take().expect("take_min_impl: leftmost node is already deleted").elem, None)
-            },
-            Some(left) => {
-                // We need to make sure the next node is not a 2-node.
-                // Making the next node not a 2-node means either it or
-                // its left child is red (or both, in the case of a 4-node).
-                // This checks if this is violated.
-                if !self.is_red(&Some(left)) && !self.is_red(&self.deref(&left).left) {
-                    node = self.move_red_left(node);
-                }
-                let left = self.deref_mut(&node).left.unwrap();
-                let (min, new_left) = self.take_min_impl(left);
-                self.deref_mut(&node).left = new_left;
-                (min, Some(self.fixup(node)))
+    fn try_move_red_right(&mut self, mut h: Ptr<I>) -> Result<Ptr<I>, CorruptionError> {
+        self.try_move_red_up_or_down(h)?;
+        let left = self.try_child(h, Side::Left)?;
+        if self.is_red(&self.try_deref(&left)?.left) {
+            h = self.try_rotate_right(h)?;
+            self.try_move_red_up_or_down(h)?;
+        }
+        Ok(h)
+    }
+
+    fn move_red_left(&mut self, h: Ptr<I>) -> Ptr<I> {
+        match self.try_move_red_left(h) {
+            Ok(h) => h,
+            Err(err) => panic!("move_red_left encounters {}", err),
+        }
+    }
+
+    fn move_red_right(&mut self, h: Ptr<I>) -> Ptr<I> {
+        match self.try_move_red_right(h) {
+            Ok(h) => h,
+            Err(err) => panic!("move_red_right encounters {}", err),
+        }
+    }
+
+    fn take_min_impl(&mut self, node: Ptr<I>) -> (T, Option<Ptr<I>>) {
+        let (min, rest) = self.detach_min_impl(node);
+        (self.free(min), rest)
+    }
+
+    // Unlinks the minimum node of a subtree without freeing it, so that
+    // the element stays in its slot. The detached node is a leaf.
+    pub(crate) fn detach_min_impl(&mut self, mut node: Ptr<I>) -> (Ptr<I>, Option<Ptr<I>>) {
+        let mut path = Vec::new();
+        while let Some(left) = self.deref(&node).left {
+            // We need to make sure the next node is not a 2-node.
+            // Making the next node not a 2-node means either it or
+            // its left child is red (or both, in the case of a 4-node).
+            // This checks if this is violated.
+            if !self.is_red(&Some(left)) && !self.is_red(&self.deref(&left).left) {
+                node = self.move_red_left(node);
             }
+            path.push((node, Side::Left));
+            node = self.deref(&node).left.unwrap();
         }
+        // The current node is the minimum in the tree.
+        (node, self.fixup_path(path, None))
     }
 
-    pub fn take_min(&mut self) -> Option<T> {
-        self.root.map(
-            |root|
-            if self.deref(&root).left.is_none() {
-                // The tree has only one element.
-                let rv = self.nodes.swap_remove(root.0).unwrap().elem;
-                self.root = None;
-                self.deleted_indices.clear();
-                self.nodes.clear();
-                rv
-            } else {
-                // The tree has more than one element.
-                let (min, new_root) = self.take_min_impl(root);
-                self.root = new_root;
-                self.deref_mut(&new_root.unwrap()).color = Color::Black;
-                min
-            })
+    fn take_max_impl(&mut self, mut node: Ptr<I>) -> (T, Option<Ptr<I>>) {
+        let mut path = Vec::new();
+        loop {
+            if self.is_red(&self.deref(&node).left) {
+                node = self.rotate_right(node);
+            }
+            let right = match self.deref(&node).right {
+                Some(right) => right,
+                None => break,
+            };
+            // Symmetric to take_min_impl: make sure the next node is
+            // not a 2-node before descending into it.
+            if !self.is_red(&Some(right)) && !self.is_red(&self.deref(&right).left) {
+                node = self.move_red_right(node);
+            }
+            path.push((node, Side::Right));
+            node = self.deref(&node).right.unwrap();
+        }
+        // The current node is the maximum in the tree.
+        self.deleted_indices.push(node);
+        let max = self.nodes[node.index()].take().expect("take_max_impl: rightmost node is already deleted").elem;
+        (max, self.fixup_path(path, None))
     }
 
-    fn print_structure_inner(&self, node: Option<Ptr>) {
-        match node {
-            None => print!("[missing]"),
-            Some(node_id) => {
-                print!("{{ node ");
-                let node = self.deref(&node_id);
-                if let Color::Red = node.color {
-                    print!("[draw=red]");
-                }
-                print!("{{{:?}}} ", node_id.0); // Prints order of insertion
-                if let Color::Red = node.color {
-                    print!("edge from parent[red]");
+    // The comparator locates the element to delete given a node and the
+    // number of elements ordered before the subtree rooted at that node.
+    fn delete_impl<F: Fn(&Self, Ptr<I>, usize) -> Ordering>(&mut self, mut node: Ptr<I>, mut offset: usize, cmp: &F) -> (T, Option<Ptr<I>>) {
+        // The element is known to be present in the subtree rooted at node.
+        let mut path = Vec::new();
+        loop {
+            if cmp(self, node, offset) == Ordering::Less {
+                let left = self.deref(&node).left.expect("delete_impl: element is missing from the tree");
+                if !self.is_red(&Some(left)) && !self.is_red(&self.deref(&left).left) {
+                    node = self.move_red_left(node);
                 }
-                print!(" child ");
-                self.print_structure_inner(node.left);
-                print!(" child ");
-                self.print_structure_inner(node.right);
-                print!(" }}");
+                path.push((node, Side::Left));
+                node = self.deref(&node).left.unwrap();
+                continue;
+            }
+            if self.is_red(&self.deref(&node).left) {
+                node = self.rotate_right(node);
+            }
+            if cmp(self, node, offset) == Ordering::Equal && self.deref(&node).right.is_none() {
+                // The current node is a leaf, since a node without a right
+                // child cannot have a black left child, and the red one
+                // has just been rotated away.
+                self.deleted_indices.push(node);
+                let removed = self.nodes[node.index()].take().expect("delete_impl: node is already deleted").elem;
+                return (removed, self.fixup_path(path, None));
+            }
+            let right = self.deref(&node).right.expect("delete_impl: element is missing from the tree");
+            if !self.is_red(&Some(right)) && !self.is_red(&self.deref(&right).left) {
+                node = self.move_red_right(node);
+            }
+            let right = self.deref(&node).right.unwrap();
+            if cmp(self, node, offset) == Ordering::Equal {
+                // Replace the current node with its successor, which is
+                // taken out of the right subtree. Moving the node rather than
+                // the element keeps every element in the slot it started in.
+                let (successor, new_right) = self.detach_min_impl(right);
+                let (left, color) = (self.deref(&node).left, self.deref(&node).color);
+                self.deref_mut(&successor).left = left;
+                self.deref_mut(&successor).color = color;
+                path.push((successor, Side::Right));
+                return (self.free(node), self.fixup_path(path, new_right));
             }
+            offset += self.size(&self.deref(&node).left) + 1;
+            path.push((node, Side::Right));
+            node = right;
         }
     }
 
-    pub fn print_structure(&self) {
-        match self.root {
-            None => (),
-            Some(ref node_id) => {
-                println!("%% Put these in your preamble\n\
-                          \\usepackage{{tikz}}\n\
-                          \\usetikzlibrary{{graphdrawing}}\n\
-                          \\usegdlibrary{{trees}}\n\
-                          \\definecolor{{red}}{{RGB}}{{171,50,37}}\n\n\
-                          %% Put these in the document body\n\
-                          \\tikz [binary tree layout, nodes={{draw,circle}}, font=\\sffamily, semithick] \
-                          \\node");
-                let node = self.deref(&node_id);
-                print!("{{{:?}}} child ", node_id.0); // Prints order of insertion
-                self.print_structure_inner(node.left);
-                print!(" child ");
-                self.print_structure_inner(node.right);
-                println!(";");
-            }
+    fn delete_located<F: Fn(&Self, Ptr<I>, usize) -> Ordering>(&mut self, cmp: F) -> T {
+        let root = self.root.unwrap();
+        let (removed, new_root) = self.delete_impl(root, 0, &cmp);
+        self.root = new_root;
+        match new_root {
+            None => self.clear(),
+            Some(new_root) => self.deref_mut(&new_root).color = Color::Black,
+        }
+        removed
+    }
+
+    pub(crate) fn delete_by<F: Fn(&T) -> Ordering>(&mut self, cmp: F) -> Option<T> {
+        self.find_by(&cmp)?;
+        Some(self.delete_located(|tree, ptr, _| cmp(&tree.deref(&ptr).elem)))
+    }
+
+    // Deletes the element with the given rank.
+    pub(crate) fn delete_at(&mut self, index: usize) -> Option<T> {
+        if index >= self.len() {
+            return None;
         }
+        Some(self.delete_located(|tree, ptr, offset| index.cmp(&(offset + tree.size(&tree.deref(&ptr).left)))))
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::BST;
+impl<T: Ord> BST<T> {
+    pub fn new() -> Self {
+        BST{ nodes: ArenaVec::new(), root: None, deleted_indices: ArenaVec::new(), augment: None }
+    }
 
-    #[test]
-    fn basics() {
-        let e: BST<i32> = BST::new();
-        let s = BST::singleton(2);
+    pub fn with_capacity(capacity: usize) -> Self {
+        BST{ nodes: ArenaVec::with_capacity(capacity), root: None, deleted_indices: ArenaVec::new(), augment: None }
+    }
 
-        assert_eq!(false, e.member(&1));
-        assert_eq!(false, e.member(&2));
-        assert_eq!(false, e.member(&3));
+    pub fn singleton(elem: T) -> Self {
+        let mut tree = BST::new();
+        tree.nodes.push(Some(Node::new(elem, Color::Black)));
+        tree.root = Some(Ptr::new(0));
+        tree
+    }
+}
 
-        assert_eq!(false, s.member(&1));
-        assert_eq!(true, s.member(&2));
-        assert_eq!(false, s.member(&3));
+// Both the arena and the free list are allocated from the allocator, hence
+// the Clone bound; a shared reference to an allocator is Clone.
+//
+// A tree with another allocator or index type supports construction,
+// insertion and deletion, lookups, Iter, IntoIter, ranges, the lazy set
+// operations and the subset tests, Debug and the checks. Cursors, drain,
+// extract_if, iter_levels, traverse and the folds, the eager set
+// operations, split and join, handles, building, freezing, rendering,
+// serialization, serde, statistics, choose and par_iter, as well as BSTBy
+// and the types wrapping a BST, still need Global and u32.
+impl<T: Ord, A: Allocator + Clone> BST<T, A> {
+    pub fn new_in(alloc: A) -> Self {
+        BST{ nodes: ArenaVec::new_in(alloc.clone()), root: None, deleted_indices: ArenaVec::new_in(alloc), augment: None }
     }
 
-    #[test]
-    fn insertion() {
-        let mut s = BST::singleton(2);
-        s.insert(1);
-        assert_eq!(true, s.member(&1));
-        assert_eq!(true, s.member(&2));
-        assert_eq!(false, s.member(&3));
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        BST{ nodes: ArenaVec::with_capacity_in(capacity, alloc.clone()), root: None, deleted_indices: ArenaVec::new_in(alloc), augment: None }
+    }
+}
 
-        s.insert(4);
-        assert_eq!(true, s.member(&1));
-        assert_eq!(true, s.member(&2));
-        assert_eq!(false, s.member(&3));
-        assert_eq!(true, s.member(&4));
+impl<T: Ord, A: Allocator, I: NodeIndex> BST<T, A, I> {
+    pub fn capacity(&self) -> usize {
+        self.nodes.capacity()
+    }
 
-        {
-            let mut thousand : BST<i32> = BST::new();
-            for i in 0..1000 {
-                thousand.insert(i);
-            }
-            for i in 0..1000 {
-                assert_eq!(true, thousand.member(&i));
-            }
-            assert_eq!(false, thousand.member(&1000));
+    pub fn reserve(&mut self, additional: usize) {
+        // Slots of deleted nodes are reused before the arena grows.
+        let free = self.deleted_indices.len();
+        if additional > free {
+            self.nodes.reserve(additional - free);
         }
+    }
 
-        {
-            let mut thousand : BST<i32> = BST::new();
-            for i in 0..1000 {
-                if i % 2 == 0 {
-                    thousand.insert(i);
-                }
-            }
-            for i in 0..1000 {
-                assert_eq!(i % 2 == 0, thousand.member(&i));
+    pub fn shrink_to_fit(&mut self) {
+        // Trailing free slots can be dropped without moving any node.
+        while let Some(&None) = self.nodes.last() {
+            self.nodes.pop();
+        }
+        let len = self.nodes.len();
+        self.deleted_indices.retain(|p| p.index() < len);
+        self.nodes.shrink_to_fit();
+        self.deleted_indices.shrink_to_fit();
+    }
+
+    pub fn member<Q: ?Sized + Ord>(&self, elem: &Q) -> bool where T: Borrow<Q> {
+        self.find_by(|e| elem.cmp(e.borrow())).is_some()
+    }
+
+    pub fn get<Q: ?Sized + Ord>(&self, elem: &Q) -> Option<&T> where T: Borrow<Q> {
+        self.find_by(|e| elem.cmp(e.borrow())).map(|ptr| &self.deref(&ptr).elem)
+    }
+
+    // Answers a batch of membership queries, which must be sorted, in one
+    // descent: each node splits the queries between its subtrees, so a
+    // subtree is visited once however many queries fall in it, and not at
+    // all if none do.
+    pub fn contains_sorted<Q: Ord>(&self, sorted_queries: &[Q]) -> Vec<bool> where T: Borrow<Q> {
+        debug_assert!(sorted_queries.windows(2).all(|w| w[0] <= w[1]), "contains_sorted: queries are not sorted");
+        let mut found = vec![false; sorted_queries.len()];
+        self.contains_sorted_in(self.root, sorted_queries, &mut found);
+        found
+    }
+
+    fn contains_sorted_in<Q: Ord>(&self, node: Option<Ptr<I>>, queries: &[Q], found: &mut [bool]) where T: Borrow<Q> {
+        let ptr = match node {
+            Some(ptr) if !queries.is_empty() => ptr,
+            _ => return,
+        };
+        let n = self.deref(&ptr);
+        let elem = n.elem.borrow();
+        let less = queries.partition_point(|q| q < elem);
+        let not_greater = less + queries[less..].partition_point(|q| q == elem);
+        for f in &mut found[less..not_greater] {
+            *f = true;
+        }
+        self.contains_sorted_in(n.left, &queries[..less], &mut found[..less]);
+        self.contains_sorted_in(n.right, &queries[not_greater..], &mut found[not_greater..]);
+    }
+
+    // Whether every one of the sorted queries is in the tree.
+    pub fn contains_all<Q: Ord>(&self, sorted_queries: &[Q]) -> bool where T: Borrow<Q> {
+        self.contains_sorted(sorted_queries).into_iter().all(|f| f)
+    }
+
+    /// Returns a mutable reference to the stored element equal to `elem`.
+    ///
+    /// The element must keep comparing the same way against every other
+    /// element after it has been modified, i.e. only parts that do not
+    /// take part in the ordering may be changed. Otherwise the tree is left
+    /// out of order and later lookups give unspecified results.
+    pub fn get_mut<Q: ?Sized + Ord>(&mut self, elem: &Q) -> Option<&mut T> where T: Borrow<Q> {
+        let ptr = self.find_by(|e| elem.cmp(e.borrow()))?;
+        Some(&mut self.deref_mut(&ptr).elem)
+    }
+
+    // Applies f to the stored element equal to elem, and returns whether
+    // there was one. Unlike with get_mut, f may change how the element is
+    // ordered: if it no longer compares equal to elem afterwards, it is
+    // moved to its new place, replacing any element equal to it there.
+    pub fn modify<Q: ?Sized + Ord, F: FnOnce(&mut T)>(&mut self, elem: &Q, f: F) -> bool where T: Borrow<Q> {
+        let mut path = Vec::new();
+        let mut node = self.root;
+        let target = loop {
+            let ptr = match node {
+                Some(ptr) => ptr,
+                None => return false,
+            };
+            match elem.cmp(self.deref(&ptr).elem.borrow()) {
+                Ordering::Less => node = self.deref(&ptr).left,
+                Ordering::Greater => node = self.deref(&ptr).right,
+                Ordering::Equal => break ptr,
             }
-            for i in 0..1000 {
-                if i % 2 == 1 {
-                    thousand.insert(i);
+            path.push(ptr);
+        };
+        f(&mut self.deref_mut(&target).elem);
+        if elem.cmp(self.deref(&target).elem.borrow()) == Ordering::Equal {
+            if let Some(augment) = self.augment {
+                augment(self, target);
+                for &ancestor in path.iter().rev() {
+                    augment(self, ancestor);
                 }
             }
-            for i in 0..1000 {
-                assert_eq!(true, thousand.member(&i));
+        } else {
+            // The modified element cannot be compared against to find it
+            // again, but every other node is still where elem would be.
+            let moved = self.delete_located(|tree, ptr, _| {
+                if ptr == target {
+                    Ordering::Equal
+                } else {
+                    elem.cmp(tree.deref(&ptr).elem.borrow())
+                }
+            });
+            self.insert(moved);
+        }
+        true
+    }
+
+    pub(crate) fn min_node(&self) -> Option<Ptr<I>> {
+        let mut node = self.root?;
+        while let Some(left) = self.deref(&node).left {
+            node = left;
+        }
+        Some(node)
+    }
+
+    pub(crate) fn max_node(&self) -> Option<Ptr<I>> {
+        let mut node = self.root?;
+        while let Some(right) = self.deref(&node).right {
+            node = right;
+        }
+        Some(node)
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.min_node().map(|ptr| &self.deref(&ptr).elem)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.max_node().map(|ptr| &self.deref(&ptr).elem)
+    }
+
+    pub fn floor<Q: ?Sized + Ord>(&self, elem: &Q) -> Option<&T> where T: Borrow<Q> {
+        let mut node = self.root;
+        let mut candidate = None;
+        while let Some(ptr) = node {
+            let n = self.deref(&ptr);
+            match elem.cmp(n.elem.borrow()) {
+                Ordering::Greater => {
+                    candidate = Some(&n.elem);
+                    node = n.right;
+                },
+                Ordering::Less => node = n.left,
+                Ordering::Equal => return Some(&n.elem),
             }
         }
+        candidate
+    }
 
-        {
-            let mut ex : BST<i32> = BST::new();
-            for c in 0..64 {
-                ex.insert(c);
-                ex.print_structure();
-                println!("");
+    pub fn ceiling<Q: ?Sized + Ord>(&self, elem: &Q) -> Option<&T> where T: Borrow<Q> {
+        let mut node = self.root;
+        let mut candidate = None;
+        while let Some(ptr) = node {
+            let n = self.deref(&ptr);
+            match elem.cmp(n.elem.borrow()) {
+                Ordering::Greater => node = n.right,
+                Ordering::Less => {
+                    candidate = Some(&n.elem);
+                    node = n.left;
+                },
+                Ordering::Equal => return Some(&n.elem),
             }
         }
+        candidate
+    }
 
-        {
-            let mut ex : BST<i32> = BST::new();
-            let v: [i32; 20] = [14, 9, 12, 6, 2, 10, 1, 18, 16, 5, 8, 17, 13, 3, 11, 15, 7, 19, 4, 20];
-            for c in &v {
-                ex.insert(*c);
-                ex.print_structure();
-                println!("");
+    pub fn predecessor<Q: ?Sized + Ord>(&self, elem: &Q) -> Option<&T> where T: Borrow<Q> {
+        let mut node = self.root;
+        let mut candidate = None;
+        while let Some(ptr) = node {
+            let n = self.deref(&ptr);
+            if elem > n.elem.borrow() {
+                candidate = Some(&n.elem);
+                node = n.right;
+            } else {
+                node = n.left;
             }
         }
+        candidate
     }
 
-    #[test]
-    fn taking_minimum() {
-        {
-            let mut tree: BST<i32> = BST::new();
-            tree.insert(2);
-            tree.insert(3);
-            tree.insert(5);
-            assert_eq!(tree.take_min(), Some(2)); // [3,5]
-            tree.insert(1);                       // [1,3,5]
-            assert_eq!(tree.take_min(), Some(1)); // [3,5]
-            assert_eq!(tree.take_min(), Some(3)); // [5]
-            tree.insert(2);                       // [2,5]
-            tree.insert(1);                       // [1,2,5]
-            assert_eq!(tree.take_min(), Some(1));
-            assert_eq!(tree.take_min(), Some(2));
-            assert_eq!(tree.take_min(), Some(5));
-            assert_eq!(tree.take_min(), None);
-            println!("Passed: take_min for a manual test case with interleaved insertions and deletions")
+    pub fn successor<Q: ?Sized + Ord>(&self, elem: &Q) -> Option<&T> where T: Borrow<Q> {
+        let mut node = self.root;
+        let mut candidate = None;
+        while let Some(ptr) = node {
+            let n = self.deref(&ptr);
+            if elem < n.elem.borrow() {
+                candidate = Some(&n.elem);
+                node = n.left;
+            } else {
+                node = n.right;
+            }
         }
+        candidate
+    }
 
-        {
-            let mut tree : BST<i32> = BST::new();
-            for size in 0..10 {
-                for i in 0..size {
-                    tree.insert(i);
-                }
-                for i in 0..size {
-                    assert_eq!(tree.take_min(), Some(i));
-                }
-                assert_eq!(tree.take_min(), None);
-                println!("Passed: take_min for tree with {} element(s)", size);
+    pub fn rank<Q: ?Sized + Ord>(&self, elem: &Q) -> usize where T: Borrow<Q> {
+        let mut node = self.root;
+        let mut rank = 0;
+        while let Some(ptr) = node {
+            let n = self.deref(&ptr);
+            match elem.cmp(n.elem.borrow()) {
+                Ordering::Greater => {
+                    rank += self.size(&n.left) + 1;
+                    node = n.right;
+                },
+                Ordering::Less => node = n.left,
+                Ordering::Equal => return rank + self.size(&n.left),
             }
         }
+        rank
+    }
+
+    pub fn select(&self, k: usize) -> Option<&T> {
+        self.select_node(k).map(|ptr| &self.deref(&ptr).elem)
+    }
+
+    pub(crate) fn select_node(&self, mut k: usize) -> Option<Ptr<I>> {
+        let mut node = self.root;
+        while let Some(ptr) = node {
+            let n = self.deref(&ptr);
+            let left_size = self.size(&n.left);
+            match k.cmp(&left_size) {
+                Ordering::Less => node = n.left,
+                Ordering::Greater => {
+                    k -= left_size + 1;
+                    node = n.right;
+                },
+                Ordering::Equal => return Some(ptr),
+            }
+        }
+        None
+    }
+
+    pub fn iter(&self) -> Iter<'_, T, A, I> {
+        Iter::new(self)
+    }
+}
+
+// Cursors, the level-order iterator, drain and extract_if are not yet
+// generic over the allocator or the index type.
+impl<T: Ord> BST<T> {
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut::new(self, 0)
+    }
+
+    // Returns a cursor at the first element above the bound, or at the
+    // ghost position if there is none.
+    pub fn lower_bound<Q: ?Sized + Ord>(&self, bound: Bound<&Q>) -> Cursor<'_, T> where T: Borrow<Q> {
+        Cursor::new(self, self.count_prefix(|e| !iter::above_lower(bound, e.borrow())))
+    }
+
+    // Returns a cursor at the last element below the bound, or at the ghost
+    // position if there is none.
+    pub fn upper_bound<Q: ?Sized + Ord>(&self, bound: Bound<&Q>) -> Cursor<'_, T> where T: Borrow<Q> {
+        match self.count_prefix(|e| iter::below_upper(bound, e.borrow())) {
+            0 => Cursor::new(self, self.len()),
+            n => Cursor::new(self, n - 1),
+        }
+    }
+
+    pub fn iter_levels(&self) -> Levels<'_, T> {
+        Levels::new(self)
+    }
+
+    pub fn extract_if<F: FnMut(&T) -> bool>(&mut self, pred: F) -> ExtractIf<'_, T, F> {
+        ExtractIf::new(self, pred)
+    }
+
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        Drain::new(self)
+    }
+}
+
+impl<T: Ord, A: Allocator, I: NodeIndex> BST<T, A, I> {
+    // Consumes the tree into a vector of its elements in order, allocated
+    // with exactly the required capacity.
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        let mut sorted = Vec::with_capacity(self.len());
+        let mut stack = Vec::new();
+        let mut node = self.root;
+        loop {
+            while let Some(ptr) = node {
+                stack.push(ptr);
+                node = self.deref(&ptr).left;
+            }
+            let ptr = match stack.pop() {
+                Some(ptr) => ptr,
+                None => return sorted,
+            };
+            let n = self.nodes[ptr.index()].take().expect("into_sorted_vec encounters a reference to a deleted node");
+            node = n.right;
+            sorted.push(n.elem);
+        }
+    }
+
+    pub fn range<Q: ?Sized + Ord, R: RangeBounds<Q>>(&self, range: R) -> Range<'_, T, A, I> where T: Borrow<Q> {
+        check_range_bounds(&range);
+        Range::new(self, range.start_bound(), range.end_bound())
+    }
+
+    // Iterates in order from the first element not less than start, so that
+    // paging through the tree can resume from the last element seen.
+    pub fn iter_from<Q: ?Sized + Ord>(&self, start: &Q) -> Range<'_, T, A, I> where T: Borrow<Q> {
+        Range::new(self, Bound::Included(start), Bound::Unbounded)
+    }
+
+    // Finds the run of elements that the comparator reports as equal to its
+    // target, in the manner of C++'s equal_range. The comparator must order
+    // the target consistently with the tree, but may be coarser, such as by
+    // a key, so the run can hold several elements. Returns the ranks the run
+    // spans along with the elements.
+    pub fn equal_range_by<F: Fn(&T) -> Ordering>(&self, cmp: F) -> (ops::Range<usize>, Range<'_, T, A, I>) {
+        let start = self.count_prefix(|e| cmp(e) == Ordering::Greater);
+        let end = self.count_prefix(|e| cmp(e) != Ordering::Less);
+        (start..end, Range::between(self, |e| cmp(e) != Ordering::Greater, |e| cmp(e) != Ordering::Less))
+    }
+
+    // Counts the elements within the range in logarithmic time, using the
+    // subtree sizes rather than visiting the elements.
+    pub fn range_count<Q: ?Sized + Ord, R: RangeBounds<Q>>(&self, range: R) -> usize where T: Borrow<Q> {
+        check_range_bounds(&range);
+        let below_end = self.count_prefix(|e| iter::below_upper(range.end_bound(), e.borrow()));
+        let before_start = self.count_prefix(|e| !iter::above_lower(range.start_bound(), e.borrow()));
+        below_end - before_start
+    }
+
+    // Counts the elements for which pred holds, given that it holds for a
+    // prefix of the elements in order.
+    fn count_prefix<F: Fn(&T) -> bool>(&self, pred: F) -> usize {
+        let mut node = self.root;
+        let mut count = 0;
+        while let Some(ptr) = node {
+            let n = self.deref(&ptr);
+            if pred(&n.elem) {
+                count += self.size(&n.left) + 1;
+                node = n.right;
+            } else {
+                node = n.left;
+            }
+        }
+        count
+    }
+
+    pub fn is_subset(&self, other: &BST<T, A, I>) -> bool {
+        if self.len() > other.len() {
+            return false;
+        }
+        let mut theirs = other.iter();
+        'outer: for mine in self.iter() {
+            for candidate in theirs.by_ref() {
+                match candidate.cmp(mine) {
+                    Ordering::Less => continue,
+                    Ordering::Equal => continue 'outer,
+                    Ordering::Greater => return false,
+                }
+            }
+            return false;
+        }
+        true
+    }
+
+    pub fn is_superset(&self, other: &BST<T, A, I>) -> bool {
+        other.is_subset(self)
+    }
+
+    pub fn is_disjoint(&self, other: &BST<T, A, I>) -> bool {
+        let mut mine = self.iter();
+        let mut theirs = other.iter();
+        let (mut a, mut b) = (mine.next(), theirs.next());
+        while let (Some(x), Some(y)) = (a, b) {
+            match x.cmp(y) {
+                Ordering::Less => a = mine.next(),
+                Ordering::Greater => b = theirs.next(),
+                Ordering::Equal => return false,
+            }
+        }
+        true
+    }
+}
+
+impl<T: Ord, A: Allocator, I: NodeIndex> BST<T, A, I> {
+    // Inserts the element, replacing and returning any equal element.
+    // Panics if the element needs a new node and I cannot index another.
+    pub fn insert(&mut self, elem: T) -> Option<T> {
+        self.insert_by(elem, T::cmp)
+    }
+
+    // Like insert, but hands the element back instead of panicking when
+    // the tree is full.
+    pub fn try_insert(&mut self, elem: T) -> Result<Option<T>, CapacityError<T>> {
+        if !self.has_room() && !self.member(&elem) {
+            return Err(CapacityError(elem));
+        }
+        Ok(self.insert(elem))
+    }
+
+    // Inserts an element that is known to be absent and returns the node
+    // now holding it.
+    pub(crate) fn insert_absent(&mut self, elem: T) -> Ptr<I> {
+        self.insert_node_by(elem, T::cmp).0
+    }
+
+    // Returns the element equal to the key, first inserting the one made by
+    // the closure if there is none, in a single descent.
+    pub fn get_or_insert_with<Q: ?Sized + Ord, F: FnOnce() -> T>(&mut self, key: &Q, make: F) -> &T where T: Borrow<Q> {
+        let mut path = Vec::new();
+        let mut node = self.root;
+        while let Some(ptr) = node {
+            let n = self.deref(&ptr);
+            match key.cmp(n.elem.borrow()) {
+                Ordering::Less => {
+                    path.push((ptr, Side::Left));
+                    node = n.left;
+                },
+                Ordering::Greater => {
+                    path.push((ptr, Side::Right));
+                    node = n.right;
+                },
+                Ordering::Equal => return &self.deref(&ptr).elem,
+            }
+        }
+        let elem = make();
+        assert!(key == elem.borrow(), "get_or_insert_with: new element does not match the key");
+        let new = self.alloc(elem);
+        let root = self.fixup_path(path, Some(new)).unwrap();
+        self.root = Some(root);
+        self.deref_mut(&root).color = Color::Black;
+        &self.deref(&new).elem
+    }
+
+    // Moves the live nodes to the front of the arena, preserving their
+    // relative order, and releases the unused capacity. Returns the number
+    // of node slots of capacity that were released.
+    pub fn compact(&mut self) -> usize {
+        let old_capacity = self.nodes.capacity();
+        let mut remap: Vec<Option<Ptr<I>>> = Vec::with_capacity(self.nodes.len());
+        let mut next = 0;
+        for node in self.nodes.iter() {
+            remap.push(node.as_ref().map(|_| {
+                next += 1;
+                Ptr::new(next - 1)
+            }));
+        }
+        self.nodes.retain(|node| node.is_some());
+        for (i, node) in self.nodes.iter_mut().flatten().enumerate() {
+            node.left = node.left.map(|p| remap[p.index()].unwrap());
+            node.right = node.right.map(|p| remap[p.index()].unwrap());
+            node.stamp(Ptr::new(i));
+        }
+        for i in 0..self.nodes.len() {
+            self.adopt_children(Ptr::new(i));
+        }
+        self.nodes.shrink_to_fit();
+        self.root = self.root.map(|p| remap[p.index()].unwrap());
+        self.deleted_indices.clear();
+        self.deleted_indices.shrink_to_fit();
+        old_capacity - self.nodes.capacity()
+    }
+
+    pub fn take_min(&mut self) -> Option<T> {
+        self.root.map(
+            |root|
+            if self.deref(&root).left.is_none() {
+                // The tree has only one element.
+                let rv = self.free(root);
+                self.clear();
+                rv
+            } else {
+                // The tree has more than one element.
+                let (min, new_root) = self.take_min_impl(root);
+                self.root = new_root;
+                self.deref_mut(&new_root.unwrap()).color = Color::Black;
+                min
+            })
+    }
+
+    pub fn take_max(&mut self) -> Option<T> {
+        self.root.map(
+            |root| {
+                let (max, new_root) = self.take_max_impl(root);
+                self.root = new_root;
+                match new_root {
+                    None => self.clear(),
+                    Some(new_root) => self.deref_mut(&new_root).color = Color::Black,
+                }
+                max
+            })
+    }
+
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.take_min()
+    }
+
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.take_max()
+    }
+
+    pub fn delete<Q: ?Sized + Ord>(&mut self, elem: &Q) -> Option<T> where T: Borrow<Q> {
+        self.delete_by(|e| elem.cmp(e.borrow()))
+    }
+
+    // Deletes the element held by a live node, which is located again by
+    // comparing against that element.
+    pub(crate) fn delete_node(&mut self, target: Ptr<I>) -> T {
+        self.delete_located(|tree, ptr, _| tree.deref(&target).elem.cmp(&tree.deref(&ptr).elem))
+    }
+
+    pub fn take<Q: ?Sized + Ord>(&mut self, elem: &Q) -> Option<T> where T: Borrow<Q> {
+        self.delete(elem)
+    }
+
+    pub fn replace(&mut self, elem: T) -> Option<T> {
+        self.insert(elem)
+    }
+}
+
+fn check_range_bounds<Q: ?Sized + Ord, R: RangeBounds<Q>>(range: &R) {
+    match (range.start_bound(), range.end_bound()) {
+        (Bound::Excluded(start), Bound::Excluded(end)) if start == end =>
+            panic!("range start and end are equal and excluded"),
+        (Bound::Included(start), Bound::Included(end)) |
+        (Bound::Included(start), Bound::Excluded(end)) |
+        (Bound::Excluded(start), Bound::Included(end)) |
+        (Bound::Excluded(start), Bound::Excluded(end)) if start > end =>
+            panic!("range start is greater than range end"),
+        _ => (),
+    }
+}
+
+// The constructors only make trees with u32 indices, so that the type of
+// BST::new() needs no annotation; a tree with another index type is made
+// with default, as in BST::<T, Global, u16>::default().
+impl<T: Ord, A: Allocator + Default, I: NodeIndex> Default for BST<T, A, I> {
+    fn default() -> Self {
+        BST{ nodes: ArenaVec::new_in(A::default()), root: None, deleted_indices: ArenaVec::new_in(A::default()), augment: None }
+    }
+}
+
+// Indexing selects by rank, so tree[k] is the k-th smallest element.
+impl<T: Ord, A: Allocator, I: NodeIndex> Index<usize> for BST<T, A, I> {
+    type Output = T;
+
+    fn index(&self, k: usize) -> &T {
+        match self.select(k) {
+            Some(elem) => elem,
+            None => panic!("index out of bounds: the len is {} but the index is {}", self.len(), k),
+        }
+    }
+}
+
+impl<T: Ord, A: Allocator, I: NodeIndex> PartialEq for BST<T, A, I> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Ord, A: Allocator, I: NodeIndex> Eq for BST<T, A, I> {}
+
+impl<T: Ord, A: Allocator, I: NodeIndex> PartialOrd for BST<T, A, I> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord, A: Allocator, I: NodeIndex> Ord for BST<T, A, I> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<T: Ord + Hash, A: Allocator, I: NodeIndex> Hash for BST<T, A, I> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for elem in self.iter() {
+            elem.hash(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+    use std::println;
+
+    use super::{BST, Color, CorruptionError, DiffEntry, MergePolicy, Ptr, TikzLabel, TikzOptions, Traversal};
+
+    // Returns the black height of the subtree, panicking if any of the
+    // left-leaning red-black invariants is violated.
+    fn check_subtree<T: Ord>(tree: &BST<T>, ptr: Option<Ptr>, lo: Option<&T>, hi: Option<&T>) -> usize {
+        match ptr {
+            None => 0,
+            Some(ptr) => {
+                let node = tree.deref(&ptr);
+                assert!(lo.is_none_or(|lo| *lo < node.elem), "elements are out of order");
+                assert!(hi.is_none_or(|hi| node.elem < *hi), "elements are out of order");
+                assert!(!tree.is_red(&node.right), "red node leans right");
+                if let Color::Red = node.color {
+                    assert!(!tree.is_red(&node.left), "two red nodes in a row");
+                }
+                let left = check_subtree(tree, node.left, lo, Some(&node.elem));
+                let right = check_subtree(tree, node.right, Some(&node.elem), hi);
+                assert_eq!(left, right, "black heights differ");
+                assert_eq!(node.size, 1 + tree.size(&node.left) + tree.size(&node.right), "subtree size is stale");
+                match node.color {
+                    Color::Red => left,
+                    Color::Black => left + 1,
+                }
+            }
+        }
+    }
+
+    fn check_invariants<T: Ord>(tree: &BST<T>) {
+        assert!(!tree.is_red(&tree.root), "root is red");
+        check_subtree(tree, tree.root, None, None);
+        assert_eq!(tree.len(), tree.size(&tree.root));
+        assert_eq!(tree.len(), tree.nodes.iter().filter(|n| n.is_some()).count());
+        assert_eq!(tree.try_self_check(), Ok(()));
+    }
+
+    // Ordered by the key alone, carrying a payload.
+    #[derive(Debug)]
+    struct Record(u32, &'static str);
+
+    impl PartialEq for Record {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for Record {}
+
+    impl PartialOrd for Record {
+        fn partial_cmp(&self, other: &Self) -> Option<::std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Record {
+        fn cmp(&self, other: &Self) -> ::std::cmp::Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+
+    // A small deterministic generator so that tests do not need extra
+    // dependencies.
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self) -> u64 {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            self.0 >> 33
+        }
+    }
+
+    // The generator only produces 31 bits at a time, so two outputs are
+    // combined for each 32-bit word.
+    #[cfg(feature = "rand")]
+    impl ::rand::RngCore for Lcg {
+        fn next_u32(&mut self) -> u32 {
+            ((self.next() << 16) ^ self.next()) as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            (u64::from(self.next_u32()) << 32) | u64::from(self.next_u32())
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for byte in dest {
+                *byte = self.next_u32() as u8;
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), ::rand::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn basics() {
+        let e: BST<i32> = BST::new();
+        let s = BST::singleton(2);
+
+        assert!(!e.member(&1));
+        assert!(!e.member(&2));
+        assert!(!e.member(&3));
+
+        assert!(!s.member(&1));
+        assert!(s.member(&2));
+        assert!(!s.member(&3));
+    }
+
+    #[test]
+    fn insertion() {
+        let mut s = BST::singleton(2);
+        s.insert(1);
+        assert!(s.member(&1));
+        assert!(s.member(&2));
+        assert!(!s.member(&3));
+
+        s.insert(4);
+        assert!(s.member(&1));
+        assert!(s.member(&2));
+        assert!(!s.member(&3));
+        assert!(s.member(&4));
+
+        {
+            let mut thousand : BST<i32> = BST::new();
+            for i in 0..1000 {
+                thousand.insert(i);
+            }
+            for i in 0..1000 {
+                assert!(thousand.member(&i));
+            }
+            assert!(!thousand.member(&1000));
+        }
+
+        {
+            let mut thousand : BST<i32> = BST::new();
+            for i in 0..1000 {
+                if i % 2 == 0 {
+                    thousand.insert(i);
+                }
+            }
+            for i in 0..1000 {
+                assert_eq!(i % 2 == 0, thousand.member(&i));
+            }
+            for i in 0..1000 {
+                if i % 2 == 1 {
+                    thousand.insert(i);
+                }
+            }
+            for i in 0..1000 {
+                assert!(thousand.member(&i));
+            }
+        }
+
+        {
+            let mut ex : BST<i32> = BST::new();
+            for c in 0..64 {
+                ex.insert(c);
+                ex.print_structure();
+                println!();
+            }
+        }
+
+        {
+            let mut ex : BST<i32> = BST::new();
+            let v: [i32; 20] = [14, 9, 12, 6, 2, 10, 1, 18, 16, 5, 8, 17, 13, 3, 11, 15, 7, 19, 4, 20];
+            for c in &v {
+                ex.insert(*c);
+                ex.print_structure();
+                println!();
+            }
+        }
+    }
+
+    #[test]
+    fn taking_minimum() {
+        {
+            let mut tree: BST<i32> = BST::new();
+            tree.insert(2);
+            tree.insert(3);
+            tree.insert(5);
+            assert_eq!(tree.take_min(), Some(2)); // [3,5]
+            tree.insert(1);                       // [1,3,5]
+            assert_eq!(tree.take_min(), Some(1)); // [3,5]
+            assert_eq!(tree.take_min(), Some(3)); // [5]
+            tree.insert(2);                       // [2,5]
+            tree.insert(1);                       // [1,2,5]
+            assert_eq!(tree.take_min(), Some(1));
+            assert_eq!(tree.take_min(), Some(2));
+            assert_eq!(tree.take_min(), Some(5));
+            assert_eq!(tree.take_min(), None);
+            println!("Passed: take_min for a manual test case with interleaved insertions and deletions")
+        }
+
+        {
+            let mut tree : BST<i32> = BST::new();
+            for size in 0..10 {
+                for i in 0..size {
+                    tree.insert(i);
+                }
+                for i in 0..size {
+                    assert_eq!(tree.take_min(), Some(i));
+                }
+                assert_eq!(tree.take_min(), None);
+                println!("Passed: take_min for tree with {} element(s)", size);
+            }
+        }
+    }
+
+    #[test]
+    fn deletion() {
+        {
+            let mut tree: BST<i32> = BST::new();
+            assert_eq!(tree.delete(&1), None);
+            tree.insert(1);
+            assert_eq!(tree.delete(&2), None);
+            assert_eq!(tree.delete(&1), Some(1));
+            assert_eq!(tree.delete(&1), None);
+            assert!(tree.is_empty());
+            assert_eq!(tree.len(), 0);
+        }
+
+        {
+            for size in 0..64 {
+                for removed in 0..size {
+                    let mut tree: BST<i32> = BST::new();
+                    for i in 0..size {
+                        tree.insert(i);
+                    }
+                    assert_eq!(tree.delete(&removed), Some(removed));
+                    check_invariants(&tree);
+                    assert_eq!(tree.len(), size as usize - 1);
+                    for i in 0..size {
+                        assert_eq!(i != removed, tree.member(&i));
+                    }
+                }
+            }
+        }
+
+        {
+            let mut rng = Lcg(42);
+            let mut tree: BST<u64> = BST::new();
+            let mut present = vec![false; 256];
+            for _ in 0..10000 {
+                let x = rng.next() % 256;
+                if rng.next() & 1 == 0 {
+                    tree.insert(x);
+                    present[x as usize] = true;
+                } else {
+                    let expected = if present[x as usize] { Some(x) } else { None };
+                    assert_eq!(tree.delete(&x), expected);
+                    present[x as usize] = false;
+                }
+                check_invariants(&tree);
+            }
+            assert_eq!(tree.len(), present.iter().filter(|&&p| p).count());
+        }
+    }
+
+    #[test]
+    fn taking_maximum() {
+        {
+            let mut tree: BST<i32> = BST::new();
+            tree.insert(2);
+            tree.insert(3);
+            tree.insert(5);
+            assert_eq!(tree.take_max(), Some(5)); // [2,3]
+            tree.insert(4);                       // [2,3,4]
+            assert_eq!(tree.take_max(), Some(4)); // [2,3]
+            assert_eq!(tree.take_min(), Some(2)); // [3]
+            tree.insert(1);                       // [1,3]
+            assert_eq!(tree.take_max(), Some(3));
+            assert_eq!(tree.take_max(), Some(1));
+            assert_eq!(tree.take_max(), None);
+        }
+
+        {
+            let mut tree : BST<i32> = BST::new();
+            for size in 0..64 {
+                for i in 0..size {
+                    tree.insert(i);
+                }
+                for i in (0..size).rev() {
+                    assert_eq!(tree.take_max(), Some(i));
+                    check_invariants(&tree);
+                }
+                assert_eq!(tree.take_max(), None);
+            }
+        }
+    }
+
+    #[test]
+    fn extremes() {
+        let mut tree: BST<i32> = BST::new();
+        assert_eq!(tree.first(), None);
+        assert_eq!(tree.last(), None);
+        for &i in &[14, 9, 12, 6, 2, 10, 1, 18, 16, 5] {
+            tree.insert(i);
+        }
+        assert_eq!(tree.first(), Some(&1));
+        assert_eq!(tree.last(), Some(&18));
+        assert_eq!(tree.len(), 10);
+        tree.take_min();
+        tree.take_max();
+        assert_eq!(tree.first(), Some(&2));
+        assert_eq!(tree.last(), Some(&16));
+    }
+
+    #[test]
+    fn iteration() {
+        let e: BST<i32> = BST::new();
+        assert_eq!(e.iter().next(), None);
+
+        let mut tree: BST<i32> = BST::new();
+        let v: [i32; 20] = [14, 9, 12, 6, 2, 10, 1, 18, 16, 5, 8, 17, 13, 3, 11, 15, 7, 19, 4, 20];
+        for c in &v {
+            tree.insert(*c);
+        }
+        let mut iter = tree.iter();
+        assert_eq!(iter.len(), 20);
+        iter.next();
+        assert_eq!(iter.len(), 19);
+        assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), (1..21).collect::<Vec<_>>());
+
+        for i in 1..21 {
+            if i % 3 == 0 {
+                tree.delete(&i);
+            }
+        }
+        assert_eq!(tree.iter().len(), 14);
+        assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), (1..21).filter(|i| i % 3 != 0).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn consuming_iteration() {
+        let e: BST<String> = BST::new();
+        assert_eq!(e.into_iter().next(), None);
+
+        let mut tree: BST<String> = BST::new();
+        for i in 0..100 {
+            tree.insert(format!("{:03}", (i * 37) % 100));
+        }
+        for i in 0..50 {
+            tree.delete(&format!("{:03}", i * 2));
+        }
+        let mut iter = tree.into_iter();
+        assert_eq!(iter.len(), 50);
+        assert_eq!(iter.next(), Some("001".to_string()));
+        assert_eq!(iter.len(), 49);
+        let rest: Vec<String> = iter.collect();
+        assert_eq!(rest, (1..50).map(|i| format!("{:03}", i * 2 + 1)).collect::<Vec<_>>());
+
+        // Dropping a partially consumed iterator drops the remaining elements.
+        let mut tree: BST<String> = BST::new();
+        tree.insert("a".to_string());
+        tree.insert("b".to_string());
+        let mut iter = tree.into_iter();
+        assert_eq!(iter.next(), Some("a".to_string()));
+    }
+
+    #[test]
+    fn ranges() {
+        use std::ops::Bound::{Excluded, Included, Unbounded};
+
+        let e: BST<i32> = BST::new();
+        assert_eq!(e.range(..).next(), None);
+        assert_eq!(e.range(1..5).next(), None);
+
+        let mut tree: BST<i32> = BST::new();
+        for i in 0..50 {
+            tree.insert(i * 2);
+        }
+        let collect = |r: super::Range<i32>| r.cloned().collect::<Vec<_>>();
+        assert_eq!(collect(tree.range(..)), (0..50).map(|i| i * 2).collect::<Vec<_>>());
+        assert_eq!(collect(tree.range(10..20)), vec![10, 12, 14, 16, 18]);
+        assert_eq!(collect(tree.range(9..=20)), vec![10, 12, 14, 16, 18, 20]);
+        assert_eq!(collect(tree.range((Excluded(10), Included(15)))), vec![12, 14]);
+        assert_eq!(collect(tree.range(..3)), vec![0, 2]);
+        assert_eq!(collect(tree.range(95..)), vec![96, 98]);
+        assert_eq!(collect(tree.range(99..)), vec![]);
+        assert_eq!(collect(tree.range(..0)), vec![]);
+        assert_eq!(collect(tree.range(11..12)), vec![]);
+        assert_eq!(collect(tree.range(12..12)), vec![]);
+        assert_eq!(collect(tree.range((Unbounded, Included(0)))), vec![0]);
+
+        for lo in -1..101 {
+            for hi in lo..101 {
+                let expected: Vec<i32> = (0..50).map(|i| i * 2).filter(|&x| lo <= x && x < hi).collect();
+                assert_eq!(collect(tree.range(lo..hi)), expected);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "range start is greater than range end")]
+    fn range_with_reversed_bounds() {
+        use std::ops::Bound::Included;
+
+        let tree = BST::singleton(1);
+        tree.range((Included(3), Included(1)));
+    }
+
+    #[test]
+    fn floor_and_ceiling() {
+        let e: BST<i32> = BST::new();
+        assert_eq!(e.floor(&1), None);
+        assert_eq!(e.ceiling(&1), None);
+
+        let mut tree: BST<i32> = BST::new();
+        for i in 1..50 {
+            tree.insert(i * 3);
+        }
+        for x in 0..160 {
+            let floor = if x < 3 { None } else { Some(std::cmp::min(x / 3, 49) * 3) };
+            let ceiling = if x > 147 { None } else { Some(std::cmp::max((x + 2) / 3, 1) * 3) };
+            assert_eq!(tree.floor(&x).cloned(), floor);
+            assert_eq!(tree.ceiling(&x).cloned(), ceiling);
+        }
+    }
+
+    #[test]
+    fn predecessor_and_successor() {
+        let e: BST<i32> = BST::new();
+        assert_eq!(e.predecessor(&1), None);
+        assert_eq!(e.successor(&1), None);
+
+        let mut tree: BST<i32> = BST::new();
+        for i in 1..50 {
+            tree.insert(i * 3);
+        }
+        for x in 0..160 {
+            let predecessor = if x <= 3 { None } else { Some(std::cmp::min((x - 1) / 3, 49) * 3) };
+            let successor = if x >= 147 { None } else { Some((x / 3 + 1) * 3) };
+            assert_eq!(tree.predecessor(&x).cloned(), predecessor);
+            assert_eq!(tree.successor(&x).cloned(), successor);
+        }
+    }
+
+    #[test]
+    fn order_statistics() {
+        let e: BST<i32> = BST::new();
+        assert_eq!(e.rank(&1), 0);
+        assert_eq!(e.select(0), None);
+
+        let mut tree: BST<i32> = BST::new();
+        let mut rng = Lcg(7);
+        for _ in 0..500 {
+            tree.insert((rng.next() % 1000) as i32);
+        }
+        for _ in 0..200 {
+            tree.delete(&((rng.next() % 1000) as i32));
+            tree.take_max();
+            tree.take_min();
+        }
+        check_invariants(&tree);
+
+        let elems: Vec<i32> = tree.iter().cloned().collect();
+        for (k, x) in elems.iter().enumerate() {
+            assert_eq!(tree.select(k), Some(x));
+            assert_eq!(tree.rank(x), k);
+        }
+        assert_eq!(tree.select(elems.len()), None);
+        for x in -1..1001 {
+            assert_eq!(tree.rank(&x), elems.iter().filter(|&&y| y < x).count());
+        }
+    }
+
+    #[test]
+    fn borrowed_lookups() {
+        use std::ops::Bound::{Excluded, Included};
+
+        let mut tree: BST<String> = BST::new();
+        for word in &["pear", "apple", "fig", "kiwi", "banana"] {
+            tree.insert(word.to_string());
+        }
+        assert!(tree.member("fig"));
+        assert!(!tree.member("grape"));
+        assert_eq!(tree.floor("grape").map(String::as_str), Some("fig"));
+        assert_eq!(tree.ceiling("grape").map(String::as_str), Some("kiwi"));
+        assert_eq!(tree.predecessor("fig").map(String::as_str), Some("banana"));
+        assert_eq!(tree.successor("fig").map(String::as_str), Some("kiwi"));
+        assert_eq!(tree.rank("kiwi"), 3);
+        assert_eq!(tree.range::<str, _>((Included("b"), Excluded("g"))).map(String::as_str).collect::<Vec<_>>(), vec!["banana", "fig"]);
+        assert_eq!(tree.delete("apple"), Some("apple".to_string()));
+        assert_eq!(tree.delete("apple"), None);
+        assert_eq!(tree.len(), 4);
+    }
+
+    #[test]
+    fn set_operations() {
+        let mut rng = Lcg(3);
+        for round in 0..200 {
+            let mut a: BST<u64> = BST::new();
+            let mut b: BST<u64> = BST::new();
+            let range = 1 + round as u64;
+            for _ in 0..rng.next() % 100 {
+                a.insert(rng.next() % range);
+            }
+            for _ in 0..rng.next() % 100 {
+                b.insert(rng.next() % range);
+            }
+            // Leave some holes in the arenas.
+            a.delete(&(rng.next() % range));
+            b.take_min();
+
+            let union = a.union(&b);
+            let intersection = a.intersection(&b);
+            let difference = a.difference(&b);
+            check_invariants(&union);
+            check_invariants(&intersection);
+            check_invariants(&difference);
+
+            let expected: Vec<u64> = (0..range).filter(|x| a.member(x) || b.member(x)).collect();
+            assert_eq!(union.iter().cloned().collect::<Vec<_>>(), expected);
+            let expected: Vec<u64> = (0..range).filter(|x| a.member(x) && b.member(x)).collect();
+            assert_eq!(intersection.iter().cloned().collect::<Vec<_>>(), expected);
+            let expected: Vec<u64> = (0..range).filter(|x| a.member(x) && !b.member(x)).collect();
+            assert_eq!(difference.iter().cloned().collect::<Vec<_>>(), expected);
+        }
+
+        {
+            let mut big: BST<i32> = BST::new();
+            for i in 0..2000 {
+                big.insert(i);
+            }
+            let small: BST<i32> = BST::singleton(5000);
+            for result in &[big.union(&small), small.union(&big)] {
+                check_invariants(result);
+                assert_eq!(result.len(), 2001);
+            }
+            check_invariants(&big.difference(&small));
+            assert!(small.intersection(&big).is_empty());
+            assert!(big.difference(&big).is_empty());
+        }
+    }
+
+    #[test]
+    fn set_relations() {
+        let build = |v: &[i32]| {
+            let mut tree = BST::new();
+            for x in v {
+                tree.insert(*x);
+            }
+            tree
+        };
+        let e = build(&[]);
+        let a = build(&[1, 3, 5, 7]);
+        let b = build(&[1, 2, 3, 4, 5, 6, 7]);
+        let c = build(&[2, 4, 6]);
+        let d = build(&[0, 3, 8]);
+
+        assert!(e.is_subset(&a));
+        assert!(a.is_subset(&a));
+        assert!(a.is_subset(&b));
+        assert!(!b.is_subset(&a));
+        assert!(!d.is_subset(&b));
+        assert!(!build(&[7, 8]).is_subset(&b));
+        assert!(b.is_superset(&c));
+        assert!(!c.is_superset(&b));
+
+        assert!(a.is_disjoint(&c));
+        assert!(c.is_disjoint(&a));
+        assert!(e.is_disjoint(&e));
+        assert!(!a.is_disjoint(&d));
+        assert!(!b.is_disjoint(&c));
+    }
+
+    #[test]
+    fn splitting() {
+        for size in 0..40 {
+            for key in -1..size + 1 {
+                let mut tree: BST<i32> = BST::new();
+                for i in 0..size {
+                    tree.insert(i);
+                }
+                tree.delete(&(size / 2));
+                let other = tree.split_off(&key);
+                check_invariants(&tree);
+                check_invariants(&other);
+                let expected: Vec<i32> = (0..size).filter(|&i| i != size / 2).collect();
+                assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), expected.iter().cloned().filter(|&i| i < key).collect::<Vec<_>>());
+                assert_eq!(other.iter().cloned().collect::<Vec<_>>(), expected.iter().cloned().filter(|&i| i >= key).collect::<Vec<_>>());
+            }
+        }
+    }
+
+    #[test]
+    fn appending() {
+        let build = |r: ::std::ops::Range<i32>| {
+            let mut tree = BST::new();
+            for i in r {
+                tree.insert(i);
+            }
+            tree
+        };
+        let cases = [(0..100, 100..103), (50..60, 0..30), (0..10, 5..500), (0..0, 3..7), (4..9, 0..0)];
+        for (a, b) in cases.iter() {
+            let mut tree = build(a.clone());
+            let mut other = build(b.clone());
+            tree.append(&mut other);
+            check_invariants(&tree);
+            assert!(other.is_empty());
+            let mut expected: Vec<i32> = a.clone().chain(b.clone()).collect();
+            expected.sort();
+            expected.dedup();
+            assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn bulk_construction() {
+        for size in 0..300 {
+            let tree = BST::from_sorted_iter(0..size);
+            check_invariants(&tree);
+            assert_eq!(tree.len(), size as usize);
+            assert!(tree.iter().cloned().eq(0..size));
+            if (size + 1) & size == 0 {
+                assert!(tree.nodes.iter().all(|n| match n.as_ref().unwrap().color {
+                    Color::Black => true,
+                    Color::Red => false,
+                }));
+            }
+        }
+
+        let mut tree = BST::from_sorted_iter(vec![1, 1, 2, 3, 3, 3]);
+        check_invariants(&tree);
+        assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), vec![1, 2, 3]);
+        tree.insert(0);
+        tree.delete(&2);
+        check_invariants(&tree);
+    }
+
+    #[test]
+    #[should_panic(expected = "input is not sorted")]
+    fn bulk_construction_from_unsorted() {
+        BST::from_sorted_iter(vec![2, 1]);
+    }
+
+    #[test]
+    fn draining() {
+        let mut tree: BST<String> = BST::new();
+        for i in 0..100 {
+            tree.insert(format!("{:02}", 99 - i));
+        }
+        let capacity = tree.nodes.capacity();
+        let drained: Vec<String> = tree.drain().collect();
+        assert_eq!(drained, (0..100).map(|i| format!("{:02}", i)).collect::<Vec<_>>());
+        assert!(tree.is_empty());
+        assert_eq!(tree.len(), 0);
+        assert_eq!(tree.nodes.capacity(), capacity);
+
+        for i in 0..10 {
+            tree.insert(i.to_string());
+        }
+        {
+            let mut drain = tree.drain();
+            assert_eq!(drain.len(), 10);
+            assert_eq!(drain.next(), Some("0".to_string()));
+        }
+        assert!(tree.is_empty());
+        check_invariants(&tree);
+        tree.insert("x".to_string());
+        assert_eq!(tree.len(), 1);
+    }
+
+    #[test]
+    fn reverse_iteration() {
+        let mut tree = BST::new();
+        let mut rng = Lcg(29);
+        for _ in 0..500 {
+            tree.insert((rng.next() % 1000) as i32);
+        }
+        let forward: Vec<i32> = tree.iter().cloned().collect();
+        let mut backward: Vec<i32> = tree.iter().rev().cloned().collect();
+        backward.reverse();
+        assert_eq!(forward, backward);
+        assert_eq!(tree.iter().rev().take(3).cloned().collect::<Vec<_>>(), forward.iter().rev().take(3).cloned().collect::<Vec<_>>());
+
+        // Alternating between the ends visits every element exactly once.
+        let mut iter = tree.iter();
+        let mut seen = Vec::new();
+        while let Some(x) = if seen.len() % 2 == 0 { iter.next() } else { iter.next_back() } {
+            seen.push(*x);
+            assert_eq!(iter.len(), forward.len() - seen.len());
+        }
+        seen.sort();
+        assert_eq!(seen, forward);
+
+        for &(a, b) in &[(0, 1000), (100, 200), (250, 251), (400, 400), (990, 2000)] {
+            let expected: Vec<i32> = forward.iter().cloned().filter(|&x| a <= x && x < b).collect();
+            let range = tree.range(a..b);
+            assert_eq!(range.len(), expected.len());
+            assert_eq!(range.clone().rev().cloned().collect::<Vec<_>>(), expected.iter().rev().cloned().collect::<Vec<_>>());
+            let mut range = range;
+            assert_eq!(range.next_back().cloned(), expected.last().cloned());
+            assert_eq!(range.next().cloned(), expected.first().cloned().filter(|_| expected.len() > 1));
+        }
+    }
+
+    #[test]
+    fn level_order() {
+        let mut tree = BST::new();
+        assert_eq!(tree.iter_levels().next(), None);
+        for i in 1..8 {
+            tree.insert(i);
+        }
+        let levels: Vec<(usize, i32, Color)> = tree.iter_levels().map(|(d, &x, c)| (d, x, c)).collect();
+        assert_eq!(levels.iter().map(|l| (l.0, l.1)).collect::<Vec<_>>(),
+                   vec![(0, 4), (1, 2), (1, 6), (2, 1), (2, 3), (2, 5), (2, 7)]);
+        assert!(levels.iter().all(|l| l.2 == Color::Black));
+
+        let mut rng = Lcg(31);
+        for _ in 0..1000 {
+            tree.insert((rng.next() % 5000) as i32);
+        }
+        let mut previous = 0;
+        for (depth, _, color) in tree.iter_levels() {
+            assert!(depth == previous || depth == previous + 1);
+            assert!(depth > 0 || color == Color::Black);
+            previous = depth;
+        }
+        assert_eq!(tree.iter_levels().count(), tree.len());
+        assert_eq!(previous + 1, tree.height());
+    }
+
+    #[test]
+    fn sorted_vec() {
+        assert_eq!(BST::<i32>::new().into_sorted_vec(), vec![]);
+        let mut tree = BST::new();
+        let mut rng = Lcg(37);
+        for _ in 0..1000 {
+            tree.insert(format!("{:04}", rng.next() % 3000));
+        }
+        for i in 0..1500 {
+            tree.delete(&format!("{:04}", i));
+        }
+        let expected: Vec<String> = tree.iter().cloned().collect();
+        let sorted = tree.into_sorted_vec();
+        assert_eq!(sorted.capacity(), expected.len());
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn extracting() {
+        let mut tree = BST::new();
+        for i in 0..100 {
+            tree.insert(i);
+        }
+        let evens: Vec<i32> = tree.extract_if(|x| x % 2 == 0).collect();
+        assert_eq!(evens, (0..50).map(|i| i * 2).collect::<Vec<_>>());
+        check_invariants(&tree);
+        assert!(tree.iter().cloned().eq((0..50).map(|i| i * 2 + 1)));
+
+        // Elements beyond the point where the iterator is dropped are kept.
+        assert_eq!(tree.extract_if(|x| x % 3 == 0).take(2).collect::<Vec<_>>(), vec![3, 9]);
+        check_invariants(&tree);
+        assert_eq!(tree.len(), 48);
+        assert!(tree.member(&15));
+
+        assert_eq!(tree.extract_if(|_| true).count(), 48);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn equality_and_hashing() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let hash = |tree: &BST<i32>| {
+            let mut hasher = DefaultHasher::new();
+            tree.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        let mut a: BST<i32> = BST::new();
+        let mut b: BST<i32> = BST::new();
+        assert!(a == b);
+        for i in 0..50 {
+            a.insert(i);
+            b.insert(49 - i);
+        }
+        b.insert(100);
+        assert!(a != b);
+        b.delete(&100);
+        assert!(a == b);
+        assert_eq!(hash(&a), hash(&b));
+        assert_eq!(a, BST::from_sorted_iter(0..50));
+
+        a.take_min();
+        assert!(a != b);
+        assert_ne!(hash(&a), hash(&b));
+    }
+
+    #[test]
+    fn lexicographic_ordering() {
+        let a = BST::from_sorted_iter(vec![1, 2, 3]);
+        let b = BST::from_sorted_iter(vec![1, 2, 4]);
+        let c = BST::from_sorted_iter(vec![1, 2]);
+        let e: BST<i32> = BST::new();
+        assert!(a < b);
+        assert!(c < a);
+        assert!(e < c);
+        assert_eq!(a.cmp(&a.clone()), ::std::cmp::Ordering::Equal);
+
+        let mut trees = BST::new();
+        trees.insert(b.clone());
+        trees.insert(a.clone());
+        trees.insert(c.clone());
+        trees.insert(a.clone());
+        assert_eq!(trees.len(), 3);
+        assert_eq!(trees.first(), Some(&c));
+        assert_eq!(trees.last(), Some(&b));
+    }
+
+    #[test]
+    fn getting_stored_elements() {
+        let mut tree: BST<Record> = BST::new();
+        tree.insert(Record(2, "two"));
+        tree.insert(Record(1, "one"));
+        tree.insert(Record(3, "three"));
+        assert_eq!(tree.get(&Record(2, "")).map(|r| r.1), Some("two"));
+        assert!(tree.get(&Record(4, "")).is_none());
+
+        tree.get_mut(&Record(3, "")).unwrap().1 = "drei";
+        assert_eq!(tree.get(&Record(3, "")).map(|r| r.1), Some("drei"));
+        assert!(tree.get_mut(&Record(0, "")).is_none());
+
+        let mut words: BST<String> = BST::new();
+        words.insert("hello".to_string());
+        assert_eq!(words.get("hello").map(String::as_str), Some("hello"));
+    }
+
+    #[test]
+    fn replacing_and_taking() {
+        let mut tree: BST<Record> = BST::new();
+        assert!(tree.replace(Record(1, "one")).is_none());
+        assert!(tree.replace(Record(2, "two")).is_none());
+        assert_eq!(tree.replace(Record(1, "uno")).map(|r| r.1), Some("one"));
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree.get(&Record(1, "")).map(|r| r.1), Some("uno"));
+
+        assert_eq!(tree.take(&Record(2, "")).map(|r| r.1), Some("two"));
+        assert!(tree.take(&Record(2, "")).is_none());
+        assert_eq!(tree.len(), 1);
+        check_invariants(&tree);
+    }
+
+    #[test]
+    fn insertion_returns_replaced() {
+        let mut tree: BST<Record> = BST::new();
+        for i in 0..50 {
+            assert!(tree.insert(Record(i, "first")).is_none());
+        }
+        for i in (0..50).rev() {
+            assert_eq!(tree.insert(Record(i, "second")).map(|r| r.1), Some("first"));
+        }
+        assert_eq!(tree.len(), 50);
+        assert!(tree.iter().all(|r| r.1 == "second"));
+        check_invariants(&tree);
+    }
+
+    #[test]
+    fn interning() {
+        let mut pool: BST<String> = BST::new();
+        let mut made = 0;
+        for word in "the quick brown fox jumps over the lazy dog the end".split(' ') {
+            let interned = pool.get_or_insert_with(word, || {
+                made += 1;
+                word.to_string()
+            });
+            assert_eq!(interned, word);
+        }
+        assert_eq!(made, 9);
+        assert_eq!(pool.len(), 9);
+        check_invariants(&pool);
+    }
+
+    #[test]
+    #[should_panic(expected = "new element does not match the key")]
+    fn interning_mismatched_element() {
+        let mut pool: BST<String> = BST::new();
+        pool.get_or_insert_with("a", || "b".to_string());
+    }
+
+    #[test]
+    fn std_style_extremes() {
+        let mut tree = BST::from_sorted_iter(1..6);
+        assert_eq!(tree.first(), Some(&1));
+        assert_eq!(tree.last(), Some(&5));
+        assert_eq!(tree.pop_first(), Some(1));
+        assert_eq!(tree.pop_last(), Some(5));
+        assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), vec![2, 3, 4]);
+        tree.clear();
+        assert_eq!(tree.first(), None);
+        assert_eq!(tree.pop_last(), None);
+    }
+
+    #[test]
+    fn cursors() {
+        let mut tree = BST::from_sorted_iter((0..20).map(|i| i * 10));
+        {
+            let mut cursor = tree.cursor_mut();
+            assert_eq!(cursor.current(), Some(&0));
+            assert_eq!(cursor.peek_prev(), None);
+            for i in 0..20 {
+                assert_eq!(cursor.current(), Some(&(i * 10)));
+                assert_eq!(cursor.index(), Some(i as usize));
+                cursor.move_next();
+            }
+            assert_eq!(cursor.current(), None);
+            assert_eq!(cursor.peek_next(), Some(&0));
+            assert_eq!(cursor.peek_prev(), Some(&190));
+            for i in (0..20).rev() {
+                cursor.move_prev();
+                assert_eq!(cursor.current(), Some(&(i * 10)));
+            }
+            cursor.move_prev();
+            assert_eq!(cursor.current(), None);
+            cursor.move_prev();
+            assert_eq!(cursor.current(), Some(&190));
+        }
+
+        {
+            // Remove every multiple of 30 and insert a neighbour after
+            // every other element in a single pass.
+            let mut cursor = tree.cursor_mut();
+            while let Some(&x) = cursor.current() {
+                if x % 30 == 0 {
+                    assert_eq!(cursor.remove_current(), Some(x));
+                } else {
+                    cursor.insert_after(x + 1);
+                    cursor.insert_before(x - 1);
+                    assert_eq!(cursor.current(), Some(&x));
+                    cursor.move_next();
+                    cursor.move_next();
+                }
+            }
+        }
+        check_invariants(&tree);
+        let expected: Vec<i32> = (0..20).map(|i| i * 10).filter(|x| x % 30 != 0).flat_map(|x| vec![x - 1, x, x + 1]).collect();
+        assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), expected);
+
+        let mut empty: BST<i32> = BST::new();
+        {
+            let mut cursor = empty.cursor_mut();
+            assert_eq!(cursor.current(), None);
+            cursor.move_next();
+            cursor.move_prev();
+            assert_eq!(cursor.remove_current(), None);
+            cursor.insert_after(5);
+            cursor.insert_before(7);
+            assert_eq!(cursor.current(), None);
+            cursor.move_next();
+            assert_eq!(cursor.current(), Some(&5));
+        }
+        assert_eq!(empty.iter().cloned().collect::<Vec<_>>(), vec![5, 7]);
+    }
+
+    #[test]
+    #[should_panic(expected = "insert_after: element is not ordered before the next element")]
+    fn cursor_insertion_out_of_order() {
+        let mut tree = BST::from_sorted_iter(vec![1, 3]);
+        tree.cursor_mut().insert_after(4);
+    }
+
+    #[test]
+    fn tikz_output() {
+        let e: BST<i32> = BST::new();
+        assert_eq!(e.to_tikz_string(), "");
+
+        let mut tree: BST<i32> = BST::new();
+        for i in 0..3 {
+            tree.insert(i);
+        }
+        let tikz = tree.to_tikz_string();
+        assert!(tikz.starts_with("%% Put these in your preamble\n\\usepackage{tikz}\n"));
+        assert!(tikz.ends_with("\\node\n{1} child { node {0}  child [missing] child [missing] } child { node {2}  child [missing] child [missing] };\n"));
+
+        let mut out = String::new();
+        tree.write_structure(&mut out).unwrap();
+        assert_eq!(out, tikz);
+    }
+
+    #[test]
+    fn traversals() {
+        use std::ops::ControlFlow;
+
+        let tree = BST::from_sorted_iter(1..=7);
+        let visit = |order, stop: i32| {
+            let mut seen = Vec::new();
+            let flow = tree.traverse(order, |&x| {
+                seen.push(x);
+                if x == stop { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+            });
+            (seen, flow.is_break())
+        };
+        assert_eq!(visit(Traversal::PreOrder, 0), (vec![4, 2, 1, 3, 6, 5, 7], false));
+        assert_eq!(visit(Traversal::InOrder, 0), (vec![1, 2, 3, 4, 5, 6, 7], false));
+        assert_eq!(visit(Traversal::PostOrder, 0), (vec![1, 3, 2, 5, 7, 6, 4], false));
+        assert_eq!(visit(Traversal::InOrder, 3), (vec![1, 2, 3], true));
+        assert_eq!(visit(Traversal::PostOrder, 2), (vec![1, 3, 2], true));
+        assert_eq!(BST::<i32>::new().traverse(Traversal::PreOrder, |_| ControlFlow::Break(())), ControlFlow::Continue(()));
+    }
+
+    #[test]
+    fn folding() {
+        let mut tree = BST::new();
+        for i in (0..100).rev() {
+            tree.insert(i);
+        }
+        assert_eq!(tree.fold_in_order(0, |acc, &x| acc + x), 4950);
+        assert_eq!(tree.fold_in_order(String::new(), |acc, x| if *x < 5 { acc + &x.to_string() } else { acc }), "01234");
+        assert_eq!(tree.sum_by(|&x| x as f64 / 2.0), 2475.0);
+        assert_eq!(BST::<i32>::new().sum_by(|&x| x), 0);
+    }
+
+    #[test]
+    fn range_removal() {
+        use std::ops::Bound::{Excluded, Unbounded};
+
+        let mut rng = Lcg(41);
+        for _ in 0..20 {
+            let mut tree = BST::new();
+            let mut expected = std::collections::BTreeSet::new();
+            for _ in 0..200 {
+                let x = rng.next() % 1000;
+                tree.insert(x);
+                expected.insert(x);
+            }
+            let (lo, hi) = (rng.next() % 1000, rng.next() % 1000);
+            let (lo, hi) = (lo.min(hi), lo.max(hi));
+            assert_eq!(tree.remove_range(lo..hi), expected.range(lo..hi).count());
+            expected.retain(|x| !(lo..hi).contains(x));
+            check_invariants(&tree);
+            assert!(tree.iter().eq(expected.iter()));
+        }
+
+        let mut tree = BST::from_sorted_iter(0..10);
+        assert_eq!(tree.remove_range((Excluded(7), Unbounded)), 2);
+        assert_eq!(tree.remove_range(..=2), 3);
+        assert_eq!(tree.remove_range(4..4), 0);
+        assert!(tree.iter().cloned().eq(3..8));
+        assert_eq!(tree.remove_range(..), 5);
+        assert!(tree.is_empty());
+        tree.insert(1);
+        check_invariants(&tree);
+    }
+
+    #[test]
+    fn partitioning() {
+        let mut tree = BST::new();
+        for i in (0..300).rev() {
+            tree.insert(i);
+        }
+        for i in (0..300).step_by(7) {
+            tree.delete(&i);
+        }
+        let (evens, odds) = tree.partition(|x| x % 2 == 0);
+        check_invariants(&evens);
+        check_invariants(&odds);
+        assert!(evens.iter().cloned().eq((0..300).filter(|x| x % 2 == 0 && x % 7 != 0)));
+        assert!(odds.iter().cloned().eq((0..300).filter(|x| x % 2 == 1 && x % 7 != 0)));
+
+        let (all, none) = BST::from_sorted_iter(0..5).partition(|_| true);
+        assert_eq!(all.len(), 5);
+        assert!(none.is_empty());
+        let mut evens = evens;
+        evens.insert(1);
+        check_invariants(&evens);
+    }
+
+    #[test]
+    fn conversions() {
+        use std::collections::BTreeSet;
+
+        let tree = BST::from(vec![5, 3, 9, 3, 1]);
+        check_invariants(&tree);
+        assert!(tree.iter().cloned().eq(vec![1, 3, 5, 9]));
+        assert_eq!(BST::from([2, 1, 2]), BST::from_sorted_iter(vec![1, 2]));
+
+        let set: BTreeSet<_> = (0..100).collect();
+        let tree = BST::from(set.clone());
+        check_invariants(&tree);
+        assert_eq!(BTreeSet::from(tree.clone()), set);
+        assert_eq!(Vec::from(tree), (0..100).collect::<Vec<_>>());
+
+        // The first of several equal elements is kept.
+        let tree = BST::from(vec![Record(1, "first"), Record(1, "second")]);
+        assert_eq!(tree.iter().next().unwrap().1, "first");
+    }
+
+    #[test]
+    fn tikz_options() {
+        let mut tree = BST::new();
+        for c in "dbeac".chars() {
+            tree.insert(c);
+        }
+        // Labels are the elements by default.
+        let tikz = tree.to_tikz_string();
+        assert!(tikz.contains("\\node\n{d} child { node [draw=red]{b} edge from parent[red] child { node {a}  child [missing] child [missing] } child { node {c}  child [missing] child [missing] } }"));
+
+        let tikz = tree.to_tikz_string_with(&TikzOptions::new().label(TikzLabel::Slot));
+        assert!(tikz.contains("\\node\n{0} child { node [draw=red]{1} edge from parent[red] child { node {3} "));
+
+        // Elements that are not Display can still be drawn by slot.
+        let mut pairs = BST::new();
+        for c in "dbeac".chars() {
+            pairs.insert((c, ()));
+        }
+        assert_eq!(pairs.to_tikz_slots_string(&TikzOptions::new()), tikz);
+
+        let tikz = tree.to_tikz_string_with(&TikzOptions::new().node_size("8mm").red((255, 0, 0)).max_depth(1));
+        assert!(tikz.contains("\\definecolor{red}{RGB}{255,0,0}"));
+        assert!(tikz.contains("nodes={draw=treeblack,circle,minimum size=8mm}"));
+        assert!(tikz.ends_with("{d} child { node [draw=red]{b} edge from parent[red] child { node [draw=none] {\\ldots} } child { node [draw=none] {\\ldots} } } child { node {e}  child [missing] child [missing] };\n"));
+    }
+
+    #[test]
+    fn dot_output() {
+        let e: BST<i32> = BST::new();
+        assert_eq!(e.to_dot(), "digraph {\n    node [shape=circle, style=filled, fontcolor=white];\n}\n");
+
+        let mut tree: BST<&str> = BST::new();
+        tree.insert("b");
+        tree.insert("a\"");
+        assert_eq!(tree.to_dot(), "digraph {\n\
+                                   \x20   node [shape=circle, style=filled, fontcolor=white];\n\
+                                   \x20   n0 [label=\"b\", fillcolor=black];\n\
+                                   \x20   n0 -> n1 [color=red];\n\
+                                   \x20   n1 [label=\"a\\\"\", fillcolor=red];\n\
+                                   }\n");
+    }
+
+    #[test]
+    fn merging_all() {
+        let shards = (0..5u32).map(|k| BST::from_sorted_iter((0..100).filter(|i| i % 5 == k || i % 7 == 0)));
+        let merged = BST::merge_all(shards);
+        check_invariants(&merged);
+        assert!(merged.iter().cloned().eq(0..100));
+        assert!(BST::<u32>::merge_all(Vec::new()).is_empty());
+
+        // The earliest tree wins ties.
+        let a = BST::from_sorted_iter(vec![Record(1, "a"), Record(2, "a")]);
+        let b = BST::from_sorted_iter(vec![Record(0, "b"), Record(2, "b")]);
+        let merged = BST::merge_all(vec![BST::new(), a, b]);
+        assert_eq!(merged.iter().map(|r| (r.0, r.1)).collect::<Vec<_>>(), vec![(0, "b"), (1, "a"), (2, "a")]);
+    }
+
+    #[test]
+    fn mermaid() {
+        let header = "graph TD\n\
+                      \x20   classDef red fill:#d00,stroke:#d00,color:#fff\n\
+                      \x20   classDef black fill:#000,stroke:#000,color:#fff\n";
+        assert_eq!(BST::<i32>::new().to_mermaid(), header);
+
+        let mut tree: BST<&str> = BST::new();
+        tree.insert("b");
+        tree.insert("a\"#");
+        tree.insert("c");
+        assert_eq!(tree.to_mermaid(), String::from(header) + "    n0((\"b\")):::black\n\
+                                                             \x20   n0 --> n1\n\
+                                                             \x20   n1((\"a#quot;#35;\")):::black\n\
+                                                             \x20   n0 --> n2\n\
+                                                             \x20   n2((\"c\")):::black\n");
+        tree.delete(&"c");
+        assert!(tree.to_mermaid().ends_with("    n0 ==> n1\n    n1((\"a#quot;#35;\")):::red\n"));
+    }
+
+    #[test]
+    fn compaction() {
+        let mut tree: BST<i32> = BST::new();
+        for i in 0..1000 {
+            tree.insert(i);
+        }
+        for i in 0..1000 {
+            if i % 4 != 0 {
+                tree.delete(&i);
+            }
+        }
+        assert_eq!(tree.nodes.len(), 1000);
+        assert!(tree.compact() >= 750);
+        assert_eq!(tree.nodes.len(), 250);
+        assert!(tree.deleted_indices.is_empty());
+        check_invariants(&tree);
+        assert!(tree.iter().cloned().eq((0..250).map(|i| i * 4)));
+        tree.insert(1);
+        tree.delete(&0);
+        check_invariants(&tree);
+
+        let mut e: BST<i32> = BST::new();
+        assert_eq!(e.compact(), 0);
+    }
+
+    #[test]
+    fn capacity_management() {
+        let mut tree: BST<i32> = BST::with_capacity(100);
+        assert!(tree.capacity() >= 100);
+        for i in 0..100 {
+            tree.insert(i);
+        }
+        assert!(tree.capacity() >= 100);
+
+        for i in 0..10 {
+            tree.delete(&i);
+        }
+        tree.reserve(10);
+        let capacity = tree.capacity();
+        for i in 0..10 {
+            tree.insert(i);
+        }
+        assert_eq!(tree.capacity(), capacity);
+        tree.reserve(1000);
+        assert!(tree.capacity() >= 1100);
+
+        tree.shrink_to_fit();
+        assert_eq!(tree.capacity(), 100);
+        for i in 50..100 {
+            tree.delete(&i);
+        }
+        tree.shrink_to_fit();
+        check_invariants(&tree);
+        assert!(tree.capacity() <= 100);
+        assert!(tree.iter().cloned().eq(0..50));
+    }
+
+    #[test]
+    fn custom_allocator() {
+        use allocator_api2::alloc::{AllocError, Allocator, Global, Layout};
+        use core::cell::Cell;
+        use core::ptr::NonNull;
+
+        // Forwards to the global allocator, keeping count of live bytes.
+        #[derive(Clone, Copy)]
+        struct Counting<'a>(&'a Cell<usize>);
+
+        unsafe impl<'a> Allocator for Counting<'a> {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                self.0.set(self.0.get() + layout.size());
+                Global.allocate(layout)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                self.0.set(self.0.get() - layout.size());
+                Global.deallocate(ptr, layout)
+            }
+        }
+
+        let live = Cell::new(0);
+        {
+            let mut tree = BST::new_in(Counting(&live));
+            for i in 0..100u32 {
+                tree.insert(i * 7 % 100);
+            }
+            assert!(live.get() >= 100 * std::mem::size_of::<u32>());
+            for i in 0..50 {
+                assert_eq!(tree.delete(&(i * 2)), Some(i * 2));
+            }
+            assert_eq!(tree.try_self_check(), Ok(()));
+            tree.compact();
+            assert_eq!(tree.capacity(), 50);
+            assert_eq!(tree.try_self_check(), Ok(()));
+            assert!(tree.iter().cloned().eq((0..50).map(|i| i * 2 + 1)));
+            assert_eq!(tree.rank(&51), 25);
+            assert!(tree.range(10..20).cloned().eq(vec![11, 13, 15, 17, 19]));
+            assert_eq!(tree.range_count(10..20), 5);
+
+            let mut other = BST::new_in(Counting(&live));
+            other.insert(0);
+            other.insert(1);
+            assert!(tree.union_iter(&other).cloned().take(3).eq(vec![0, 1, 3]));
+            assert!(tree.intersection_iter(&other).cloned().eq(vec![1]));
+            assert!(!tree.is_disjoint(&other));
+            other.delete(&0);
+            assert!(other.is_subset(&tree));
+            assert!(tree.into_iter().eq((0..50).map(|i| i * 2 + 1)));
+        }
+        assert_eq!(live.get(), 0);
+    }
+
+    #[test]
+    fn freezing() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let tree = BST::from_sorted_iter((0..200u32).map(|i| i * 3));
+        let frozen = tree.clone().freeze();
+        assert_eq!(frozen.len(), tree.len());
+        assert!(frozen.iter().eq(tree.iter()));
+        for x in 0..610 {
+            assert_eq!(frozen.member(&x), tree.member(&x));
+            assert_eq!(frozen.rank(&x), tree.rank(&x));
+            assert_eq!(frozen.floor(&x), tree.floor(&x));
+            assert_eq!(frozen.ceiling(&x), tree.ceiling(&x));
+            assert_eq!(frozen.predecessor(&x), tree.predecessor(&x));
+            assert_eq!(frozen.successor(&x), tree.successor(&x));
+            assert!(frozen.range(x..x + 10).eq(tree.range(x..x + 10)));
+        }
+        assert_eq!(frozen.select(10), tree.select(10));
+        assert_eq!((frozen.first(), frozen.last()), (tree.first(), tree.last()));
+
+        let shared = Arc::new(frozen);
+        let readers: Vec<_> = (0..4).map(|t| {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || shared.range(t * 100..).count())
+        }).collect();
+        let counts: Vec<usize> = readers.into_iter().map(|r| r.join().unwrap()).collect();
+        assert_eq!(counts, (0..4).map(|t| tree.range(t * 100..).count()).collect::<Vec<_>>());
+
+        let thawed = Arc::try_unwrap(shared).unwrap().thaw();
+        check_invariants(&thawed);
+        assert_eq!(thawed, tree);
+    }
+
+    #[test]
+    fn taking_smallest() {
+        let mut tree = BST::new();
+        for i in 0..100u32 {
+            tree.insert(i * 37 % 100);
+        }
+        tree.delete(&3);
+        assert_eq!(tree.take_min_n(0), vec![]);
+        assert_eq!(tree.take_min_n(5), vec![0, 1, 2, 4, 5]);
+        check_invariants(&tree);
+        assert_eq!(tree.take_min_n(30), (6..36).collect::<Vec<_>>());
+        check_invariants(&tree);
+        assert_eq!(tree.first(), Some(&36));
+        tree.insert(1);
+        assert_eq!(tree.take_min_n(1), vec![1]);
+        assert_eq!(tree.take_min_n(1000), (36..100).collect::<Vec<_>>());
+        assert!(tree.is_empty());
+        check_invariants(&tree);
+    }
+
+    #[test]
+    fn draining_below() {
+        let mut deadlines = BST::from_sorted_iter((0..100u64).map(|i| i * 10));
+        assert_eq!(deadlines.drain_below(&0).count(), 0);
+        assert!(deadlines.drain_below(&35).eq(vec![0, 10, 20, 30]));
+        check_invariants(&deadlines);
+        assert!(deadlines.drain_below(&50).eq(vec![40]));
+        assert_eq!(deadlines.first(), Some(&50));
+        assert_eq!(deadlines.drain_below(&u64::MAX).len(), 95);
+        assert!(deadlines.is_empty());
+    }
+
+    #[test]
+    fn equal_ranges() {
+        let tree = BST::from_sorted_iter((0..100u32).filter(|x| x % 3 != 0));
+        // Compare by the tens digit only.
+        let (ranks, run) = tree.equal_range_by(|e| 4.cmp(&(e / 10)));
+        assert_eq!(ranks, 26..33);
+        assert!(run.cloned().eq(vec![40, 41, 43, 44, 46, 47, 49]));
+        assert!(tree.equal_range_by(|e| 4.cmp(&(e / 10))).1.rev().cloned().eq(vec![49, 47, 46, 44, 43, 41, 40]));
+
+        let (ranks, run) = tree.equal_range_by(|e| 50.cmp(e));
+        assert_eq!((ranks, run.count()), (33..34, 1));
+        let (ranks, run) = tree.equal_range_by(|e| 51.cmp(e));
+        assert_eq!((ranks, run.count()), (34..34, 0));
+        let (ranks, run) = tree.equal_range_by(|e| 20.cmp(&(e / 10)));
+        assert_eq!((ranks, run.count()), (66..66, 0));
+    }
+
+    #[test]
+    fn batch_membership() {
+        let tree = BST::from_sorted_iter((0..100u32).map(|i| i * 3));
+        let queries: Vec<u32> = (0..400).collect();
+        let found = tree.contains_sorted(&queries);
+        assert!(found.iter().zip(&queries).all(|(&f, q)| f == tree.member(q)));
+        assert_eq!(tree.contains_sorted(&[3, 3, 4, 297, 300]), vec![true, true, false, true, false]);
+        assert!(tree.contains_sorted::<u32>(&[]).is_empty());
+        assert_eq!(BST::<u32>::new().contains_sorted(&[1, 2]), vec![false, false]);
+        assert!(tree.contains_all(&[0, 6, 60, 297]));
+        assert!(!tree.contains_all(&[0, 7]));
+        assert!(tree.contains_all::<u32>(&[]));
+    }
+
+    #[test]
+    fn iterating_from() {
+        let tree = BST::from_sorted_iter((0..100u32).map(|i| i * 3));
+        assert!(tree.iter_from(&30).cloned().eq((10..100).map(|i| i * 3)));
+        assert!(tree.iter_from(&31).cloned().eq((11..100).map(|i| i * 3)));
+        assert_eq!(tree.iter_from(&1000).next(), None);
+        assert_eq!(tree.iter_from(&0).len(), 100);
+
+        // Paging through the tree, resuming after the last element seen.
+        let mut pages = Vec::new();
+        let mut start = 0;
+        loop {
+            let page: Vec<u32> = tree.iter_from(&start).take(16).cloned().collect();
+            match page.last() {
+                Some(&last) => start = last + 1,
+                None => break,
+            }
+            pages.push(page);
+        }
+        assert_eq!(pages.len(), 7);
+        assert!(pages.concat().iter().eq(tree.iter()));
+    }
+
+    #[test]
+    fn rebuilding() {
+        let mut tree = BST::new();
+        for i in 0..1000u32 {
+            tree.insert(i);
+        }
+        for i in 0..700 {
+            tree.delete(&(i * 7 % 1000));
+        }
+        let expected: Vec<u32> = tree.iter().cloned().collect();
+        tree.rebuild();
+        check_invariants(&tree);
+        assert_eq!(tree.iter().cloned().collect::<Vec<_>>(), expected);
+        let stats = tree.shape_stats();
+        assert_eq!(stats, BST::from_sorted_iter(expected).shape_stats());
+        // The least height that 300 elements fit in.
+        assert_eq!(stats.height, 9);
+        let memory = tree.memory_usage();
+        assert_eq!((memory.live_nodes, memory.deleted_slots, memory.capacity), (300, 0, 300));
+
+        let mut empty = BST::<u32>::new();
+        empty.rebuild();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn modifying() {
+        let mut tree = BST::new();
+        for i in 0..50 {
+            tree.insert(Record(i, "old"));
+        }
+        assert!(tree.modify(&Record(7, ""), |r| r.1 = "new"));
+        assert_eq!(tree.get(&Record(7, "")).map(|r| r.1), Some("new"));
+        assert!(!tree.modify(&Record(70, ""), |r| r.1 = "new"));
+
+        // Elements whose order changes are moved.
+        assert!(tree.modify(&Record(3, ""), |r| r.0 = 100));
+        check_invariants(&tree);
+        assert!(!tree.member(&Record(3, "")));
+        assert_eq!(tree.last().map(|r| (r.0, r.1)), Some((100, "old")));
+        assert!(tree.modify(&Record(7, ""), |r| r.0 = 8));
+        check_invariants(&tree);
+        assert_eq!(tree.len(), 49);
+        assert_eq!(tree.get(&Record(8, "")).map(|r| r.1), Some("new"));
+
+        let mut tree = BST::from_sorted_iter(0..200u32);
+        for i in 0..100 {
+            assert!(tree.modify(&(i * 2), |x| *x += 1000));
+            check_invariants(&tree);
+        }
+        assert!(tree.iter().cloned().eq((0..100).map(|i| i * 2 + 1).chain((0..100).map(|i| i * 2 + 1000))));
+    }
+
+    #[test]
+    fn debug_format() {
+        let tree = BST::from_sorted_iter(1..4);
+        assert_eq!(format!("{:?}", tree), "{1, 2, 3}");
+        assert_eq!(format!("{:?}", BST::<u32>::new()), "{}");
+        assert_eq!(format!("{:#?}", BST::<u32>::new()), "Leaf");
+        let shape = format!("{:#?}", tree);
+        assert!(shape.starts_with("Node {\n    elem: 2,\n    color: Black,\n    left: Node {\n        elem: 1,"), "{}", shape);
+        assert_eq!(shape.matches("Leaf").count(), 4);
+        let mut map = super::LLRBMap::new();
+        map.insert(1, "one");
+        assert!(format!("{:?}", map).contains("\"one\""));
+    }
+
+    #[test]
+    fn mapping() {
+        let mut tree = BST::new();
+        for i in 0..100u32 {
+            tree.insert(i * 37 % 100);
+        }
+        for i in 0..10 {
+            tree.delete(&(i * 3));
+        }
+        let doubled = tree.map_monotone(|&x| x as u64 * 2);
+        check_invariants(&doubled);
+        assert!(doubled.iter().cloned().eq(tree.iter().map(|&x| x as u64 * 2)));
+        assert_eq!(doubled.shape_stats(), tree.shape_stats());
+        assert_eq!(doubled.memory_usage().deleted_slots, 10);
+
+        let residues = tree.map(|&x| x % 7);
+        check_invariants(&residues);
+        assert!(residues.iter().cloned().eq(0..7));
+        assert!(BST::<u32>::new().map_monotone(|&x| x).is_empty());
+    }
+
+    #[test]
+    fn batch_insertion() {
+        let mut tree = BST::new();
+        for i in 0..50 {
+            tree.insert(Record(i * 2, "old"));
+        }
+        tree.delete(&Record(10, ""));
+        tree.insert_many((0..60).rev().map(|i| Record(i, "first")).chain(vec![Record(7, "second")]));
+        check_invariants(&tree);
+        assert!(tree.iter().map(|r| r.0).eq((0..60).chain((60..100).step_by(2))));
+        assert_eq!(tree.get(&Record(7, "")).unwrap().1, "second");
+        assert_eq!(tree.get(&Record(8, "")).unwrap().1, "first");
+        assert_eq!(tree.get(&Record(60, "")).unwrap().1, "old");
+
+        tree.insert_many(vec![]);
+        assert_eq!(tree.len(), 80);
+        let mut empty = BST::new();
+        empty.insert_many(vec![3, 1, 2, 3]);
+        check_invariants(&empty);
+        assert!(empty.iter().cloned().eq(1..4));
+    }
+
+    #[test]
+    fn std_traits() {
+        let mut tree: BST<u32> = Default::default();
+        assert!(tree.is_empty());
+        tree.insert_many(vec![30, 10, 20]);
+        let mut seen = Vec::new();
+        for x in &tree {
+            seen.push(*x);
+        }
+        assert_eq!(seen, vec![10, 20, 30]);
+        assert_eq!((tree[0], tree[1], tree[2]), (10, 20, 30));
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds: the len is 3 but the index is 3")]
+    fn index_out_of_bounds() {
+        let tree = BST::from(vec![1, 2, 3]);
+        let _ = tree[3];
+    }
+
+    #[test]
+    fn memory_usage() {
+        let mut tree = BST::with_capacity(100);
+        for i in 0..100u32 {
+            tree.insert(i);
+        }
+        let report = tree.memory_usage();
+        assert_eq!((report.live_nodes, report.deleted_slots, report.capacity), (100, 0, tree.capacity()));
+        assert!(report.bytes_allocated >= 100 * std::mem::size_of::<u32>());
+        assert_eq!(report.fragmentation(), 0.0);
+
+        for i in 0..25 {
+            tree.delete(&i);
+        }
+        let report = tree.memory_usage();
+        assert_eq!((report.live_nodes, report.deleted_slots), (75, 25));
+        assert_eq!(report.fragmentation(), 0.25);
+        tree.compact();
+        tree.shrink_to_fit();
+        let report = tree.memory_usage();
+        assert_eq!((report.live_nodes, report.deleted_slots, report.capacity), (75, 0, 75));
+        assert_eq!(BST::<u32>::new().memory_usage().fragmentation(), 0.0);
+    }
+
+    #[cfg(feature = "parent-pointers")]
+    #[test]
+    fn parent_pointers() {
+        let mut tree = BST::new();
+        for i in 0..500u32 {
+            tree.insert(i * 37 % 500);
+        }
+        for i in 0..100 {
+            tree.delete(&(i * 5));
+        }
+        let mut other = tree.split_off(&250);
+        other.append(&mut BST::from_sorted_iter(500..600));
+        tree.compact();
+        check_invariants(&tree);
+        check_invariants(&other);
+
+        // The iterator holds no stacks, and both ends still meet exactly.
+        assert!(std::mem::size_of::<super::Iter<u32>>() <= 6 * std::mem::size_of::<usize>());
+        let mut iter = other.iter();
+        let (mut front, mut back): (Vec<u32>, Vec<u32>) = (Vec::new(), Vec::new());
+        while let Some(&x) = iter.next() {
+            front.push(x);
+            back.extend(iter.next_back());
+        }
+        back.reverse();
+        front.extend(back);
+        assert!(front.iter().eq(other.iter()));
+        assert_eq!(front.len(), 250 - 50 + 100);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_iteration() {
+        use rayon::prelude::*;
+
+        let tree = BST::from_sorted_iter(0..10000u64);
+        assert_eq!(tree.par_iter().sum::<u64>(), 10000 * 9999 / 2);
+        assert_eq!(tree.par_iter().count(), 10000);
+        let doubled: Vec<u64> = (&tree).into_par_iter().map(|x| x * 2).collect();
+        assert_eq!(doubled, (0..10000).map(|x| x * 2).collect::<Vec<_>>());
+        assert_eq!(BST::<u64>::new().par_iter().count(), 0);
+    }
+
+    #[test]
+    fn compact_indices() {
+        use std::mem::size_of;
+
+        // Debug builds add a generation to every Ptr.
+        assert_eq!(size_of::<Option<Ptr>>(), if cfg!(debug_assertions) { 8 } else { 4 });
+        assert_eq!(size_of::<Option<super::Node<u32>>>(), size_of::<super::Node<u32>>());
+        assert_eq!(Ptr::<u32>::new(0).index(), 0);
+        assert_eq!(Ptr::<u32>::new(u32::MAX as usize - 1).index(), u32::MAX as usize - 1);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "stale reference")]
+    fn stale_references() {
+        let mut tree = BST::new();
+        for i in 0..10 {
+            tree.insert(i);
+        }
+        let stale = tree.find_by(|e| 4.cmp(e)).unwrap();
+        tree.delete(&4);
+        // The freed slot is reused for the new element.
+        let fresh = tree.insert_absent(40);
+        assert_eq!(fresh.index(), stale.index());
+        assert_eq!(tree.deref(&fresh).elem, 40);
+        tree.deref(&stale);
+    }
+
+    #[test]
+    fn corruption_checks() {
+        let mut tree = BST::new();
+        for i in 0..20 {
+            tree.insert(i);
+        }
+        tree.delete(&7);
+        assert_eq!(tree.try_self_check(), Ok(()));
+
+        let root = tree.root.unwrap();
+        let left = tree.deref(&root).left.unwrap();
+        let right = tree.deref(&root).right.unwrap();
+
+        let mut broken = tree.clone();
+        broken.deref_mut(&root).color = Color::Red;
+        assert_eq!(broken.try_self_check(), Err(CorruptionError::RedRoot));
+
+        let mut broken = tree.clone();
+        let (a, b) = (broken.deref(&left).elem, broken.deref(&right).elem);
+        broken.deref_mut(&left).elem = b;
+        broken.deref_mut(&right).elem = a;
+        assert!(matches!(broken.try_self_check(), Err(CorruptionError::OutOfOrder(_))));
+
+        let mut broken = tree.clone();
+        broken.deref_mut(&root).right = Some(left);
+        assert!(matches!(broken.try_self_check(), Err(CorruptionError::Shared(_))));
+
+        let mut broken = tree.clone();
+        let freed = broken.deleted_indices[0];
+        broken.deref_mut(&root).left = Some(freed);
+        assert_eq!(broken.try_self_check(), Err(CorruptionError::Deleted(freed.index())));
+
+        let mut broken = tree.clone();
+        broken.deref_mut(&root).size += 1;
+        assert_eq!(broken.try_self_check(), Err(CorruptionError::WrongSize(root.index())));
+
+        let mut broken = tree.clone();
+        broken.deleted_indices.push(root);
+        assert_eq!(broken.try_self_check(), Err(CorruptionError::WrongLength));
+
+        // Rotations and color flips report a child that is missing or
+        // deleted before changing anything.
+        let mut broken = tree.clone();
+        let min = broken.min_node().unwrap();
+        assert_eq!(broken.try_rotate_right(min), Err(CorruptionError::MissingChild(min.index())));
+        assert_eq!(broken.try_move_red_up_or_down(min), Err(CorruptionError::MissingChild(min.index())));
+        let mut broken = tree.clone();
+        let freed = broken.deleted_indices[0];
+        broken.deref_mut(&left).right = Some(freed);
+        assert_eq!(broken.try_rotate_right(root), Err(CorruptionError::Deleted(freed.index())));
+        assert_eq!(broken.deref(&root).left, Some(left));
+    }
+
+    #[test]
+    fn handles() {
+        let mut tree = BST::new();
+        let mut rng = Lcg(11);
+        let mut handles = Vec::new();
+        for i in 0..500 {
+            let (handle, replaced) = tree.insert_with_handle(i);
+            assert_eq!(replaced, None);
+            handles.push((i, handle));
+        }
+        // Deleting through keys and through handles must leave every other
+        // handle pointing at its own element.
+        while handles.len() > 100 {
+            let (elem, handle) = handles.swap_remove(rng.next() as usize % handles.len());
+            if elem % 2 == 0 {
+                assert_eq!(tree.remove_by_handle(handle), Some(elem));
+            } else {
+                assert_eq!(tree.delete(&elem), Some(elem));
+            }
+            check_invariants(&tree);
+            for &(elem, handle) in &handles {
+                assert_eq!(tree.get_by_handle(handle), Some(&elem));
+            }
+        }
+        for i in 1000..1100 {
+            handles.push((i, tree.insert_with_handle(i).0));
+        }
+        for &(elem, handle) in &handles {
+            assert_eq!(tree.get_by_handle(handle), Some(&elem));
+        }
+        let (elem, handle) = handles[0];
+        assert_eq!(tree.insert_with_handle(elem), (handle, Some(elem)));
+    }
+
+    // Emptying the tree in any way frees every slot, and debug builds must
+    // still tell a handle from before from one to whatever takes the slot
+    // next.
+    #[test]
+    #[cfg(debug_assertions)]
+    fn handles_after_clear() {
+        let mut tree = BST::new();
+        let (one, _) = tree.insert_with_handle(1);
+        tree.clear();
+        tree.insert(2);
+        assert_eq!(tree.get_by_handle(one), None);
+        assert_eq!(tree.try_self_check(), Ok(()));
+
+        let (two, _) = tree.insert_with_handle(3);
+        assert_eq!(tree.take_min(), Some(2));
+        assert_eq!(tree.take_min(), Some(3));
+        let (four, _) = tree.insert_with_handle(4);
+        tree.insert(5);
+        assert_eq!(tree.get_by_handle(two), None);
+        assert_eq!(tree.get_by_handle(four), Some(&4));
+
+        assert_eq!(tree.drain().count(), 2);
+        tree.insert(6);
+        tree.insert(7);
+        assert_eq!((tree.get_by_handle(one), tree.get_by_handle(two), tree.get_by_handle(four)), (None, None, None));
+        assert_eq!(tree.try_self_check(), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "BST cannot hold more than")]
+    fn index_overflow() {
+        Ptr::<u32>::new(u32::MAX as usize);
+    }
+
+    #[test]
+    fn narrow_indices() {
+        use allocator_api2::alloc::Global;
+        use std::mem::size_of;
+
+        assert_eq!(size_of::<Option<Ptr<u16>>>(), if cfg!(debug_assertions) { 8 } else { 2 });
+        let mut tree: BST<u32, Global, u16> = BST::default();
+        for i in 0..u16::MAX as u32 {
+            tree.insert(i);
+        }
+        assert_eq!(tree.try_insert(70000), Err(super::CapacityError(70000)));
+        assert_eq!(tree.try_insert(5), Ok(Some(5)));
+        assert_eq!(tree.delete(&6), Some(6));
+        assert_eq!(tree.try_insert(70000), Ok(None));
+        assert_eq!(tree.len(), u16::MAX as usize);
+        assert_eq!(tree.try_self_check(), Ok(()));
+        assert!(tree.range(4..8).cloned().eq(vec![4, 5, 7]));
+        assert!(tree.iter_from(&65533).cloned().eq(vec![65533, 65534, 70000]));
+        assert_eq!(tree.into_iter().last(), Some(70000));
+    }
+
+    #[test]
+    fn small_stack() {
+        let handle = std::thread::Builder::new().stack_size(32 * 1024).spawn(|| {
+            let mut tree = BST::new();
+            let mut rng = Lcg(37);
+            for _ in 0..20000 {
+                tree.insert(rng.next() % 5000);
+            }
+            for i in 0..2500 {
+                tree.delete(&(i * 2));
+            }
+            tree.take_min();
+            tree.take_max();
+            check_invariants(&tree);
+            tree.len()
+        }).unwrap();
+        assert!(handle.join().unwrap() <= 2498);
+    }
+
+    #[test]
+    fn shape_statistics() {
+        let tree: BST<i32> = BST::new();
+        assert_eq!((tree.height(), tree.black_height()), (0, 0));
+        assert_eq!(tree.shape_stats().nodes_per_depth, vec![]);
+
+        let mut tree = BST::new();
+        for i in 0..7 {
+            tree.insert(i);
+        }
+        // Ascending insertions leave a perfectly balanced tree of height 3.
+        let stats = tree.shape_stats();
+        assert_eq!(stats.len, 7);
+        assert_eq!(stats.height, tree.height());
+        assert_eq!(stats.nodes_per_depth, vec![1, 2, 4]);
+        assert_eq!(stats.red_nodes, 0);
+        assert_eq!(stats.black_height, 3);
+        assert_eq!(stats.average_depth, 10.0 / 7.0);
+
+        let mut rng = Lcg(5);
+        for _ in 0..5000 {
+            tree.insert((rng.next() % 100000) as i32);
+        }
+        let stats = tree.shape_stats();
+        assert_eq!(stats.nodes_per_depth.iter().sum::<usize>(), tree.len());
+        assert!(stats.height <= 2 * stats.black_height);
+        assert!(stats.red_nodes > 0);
+    }
+
+    #[test]
+    fn range_counting() {
+        use std::ops::Bound::{Excluded, Included, Unbounded};
+
+        let mut tree = BST::new();
+        for i in 0..100 {
+            tree.insert(i * 2);
+        }
+        assert_eq!(tree.range_count(..), 100);
+        assert_eq!(tree.range_count(10..20), 5);
+        assert_eq!(tree.range_count(10..=20), 6);
+        assert_eq!(tree.range_count(11..20), 4);
+        assert_eq!(tree.range_count(..7), 4);
+        assert_eq!(tree.range_count(190..), 5);
+        assert_eq!(tree.range_count(500..), 0);
+        assert_eq!(tree.range_count(7..7), 0);
+        assert_eq!(tree.range_count((Excluded(10), Included(20))), 5);
+        assert_eq!(tree.range_count((Excluded(10), Unbounded)), 94);
+
+        let mut rng = Lcg(11);
+        for _ in 0..200 {
+            let a = (rng.next() % 220) as i32;
+            let b = a + (rng.next() % 50) as i32;
+            assert_eq!(tree.range_count(a..b), tree.range(a..b).count());
+            assert_eq!(tree.range_count(a..=b), tree.range(a..=b).count());
+        }
+    }
+
+    #[test]
+    fn custom_ordering() {
+        use super::BSTBy;
+
+        // Neither field ordering is the natural one for the tuple.
+        let mut events = BSTBy::new(|a: &(u32, &str), b: &(u32, &str)| a.0.cmp(&b.0));
+        events.insert((30, "lunch"));
+        events.insert((10, "wake"));
+        events.insert((20, "coffee"));
+        events.insert((20, "tea"));
+        assert_eq!(events.len(), 3);
+        assert_eq!(events.iter().map(|e| e.1).collect::<Vec<_>>(), vec!["wake", "tea", "lunch"]);
+        assert_eq!(events.get(&(20, "")), Some(&(20, "tea")));
+        assert!(events.contains(&(30, "")));
+        assert_eq!(events.first(), Some(&(10, "wake")));
+        assert_eq!(events.last(), Some(&(30, "lunch")));
+        assert_eq!(events.remove(&(10, "")), Some((10, "wake")));
+        assert_eq!(events.remove(&(10, "")), None);
+
+        let mut reversed = BSTBy::new(|a: &i32, b: &i32| b.cmp(a));
+        let mut rng = Lcg(3);
+        for _ in 0..500 {
+            reversed.insert((rng.next() % 1000) as i32);
+        }
+        let elems: Vec<i32> = reversed.iter().cloned().collect();
+        assert!(elems.windows(2).all(|w| w[0] > w[1]));
+        assert_eq!(format!("{:?}", BSTBy::new(|a: &f64, b: &f64| a.total_cmp(b))), "{}");
+    }
+
+    #[test]
+    fn bound_cursors() {
+        use std::ops::Bound::{Excluded, Included, Unbounded};
+
+        let mut tree = BST::new();
+        for i in 1..10 {
+            tree.insert(i * 10);
+        }
+        let cursor = tree.lower_bound(Included(&30));
+        assert_eq!((cursor.current(), cursor.index()), (Some(&30), Some(2)));
+        assert_eq!(tree.lower_bound(Excluded(&30)).current(), Some(&40));
+        assert_eq!(tree.lower_bound(Included(&35)).current(), Some(&40));
+        assert_eq!(tree.lower_bound(Unbounded).current(), Some(&10));
+        assert_eq!(tree.lower_bound(Excluded(&90)).current(), None);
+        assert_eq!(tree.upper_bound(Included(&30)).current(), Some(&30));
+        assert_eq!(tree.upper_bound(Excluded(&30)).current(), Some(&20));
+        assert_eq!(tree.upper_bound(Included(&35)).current(), Some(&30));
+        assert_eq!(tree.upper_bound(Unbounded).current(), Some(&90));
+        assert_eq!(tree.upper_bound(Excluded(&10)).current(), None);
+
+        // Walk forward from a bound, through the ghost and around again.
+        let mut cursor = tree.lower_bound(Included(&75));
+        let mut seen = Vec::new();
+        while let Some(x) = cursor.current() {
+            seen.push(*x);
+            cursor.move_next();
+        }
+        assert_eq!(seen, vec![80, 90]);
+        assert_eq!(cursor.index(), None);
+        assert_eq!(cursor.peek_next(), Some(&10));
+        assert_eq!(cursor.peek_prev(), Some(&90));
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&10));
+        assert_eq!(cursor.peek_prev(), None);
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.clone().current(), Some(&90));
+        assert_eq!(cursor.peek_next(), None);
+
+        let empty: BST<i32> = BST::new();
+        let mut cursor = empty.lower_bound(Unbounded);
+        cursor.move_next();
+        cursor.move_prev();
+        assert_eq!((cursor.current(), cursor.peek_next(), cursor.peek_prev()), (None, None, None));
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn random_sampling() {
+        let mut rng = Lcg(17);
+        assert_eq!(BST::<i32>::new().choose(&mut rng), None);
+
+        let mut tree = BST::new();
+        for i in 0..10 {
+            tree.insert(i);
+        }
+        let mut counts = [0; 10];
+        for _ in 0..10000 {
+            counts[*tree.choose(&mut rng).unwrap()] += 1;
+        }
+        assert!(counts.iter().all(|&c| c > 800 && c < 1200), "{:?}", counts);
+    }
+
+    #[test]
+    fn lazy_set_operations() {
+        let collect = |iter: &mut dyn Iterator<Item = &i32>| iter.cloned().collect::<Vec<_>>();
+        let evens: BST<i32> = BST::from_sorted_iter((0..20).filter(|x| x % 2 == 0));
+        let triples: BST<i32> = BST::from_sorted_iter((0..20).filter(|x| x % 3 == 0));
+        let empty: BST<i32> = BST::new();
+
+        assert_eq!(collect(&mut evens.union_iter(&triples)), vec![0, 2, 3, 4, 6, 8, 9, 10, 12, 14, 15, 16, 18]);
+        assert_eq!(collect(&mut evens.intersection_iter(&triples)), vec![0, 6, 12, 18]);
+        assert_eq!(collect(&mut evens.difference_iter(&triples)), vec![2, 4, 8, 10, 14, 16]);
+        assert_eq!(collect(&mut triples.difference_iter(&evens)), vec![3, 9, 15]);
+        assert_eq!(collect(&mut evens.union_iter(&empty)), collect(&mut evens.iter()));
+        assert_eq!(evens.intersection_iter(&empty).next(), None);
+        assert_eq!(collect(&mut evens.difference_iter(&empty)), collect(&mut evens.iter()));
+        assert_eq!(evens.union_iter(&triples).size_hint(), (10, Some(17)));
+
+        // The lazy and eager operations agree.
+        let mut rng = Lcg(23);
+        let mut a = BST::new();
+        let mut b = BST::new();
+        for _ in 0..300 {
+            a.insert((rng.next() % 500) as i32);
+            b.insert((rng.next() % 500) as i32);
+        }
+        assert!(a.union_iter(&b).eq(a.union(&b).iter()));
+        assert!(a.intersection_iter(&b).eq(a.intersection(&b).iter()));
+        assert!(a.difference_iter(&b).eq(a.difference(&b).iter()));
+    }
+
+    #[test]
+    fn merging() {
+        // Ordered by the key alone, so that the policy decides which
+        // record of a shared key is seen.
+        let a = BST::from_sorted_iter(vec![Record(1, "a"), Record(3, "a"), Record(5, "a")]);
+        let b = BST::from_sorted_iter(vec![Record(2, "b"), Record(3, "b"), Record(6, "b")]);
+        let collect = |policy| a.merge_iter(&b, policy).map(|r| (r.0, r.1)).collect::<Vec<_>>();
+        assert_eq!(collect(MergePolicy::Both), vec![(1, "a"), (2, "b"), (3, "a"), (3, "b"), (5, "a"), (6, "b")]);
+        assert_eq!(collect(MergePolicy::First), vec![(1, "a"), (2, "b"), (3, "a"), (5, "a"), (6, "b")]);
+        assert_eq!(collect(MergePolicy::Second), vec![(1, "a"), (2, "b"), (3, "b"), (5, "a"), (6, "b")]);
+        assert_eq!(a.merge_iter(&b, MergePolicy::Both).size_hint(), (6, Some(6)));
+        assert_eq!(a.merge_iter(&b, MergePolicy::First).size_hint(), (3, Some(6)));
+
+        let empty = BST::new();
+        assert_eq!(a.merge_iter(&empty, MergePolicy::Second).count(), 3);
+        assert_eq!(empty.merge_iter(&b, MergePolicy::First).count(), 3);
+    }
+
+    #[test]
+    fn diffing() {
+        let a: BST<i32> = BST::from_sorted_iter(vec![1, 2, 4, 7]);
+        let b: BST<i32> = BST::from_sorted_iter(vec![2, 3, 4, 8, 9]);
+        assert_eq!(a.diff(&b).collect::<Vec<_>>(), vec![
+            DiffEntry::OnlyLeft(&1), DiffEntry::OnlyRight(&3), DiffEntry::OnlyLeft(&7), DiffEntry::OnlyRight(&8), DiffEntry::OnlyRight(&9),
+        ]);
+        assert_eq!(a.diff(&b).size_hint(), (1, Some(9)));
+        assert_eq!(a.diff(&a).next(), None);
+    }
+
+    #[test]
+    fn symmetric_difference() {
+        let evens: BST<i32> = BST::from_sorted_iter((0..20).filter(|x| x % 2 == 0));
+        let triples: BST<i32> = BST::from_sorted_iter((0..20).filter(|x| x % 3 == 0));
+        let expected = vec![2, 3, 4, 8, 9, 10, 14, 15, 16];
+        assert_eq!(evens.symmetric_difference_iter(&triples).cloned().collect::<Vec<_>>(), expected);
+        assert_eq!(triples.symmetric_difference_iter(&evens).cloned().collect::<Vec<_>>(), expected);
+        let eager = evens.symmetric_difference(&triples);
+        check_invariants(&eager);
+        assert_eq!(eager.iter().cloned().collect::<Vec<_>>(), expected);
+        assert!(evens.symmetric_difference(&evens).is_empty());
+        assert!(evens.symmetric_difference(&BST::new()).iter().eq(evens.iter()));
     }
 }
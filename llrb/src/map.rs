@@ -0,0 +1,653 @@
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::iter::Peekable;
+use core::mem;
+use core::ops::{Bound, RangeBounds};
+
+use alloc::vec::{self, Vec};
+
+use super::{check_range_bounds, DiffEntry, BST, Ptr};
+use iter;
+
+// The map is a set of entries that are compared by key alone, so that all
+// the balancing logic is shared with BST.
+#[derive(Debug, Clone)]
+struct MapEntry<K, V> {
+    key: K,
+    value: V,
+}
+
+impl<K: Ord, V> PartialEq for MapEntry<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<K: Ord, V> Eq for MapEntry<K, V> {}
+
+impl<K: Ord, V> PartialOrd for MapEntry<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord, V> Ord for MapEntry<K, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LLRBMap<K, V> {
+    tree: BST<MapEntry<K, V>>,
+}
+
+#[allow(clippy::new_without_default)]
+impl<K: Ord, V> LLRBMap<K, V> {
+    pub fn new() -> Self {
+        LLRBMap { tree: BST::new() }
+    }
+
+    // Builds a map from entries in strictly increasing order of key in
+    // linear time.
+    #[cfg(feature = "serde")]
+    pub(crate) fn from_sorted_vec(entries: Vec<(K, V)>) -> Self {
+        LLRBMap { tree: BST::from_sorted_vec(entries.into_iter().map(|(key, value)| MapEntry { key, value }).collect()) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear()
+    }
+
+    pub fn get<Q: ?Sized + Ord>(&self, key: &Q) -> Option<&V> where K: Borrow<Q> {
+        self.tree.find_by(|e| key.cmp(e.key.borrow())).map(|ptr| &self.tree.deref(&ptr).elem.value)
+    }
+
+    pub fn get_mut<Q: ?Sized + Ord>(&mut self, key: &Q) -> Option<&mut V> where K: Borrow<Q> {
+        let ptr = self.tree.find_by(|e| key.cmp(e.key.borrow()))?;
+        Some(&mut self.tree.deref_mut(&ptr).elem.value)
+    }
+
+    pub fn contains_key<Q: ?Sized + Ord>(&self, key: &Q) -> bool where K: Borrow<Q> {
+        self.tree.find_by(|e| key.cmp(e.key.borrow())).is_some()
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(old) = self.get_mut(&key) {
+            return Some(mem::replace(old, value));
+        }
+        self.tree.insert(MapEntry { key, value });
+        None
+    }
+
+    pub fn remove<Q: ?Sized + Ord>(&mut self, key: &Q) -> Option<V> where K: Borrow<Q> {
+        self.tree.delete_by(|e| key.cmp(e.key.borrow())).map(|e| e.value)
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        match self.tree.find_by(|e| key.cmp(&e.key)) {
+            Some(ptr) => Entry::Occupied(OccupiedEntry { tree: &mut self.tree, ptr }),
+            None => Entry::Vacant(VacantEntry { tree: &mut self.tree, key }),
+        }
+    }
+
+    pub fn first_entry(&mut self) -> Option<OccupiedEntry<'_, K, V>> {
+        let ptr = self.tree.min_node()?;
+        Some(OccupiedEntry { tree: &mut self.tree, ptr })
+    }
+
+    pub fn last_entry(&mut self) -> Option<OccupiedEntry<'_, K, V>> {
+        let ptr = self.tree.max_node()?;
+        Some(OccupiedEntry { tree: &mut self.tree, ptr })
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { inner: self.tree.iter() }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.tree.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.tree.iter() }
+    }
+
+    // The nodes are laid out in the arena in no particular order, so the
+    // in-order sequence of slots within the bounds is worked out before
+    // the slots are borrowed mutably. The walk starts from the lower bound,
+    // which makes it logarithmic in the size of the map plus linear in the
+    // number of slots found.
+    fn slot_order<Q: ?Sized + Ord>(&self, start: Bound<&Q>, end: Bound<&Q>) -> Vec<usize> where K: Borrow<Q> {
+        let mut order = Vec::new();
+        let mut stack = Vec::new();
+        let mut node = self.tree.root;
+        loop {
+            while let Some(ptr) = node {
+                let n = self.tree.deref(&ptr);
+                if iter::above_lower(start, n.elem.key.borrow()) {
+                    stack.push(ptr);
+                    node = n.left;
+                } else {
+                    node = n.right;
+                }
+            }
+            match stack.pop() {
+                Some(ptr) if iter::below_upper(end, self.tree.deref(&ptr).elem.key.borrow()) => {
+                    order.push(ptr.index());
+                    node = self.tree.deref(&ptr).right;
+                },
+                _ => return order,
+            }
+        }
+    }
+
+    // Borrows the entries in the given slots mutably, in the order given.
+    fn entries_mut(&mut self, order: Vec<usize>) -> Vec<&mut MapEntry<K, V>> {
+        let base = self.tree.nodes.as_mut_ptr();
+        order.into_iter().map(|index| {
+            // The slots come from a walk of the tree, which visits each
+            // live node once, so the borrows are of distinct nodes, and
+            // nothing else touches the arena while self is borrowed.
+            match unsafe { &mut *base.add(index) } {
+                Some(node) => &mut node.elem,
+                None => panic!("entries_mut encounters a reference to a deleted node"),
+            }
+        }).collect()
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, V> {
+        let order = self.slot_order::<K>(Bound::Unbounded, Bound::Unbounded);
+        let values: Vec<_> = self.entries_mut(order).into_iter().map(|entry| &mut entry.value).collect();
+        ValuesMut { inner: values.into_iter() }
+    }
+
+    pub fn range_mut<Q: ?Sized + Ord, R: RangeBounds<Q>>(&mut self, range: R) -> RangeMut<'_, K, V> where K: Borrow<Q> {
+        check_range_bounds(&range);
+        let order = self.slot_order(range.start_bound(), range.end_bound());
+        let entries: Vec<_> = self.entries_mut(order).into_iter().map(|entry| (&entry.key, &mut entry.value)).collect();
+        RangeMut { inner: entries.into_iter() }
+    }
+
+    // Reports the keys found in only one of the maps and the keys whose
+    // values differ, in key order.
+    pub fn diff<'a>(&'a self, other: &'a LLRBMap<K, V>) -> Diff<'a, K, V> where V: PartialEq {
+        Diff { a: self.tree.iter().peekable(), b: other.tree.iter().peekable() }
+    }
+
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        IntoKeys { inner: self.tree.into_iter() }
+    }
+
+    pub fn into_values(self) -> IntoValues<K, V> {
+        IntoValues { inner: self.tree.into_iter() }
+    }
+}
+
+pub enum Entry<'a, K, V> {
+    Vacant(VacantEntry<'a, K, V>),
+    Occupied(OccupiedEntry<'a, K, V>),
+}
+
+pub struct VacantEntry<'a, K, V> {
+    tree: &'a mut BST<MapEntry<K, V>>,
+    key: K,
+}
+
+pub struct OccupiedEntry<'a, K, V> {
+    tree: &'a mut BST<MapEntry<K, V>>,
+    ptr: Ptr,
+}
+
+impl<'a, K: Ord, V> Entry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        match *self {
+            Entry::Vacant(ref entry) => entry.key(),
+            Entry::Occupied(ref entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Vacant(entry) => entry.insert(default),
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Vacant(entry) => entry.insert(default()),
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+        }
+    }
+}
+
+impl<'a, K: Ord, V> VacantEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V {
+        let VacantEntry { tree, key } = self;
+        let ptr = tree.insert_absent(MapEntry { key, value });
+        &mut tree.deref_mut(&ptr).elem.value
+    }
+}
+
+impl<'a, K: Ord, V> OccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.tree.deref(&self.ptr).elem.key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.tree.deref(&self.ptr).elem.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.tree.deref_mut(&self.ptr).elem.value
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.tree.deref_mut(&self.ptr).elem.value
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        let entry = self.tree.delete_node(self.ptr);
+        (entry.key, entry.value)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    inner: iter::Iter<'a, MapEntry<K, V>>,
+}
+
+impl<'a, K: Ord, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.inner.next().map(|e| (&e.key, &e.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+pub struct IntoIter<K, V> {
+    inner: iter::IntoIter<MapEntry<K, V>>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        self.inner.next().map(|e| (e.key, e.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K: Ord, V> IntoIterator for LLRBMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter { inner: self.tree.into_iter() }
+    }
+}
+
+pub struct Keys<'a, K, V> {
+    inner: iter::Iter<'a, MapEntry<K, V>>,
+}
+
+impl<'a, K: Ord, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.inner.next().map(|e| &e.key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for Keys<'a, K, V> {}
+
+pub struct Values<'a, K, V> {
+    inner: iter::Iter<'a, MapEntry<K, V>>,
+}
+
+impl<'a, K: Ord, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(|e| &e.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for Values<'a, K, V> {}
+
+pub struct ValuesMut<'a, V: 'a> {
+    inner: vec::IntoIter<&'a mut V>,
+}
+
+impl<'a, V> Iterator for ValuesMut<'a, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, V> ExactSizeIterator for ValuesMut<'a, V> {}
+
+pub struct RangeMut<'a, K: 'a, V: 'a> {
+    inner: vec::IntoIter<(&'a K, &'a mut V)>,
+}
+
+impl<'a, K, V> Iterator for RangeMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for RangeMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a mut V)> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for RangeMut<'a, K, V> {}
+
+pub struct Diff<'a, K: 'a, V: 'a> {
+    a: Peekable<iter::Iter<'a, MapEntry<K, V>>>,
+    b: Peekable<iter::Iter<'a, MapEntry<K, V>>>,
+}
+
+impl<'a, K: Ord, V: PartialEq> Iterator for Diff<'a, K, V> {
+    type Item = DiffEntry<(&'a K, &'a V)>;
+
+    fn next(&mut self) -> Option<DiffEntry<(&'a K, &'a V)>> {
+        loop {
+            let order = match (self.a.peek(), self.b.peek()) {
+                (Some(x), Some(y)) => x.cmp(y),
+                (Some(_), None) => Ordering::Less,
+                (None, _) => Ordering::Greater,
+            };
+            match order {
+                Ordering::Less => return self.a.next().map(|e| DiffEntry::OnlyLeft((&e.key, &e.value))),
+                Ordering::Greater => return self.b.next().map(|e| DiffEntry::OnlyRight((&e.key, &e.value))),
+                Ordering::Equal => {
+                    let (x, y) = (self.a.next().unwrap(), self.b.next().unwrap());
+                    if x.value != y.value {
+                        return Some(DiffEntry::Changed((&x.key, &x.value), (&y.key, &y.value)));
+                    }
+                },
+            }
+        }
+    }
+}
+
+pub struct IntoKeys<K, V> {
+    inner: iter::IntoIter<MapEntry<K, V>>,
+}
+
+impl<K, V> Iterator for IntoKeys<K, V> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        self.inner.next().map(|e| e.key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoKeys<K, V> {}
+
+pub struct IntoValues<K, V> {
+    inner: iter::IntoIter<MapEntry<K, V>>,
+}
+
+impl<K, V> Iterator for IntoValues<K, V> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        self.inner.next().map(|e| e.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoValues<K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    use super::LLRBMap;
+    use DiffEntry;
+
+    #[test]
+    fn basics() {
+        let mut map: LLRBMap<String, i32> = LLRBMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.get("a"), None);
+
+        assert_eq!(map.insert("b".to_string(), 2), None);
+        assert_eq!(map.insert("a".to_string(), 1), None);
+        assert_eq!(map.insert("c".to_string(), 3), None);
+        assert_eq!(map.insert("b".to_string(), 20), Some(2));
+        assert_eq!(map.len(), 3);
+
+        assert_eq!(map.get("b"), Some(&20));
+        assert!(map.contains_key("c"));
+        assert!(!map.contains_key("d"));
+        *map.get_mut("a").unwrap() += 10;
+        assert_eq!(map.get("a"), Some(&11));
+
+        assert_eq!(map.remove("c"), Some(3));
+        assert_eq!(map.remove("c"), None);
+        assert_eq!(map.len(), 2);
+
+        assert_eq!(map.iter().collect::<Vec<_>>(), vec![(&"a".to_string(), &11), (&"b".to_string(), &20)]);
+        assert_eq!(map.into_iter().collect::<Vec<_>>(), vec![("a".to_string(), 11), ("b".to_string(), 20)]);
+    }
+
+    #[test]
+    fn many_entries() {
+        let mut map: LLRBMap<i32, i32> = LLRBMap::new();
+        for i in 0..1000 {
+            map.insert((i * 7) % 1000, i);
+        }
+        for i in 0..1000 {
+            assert_eq!(map.get(&((i * 7) % 1000)), Some(&i));
+        }
+        for i in 0..500 {
+            assert_eq!(map.remove(&(i * 2)), Some((i * 2 * 143) % 1000));
+        }
+        assert_eq!(map.len(), 500);
+        assert!(map.iter().map(|(k, _)| *k).eq((0..500).map(|i| i * 2 + 1)));
+    }
+
+    #[test]
+    fn entries() {
+        use super::Entry;
+
+        let mut counts: LLRBMap<char, usize> = LLRBMap::new();
+        for c in "mississippi".chars() {
+            *counts.entry(c).or_insert(0) += 1;
+        }
+        assert_eq!(counts.into_iter().collect::<Vec<_>>(), vec![('i', 4), ('m', 1), ('p', 2), ('s', 4)]);
+
+        let mut map: LLRBMap<i32, String> = LLRBMap::new();
+        map.entry(1).or_insert_with(|| "one".to_string());
+        map.entry(1).or_insert_with(|| unreachable!());
+        map.entry(1).and_modify(|v| v.push('!')).or_insert_with(|| unreachable!());
+        map.entry(2).and_modify(|_| unreachable!()).or_insert_with(|| "two".to_string());
+        assert_eq!(map.get(&1).map(String::as_str), Some("one!"));
+        assert_eq!(map.get(&2).map(String::as_str), Some("two"));
+        assert_eq!(*map.entry(3).key(), 3);
+
+        match map.entry(1) {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), &1);
+                assert_eq!(entry.insert("uno".to_string()), "one!");
+                assert_eq!(entry.get(), "uno");
+                assert_eq!(entry.remove(), "uno");
+            },
+            Entry::Vacant(_) => panic!("entry 1 should be occupied"),
+        }
+        assert!(!map.contains_key(&1));
+        match map.entry(1) {
+            Entry::Occupied(_) => panic!("entry 1 should be vacant"),
+            Entry::Vacant(entry) => assert_eq!(entry.into_key(), 1),
+        }
+
+        let mut map: LLRBMap<i32, i32> = LLRBMap::new();
+        for i in 0..200 {
+            *map.entry(i % 37).or_insert(0) += 1;
+        }
+        for i in 0..37 {
+            assert_eq!(map.get(&i), Some(&if i < 200 % 37 { 6 } else { 5 }));
+        }
+    }
+
+    #[test]
+    fn projections() {
+        let mut map: LLRBMap<i32, String> = LLRBMap::new();
+        for i in (0..10).rev() {
+            map.insert(i, i.to_string());
+        }
+        assert!(map.keys().cloned().eq(0..10));
+        assert_eq!(map.values().map(String::as_str).collect::<String>(), "0123456789");
+        assert_eq!(map.keys().len(), 10);
+
+        for (i, value) in map.values_mut().enumerate() {
+            value.push_str(&"!".repeat(i));
+        }
+        assert_eq!(map.get(&3).map(String::as_str), Some("3!!!"));
+        map.remove(&3);
+        assert_eq!(map.values_mut().len(), 9);
+        assert_eq!(map.values_mut().count(), 9);
+
+        assert!(map.clone().into_keys().eq((0..10).filter(|&i| i != 3)));
+        assert_eq!(map.into_values().next(), Some("0".to_string()));
+    }
+
+    #[test]
+    fn extreme_entries() {
+        let mut map = LLRBMap::new();
+        assert!(map.first_entry().is_none());
+        for (i, job) in ["c", "a", "d", "b"].iter().enumerate() {
+            map.insert(i as u32 * 10 % 7, *job);
+        }
+        // Keys are 0, 3, 6 and 2.
+        *map.first_entry().unwrap().get_mut() = "A";
+        assert_eq!(map.get(&0), Some(&"A"));
+        assert_eq!(map.last_entry().unwrap().key(), &6);
+        let mut order = Vec::new();
+        while let Some(entry) = map.first_entry() {
+            order.push(entry.remove_entry());
+        }
+        assert_eq!(order, vec![(0, "A"), (2, "b"), (3, "a"), (6, "d")]);
+        assert!(map.is_empty());
+
+        map.insert(1, "x");
+        map.insert(2, "y");
+        assert_eq!(map.last_entry().unwrap().remove(), "y");
+        assert_eq!(map.iter().collect::<Vec<_>>(), vec![(&1, &"x")]);
+    }
+
+    #[test]
+    fn diffing() {
+        let mut before = LLRBMap::new();
+        for &(k, v) in &[(1, "a"), (2, "b"), (3, "c"), (5, "e")] {
+            before.insert(k, v);
+        }
+        let mut after = before.clone();
+        after.remove(&1);
+        after.insert(3, "C");
+        after.insert(4, "d");
+        assert_eq!(before.diff(&after).collect::<Vec<_>>(), vec![
+            DiffEntry::OnlyLeft((&1, &"a")),
+            DiffEntry::Changed((&3, &"c"), (&3, &"C")),
+            DiffEntry::OnlyRight((&4, &"d")),
+        ]);
+        assert_eq!(before.diff(&before).next(), None);
+    }
+
+    #[test]
+    fn mutable_range() {
+        let mut map = LLRBMap::new();
+        for i in 0..50 {
+            map.insert(i * 2, 0);
+        }
+        for (_, count) in map.range_mut(10..20) {
+            *count += 1;
+        }
+        for (_, count) in map.range_mut(15..=30).rev() {
+            *count += 10;
+        }
+        assert_eq!(map.range_mut(10..20).len(), 5);
+        assert_eq!(map.range_mut(11..12).next(), None);
+        assert_eq!(map.range_mut(95..).map(|(&k, _)| k).collect::<Vec<_>>(), vec![96, 98]);
+        let counts: Vec<_> = map.iter().filter(|&(_, &v)| v > 0).map(|(&k, &v)| (k, v)).collect();
+        assert_eq!(counts, vec![(10, 1), (12, 1), (14, 1), (16, 11), (18, 11), (20, 10), (22, 10), (24, 10), (26, 10), (28, 10), (30, 10)]);
+    }
+}
@@ -0,0 +1,249 @@
+use core::cmp::{self, Ordering};
+use core::iter::Peekable;
+
+use allocator_api2::alloc::{Allocator, Global};
+
+use super::{BST, NodeIndex};
+use iter::Iter;
+
+// Lazy set operations that merge the in-order streams of two trees.
+
+pub struct Union<'a, T: 'a, A: Allocator + 'a = Global, I: NodeIndex + 'a = u32> {
+    a: Peekable<Iter<'a, T, A, I>>,
+    b: Peekable<Iter<'a, T, A, I>>,
+}
+
+pub struct Intersection<'a, T: 'a, A: Allocator + 'a = Global, I: NodeIndex + 'a = u32> {
+    a: Peekable<Iter<'a, T, A, I>>,
+    b: Peekable<Iter<'a, T, A, I>>,
+}
+
+pub struct Difference<'a, T: 'a, A: Allocator + 'a = Global, I: NodeIndex + 'a = u32> {
+    a: Peekable<Iter<'a, T, A, I>>,
+    b: Peekable<Iter<'a, T, A, I>>,
+}
+
+pub struct SymmetricDifference<'a, T: 'a, A: Allocator + 'a = Global, I: NodeIndex + 'a = u32> {
+    a: Peekable<Iter<'a, T, A, I>>,
+    b: Peekable<Iter<'a, T, A, I>>,
+}
+
+// What a merge yields for an element present in both trees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    // Both elements, the one from the first tree first.
+    Both,
+    First,
+    Second,
+}
+
+// An element of a diff between two collections. Changed only arises for
+// maps, whose entries can share a key but differ in value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffEntry<T> {
+    OnlyLeft(T),
+    OnlyRight(T),
+    Changed(T, T),
+}
+
+pub struct Diff<'a, T: 'a, A: Allocator + 'a = Global, I: NodeIndex + 'a = u32> {
+    a: Peekable<Iter<'a, T, A, I>>,
+    b: Peekable<Iter<'a, T, A, I>>,
+}
+
+pub struct Merge<'a, T: 'a, A: Allocator + 'a = Global, I: NodeIndex + 'a = u32> {
+    a: Peekable<Iter<'a, T, A, I>>,
+    b: Peekable<Iter<'a, T, A, I>>,
+    policy: MergePolicy,
+}
+
+impl<T: Ord, A: Allocator, I: NodeIndex> BST<T, A, I> {
+    pub fn union_iter<'a>(&'a self, other: &'a BST<T, A, I>) -> Union<'a, T, A, I> {
+        Union { a: self.iter().peekable(), b: other.iter().peekable() }
+    }
+
+    pub fn intersection_iter<'a>(&'a self, other: &'a BST<T, A, I>) -> Intersection<'a, T, A, I> {
+        Intersection { a: self.iter().peekable(), b: other.iter().peekable() }
+    }
+
+    pub fn difference_iter<'a>(&'a self, other: &'a BST<T, A, I>) -> Difference<'a, T, A, I> {
+        Difference { a: self.iter().peekable(), b: other.iter().peekable() }
+    }
+
+    pub fn symmetric_difference_iter<'a>(&'a self, other: &'a BST<T, A, I>) -> SymmetricDifference<'a, T, A, I> {
+        SymmetricDifference { a: self.iter().peekable(), b: other.iter().peekable() }
+    }
+
+    // Reports the elements found in only one of the trees, in order.
+    pub fn diff<'a>(&'a self, other: &'a BST<T, A, I>) -> Diff<'a, T, A, I> {
+        Diff { a: self.iter().peekable(), b: other.iter().peekable() }
+    }
+
+    pub fn merge_iter<'a>(&'a self, other: &'a BST<T, A, I>, policy: MergePolicy) -> Merge<'a, T, A, I> {
+        Merge { a: self.iter().peekable(), b: other.iter().peekable(), policy }
+    }
+}
+
+impl<T: Ord + Clone> BST<T> {
+    pub fn symmetric_difference(&self, other: &BST<T>) -> BST<T> {
+        BST::from_sorted_iter(self.symmetric_difference_iter(other).cloned())
+    }
+}
+
+impl<'a, T: Ord, A: Allocator, I: NodeIndex> Iterator for Union<'a, T, A, I> {
+    type Item = &'a T;
+
+    // Elements present in both trees are taken from the first one.
+    fn next(&mut self) -> Option<&'a T> {
+        let order = match (self.a.peek(), self.b.peek()) {
+            (Some(x), Some(y)) => x.cmp(y),
+            (Some(_), None) => Ordering::Less,
+            (None, _) => Ordering::Greater,
+        };
+        match order {
+            Ordering::Less => self.a.next(),
+            Ordering::Greater => self.b.next(),
+            Ordering::Equal => {
+                self.b.next();
+                self.a.next()
+            },
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a, b) = (self.a.len(), self.b.len());
+        (cmp::max(a, b), Some(a + b))
+    }
+}
+
+impl<'a, T: Ord, A: Allocator, I: NodeIndex> Iterator for Intersection<'a, T, A, I> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            match self.a.peek()?.cmp(self.b.peek()?) {
+                Ordering::Less => { self.a.next(); },
+                Ordering::Greater => { self.b.next(); },
+                Ordering::Equal => {
+                    self.b.next();
+                    return self.a.next();
+                },
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(cmp::min(self.a.len(), self.b.len())))
+    }
+}
+
+impl<'a, T: Ord, A: Allocator, I: NodeIndex> Iterator for Difference<'a, T, A, I> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            let order = match self.b.peek() {
+                None => return self.a.next(),
+                Some(y) => self.a.peek()?.cmp(y),
+            };
+            match order {
+                Ordering::Less => return self.a.next(),
+                Ordering::Greater => { self.b.next(); },
+                Ordering::Equal => {
+                    self.a.next();
+                    self.b.next();
+                },
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a, b) = (self.a.len(), self.b.len());
+        (a.saturating_sub(b), Some(a))
+    }
+}
+
+impl<'a, T: Ord, A: Allocator, I: NodeIndex> Iterator for SymmetricDifference<'a, T, A, I> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            let order = match (self.a.peek(), self.b.peek()) {
+                (Some(x), Some(y)) => x.cmp(y),
+                (Some(_), None) => Ordering::Less,
+                (None, _) => Ordering::Greater,
+            };
+            match order {
+                Ordering::Less => return self.a.next(),
+                Ordering::Greater => return self.b.next(),
+                Ordering::Equal => {
+                    self.a.next();
+                    self.b.next();
+                },
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.a.len() + self.b.len()))
+    }
+}
+
+impl<'a, T: Ord, A: Allocator, I: NodeIndex> Iterator for Merge<'a, T, A, I> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let order = match (self.a.peek(), self.b.peek()) {
+            (Some(x), Some(y)) => x.cmp(y),
+            (Some(_), None) => Ordering::Less,
+            (None, _) => Ordering::Greater,
+        };
+        match (order, self.policy) {
+            (Ordering::Less, _) | (Ordering::Equal, MergePolicy::Both) => self.a.next(),
+            (Ordering::Greater, _) => self.b.next(),
+            (Ordering::Equal, MergePolicy::First) => {
+                self.b.next();
+                self.a.next()
+            },
+            (Ordering::Equal, MergePolicy::Second) => {
+                self.a.next();
+                self.b.next()
+            },
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a, b) = (self.a.len(), self.b.len());
+        match self.policy {
+            MergePolicy::Both => (a + b, Some(a + b)),
+            MergePolicy::First | MergePolicy::Second => (cmp::max(a, b), Some(a + b)),
+        }
+    }
+}
+
+impl<'a, T: Ord, A: Allocator, I: NodeIndex> Iterator for Diff<'a, T, A, I> {
+    type Item = DiffEntry<&'a T>;
+
+    fn next(&mut self) -> Option<DiffEntry<&'a T>> {
+        loop {
+            let order = match (self.a.peek(), self.b.peek()) {
+                (Some(x), Some(y)) => x.cmp(y),
+                (Some(_), None) => Ordering::Less,
+                (None, _) => Ordering::Greater,
+            };
+            match order {
+                Ordering::Less => return self.a.next().map(DiffEntry::OnlyLeft),
+                Ordering::Greater => return self.b.next().map(DiffEntry::OnlyRight),
+                Ordering::Equal => {
+                    self.a.next();
+                    self.b.next();
+                },
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a, b) = (self.a.len(), self.b.len());
+        (a.abs_diff(b), Some(a + b))
+    }
+}
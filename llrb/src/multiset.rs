@@ -0,0 +1,213 @@
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+
+use super::BST;
+use iter;
+
+// Each distinct element is stored once along with its multiplicity, and
+// entries are compared by element alone.
+#[derive(Debug, Clone)]
+struct MultiEntry<T> {
+    elem: T,
+    count: usize,
+}
+
+impl<T: Ord> PartialEq for MultiEntry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.elem == other.elem
+    }
+}
+
+impl<T: Ord> Eq for MultiEntry<T> {}
+
+impl<T: Ord> PartialOrd for MultiEntry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for MultiEntry<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.elem.cmp(&other.elem)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BSTMultiSet<T> {
+    tree: BST<MultiEntry<T>>,
+    // The total number of elements, counting duplicates.
+    len: usize,
+}
+
+#[allow(clippy::new_without_default)]
+impl<T: Ord> BSTMultiSet<T> {
+    pub fn new() -> Self {
+        BSTMultiSet { tree: BST::new(), len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn distinct_len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear();
+        self.len = 0;
+    }
+
+    pub fn count<Q: ?Sized + Ord>(&self, elem: &Q) -> usize where T: Borrow<Q> {
+        self.tree.find_by(|e| elem.cmp(e.elem.borrow())).map_or(0, |ptr| self.tree.deref(&ptr).elem.count)
+    }
+
+    pub fn contains<Q: ?Sized + Ord>(&self, elem: &Q) -> bool where T: Borrow<Q> {
+        self.tree.find_by(|e| elem.cmp(e.elem.borrow())).is_some()
+    }
+
+    // Adds one more copy of the element and returns its new multiplicity.
+    pub fn insert(&mut self, elem: T) -> usize {
+        self.len += 1;
+        match self.tree.find_by(|e| elem.cmp(&e.elem)) {
+            Some(ptr) => {
+                let entry = &mut self.tree.deref_mut(&ptr).elem;
+                entry.count += 1;
+                entry.count
+            },
+            None => {
+                self.tree.insert(MultiEntry { elem, count: 1 });
+                1
+            }
+        }
+    }
+
+    // Removes one copy of the element and returns whether there was one.
+    pub fn remove_one<Q: ?Sized + Ord>(&mut self, elem: &Q) -> bool where T: Borrow<Q> {
+        match self.tree.find_by(|e| elem.cmp(e.elem.borrow())) {
+            None => false,
+            Some(ptr) => {
+                if self.tree.deref(&ptr).elem.count == 1 {
+                    self.tree.delete_by(|e| elem.cmp(e.elem.borrow()));
+                } else {
+                    self.tree.deref_mut(&ptr).elem.count -= 1;
+                }
+                self.len -= 1;
+                true
+            }
+        }
+    }
+
+    // Removes every copy of the element and returns how many there were.
+    pub fn remove_all<Q: ?Sized + Ord>(&mut self, elem: &Q) -> usize where T: Borrow<Q> {
+        let count = self.tree.delete_by(|e| elem.cmp(e.elem.borrow())).map_or(0, |e| e.count);
+        self.len -= count;
+        count
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { inner: self.tree.iter(), current: None, remaining: self.len }
+    }
+
+    // Iterates over the distinct elements along with their multiplicities.
+    pub fn iter_counts(&self) -> IterCounts<'_, T> {
+        IterCounts { inner: self.tree.iter() }
+    }
+}
+
+pub struct Iter<'a, T: 'a> {
+    inner: iter::Iter<'a, MultiEntry<T>>,
+    // The element being repeated and the number of copies still to yield.
+    current: Option<(&'a T, usize)>,
+    remaining: usize,
+}
+
+impl<'a, T: Ord> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let (elem, left) = match self.current {
+            Some((elem, left)) if left > 0 => (elem, left),
+            _ => {
+                let entry = self.inner.next()?;
+                (&entry.elem, entry.count)
+            }
+        };
+        self.current = Some((elem, left - 1));
+        self.remaining -= 1;
+        Some(elem)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T: Ord> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T: Ord> IntoIterator for &'a BSTMultiSet<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+pub struct IterCounts<'a, T: 'a> {
+    inner: iter::Iter<'a, MultiEntry<T>>,
+}
+
+impl<'a, T: Ord> Iterator for IterCounts<'a, T> {
+    type Item = (&'a T, usize);
+
+    fn next(&mut self) -> Option<(&'a T, usize)> {
+        self.inner.next().map(|e| (&e.elem, e.count))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T: Ord> ExactSizeIterator for IterCounts<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::BSTMultiSet;
+
+    #[test]
+    fn counting() {
+        let mut set = BSTMultiSet::new();
+        for c in "mississippi".chars() {
+            set.insert(c);
+        }
+        assert_eq!(set.len(), 11);
+        assert_eq!(set.distinct_len(), 4);
+        assert_eq!(set.count(&'s'), 4);
+        assert_eq!(set.count(&'x'), 0);
+        assert!(set.contains(&'m'));
+        assert_eq!(set.iter().collect::<std::string::String>(), "iiiimppssss");
+        assert_eq!(set.iter().len(), 11);
+        assert_eq!(set.iter_counts().collect::<Vec<_>>(), vec![(&'i', 4), (&'m', 1), (&'p', 2), (&'s', 4)]);
+
+        assert!(set.remove_one(&'m'));
+        assert!(!set.remove_one(&'m'));
+        assert!(set.remove_one(&'p'));
+        assert_eq!(set.count(&'p'), 1);
+        assert_eq!(set.remove_all(&'s'), 4);
+        assert_eq!(set.remove_all(&'s'), 0);
+        assert_eq!(set.len(), 5);
+        assert_eq!((&set).into_iter().collect::<std::string::String>(), "iiiip");
+
+        set.clear();
+        assert!(set.is_empty());
+        assert_eq!(set.iter().next(), None);
+    }
+}
@@ -0,0 +1,67 @@
+use rayon::iter::plumbing::{bridge_unindexed, Folder, UnindexedConsumer, UnindexedProducer};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use super::{BST, Ptr};
+use iter::Iter;
+
+pub struct ParIter<'a, T: 'a> {
+    tree: &'a BST<T>,
+}
+
+impl<T: Ord + Sync> BST<T> {
+    pub fn par_iter(&self) -> ParIter<'_, T> {
+        ParIter { tree: self }
+    }
+}
+
+impl<'a, T: Ord + Sync> IntoParallelIterator for &'a BST<T> {
+    type Item = &'a T;
+    type Iter = ParIter<'a, T>;
+
+    fn into_par_iter(self) -> ParIter<'a, T> {
+        self.par_iter()
+    }
+}
+
+impl<'a, T: Ord + Sync> ParallelIterator for ParIter<'a, T> {
+    type Item = &'a T;
+
+    fn drive_unindexed<C: UnindexedConsumer<&'a T>>(self, consumer: C) -> C::Result {
+        bridge_unindexed(Producer { tree: self.tree, head: None, subtree: self.tree.root }, consumer)
+    }
+}
+
+// A producer yields the element at head, if any, followed by the whole
+// subtree. Splitting at the root of the subtree keeps both halves in that
+// shape: the left half is head plus the left subtree, and the right half is
+// the root followed by the right subtree.
+struct Producer<'a, T: 'a> {
+    tree: &'a BST<T>,
+    head: Option<Ptr>,
+    subtree: Option<Ptr>,
+}
+
+impl<'a, T: Ord + Sync> UnindexedProducer for Producer<'a, T> {
+    type Item = &'a T;
+
+    fn split(self) -> (Self, Option<Self>) {
+        let ptr = match self.subtree {
+            Some(ptr) => ptr,
+            None => return (self, None),
+        };
+        let node = self.tree.deref(&ptr);
+        if node.left.is_none() && node.right.is_none() {
+            return (self, None);
+        }
+        let left = Producer { tree: self.tree, head: self.head, subtree: node.left };
+        let right = Producer { tree: self.tree, head: Some(ptr), subtree: node.right };
+        (left, Some(right))
+    }
+
+    fn fold_with<F: Folder<&'a T>>(self, mut folder: F) -> F {
+        if let Some(ptr) = self.head {
+            folder = folder.consume(&self.tree.deref(&ptr).elem);
+        }
+        folder.consume_iter(Iter::subtree(self.tree, self.subtree))
+    }
+}
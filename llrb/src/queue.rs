@@ -0,0 +1,184 @@
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+
+use super::BST;
+use iter;
+
+// A priority queue with access to both ends and removal of arbitrary
+// elements. Each element is kept alongside the order in which it was
+// pushed, so equal elements are all retained and break ties by age:
+// pop_min returns the oldest of them, and pop_max the newest.
+#[derive(Debug, Clone)]
+pub struct TreePriorityQueue<T> {
+    tree: BST<(T, u64)>,
+    pushed: u64,
+}
+
+#[allow(clippy::new_without_default)]
+impl<T: Ord> TreePriorityQueue<T> {
+    pub fn new() -> Self {
+        TreePriorityQueue { tree: BST::new(), pushed: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear();
+    }
+
+    pub fn push(&mut self, elem: T) {
+        self.tree.insert_absent((elem, self.pushed));
+        self.pushed += 1;
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        self.tree.first().map(|entry| &entry.0)
+    }
+
+    pub fn peek_max(&self) -> Option<&T> {
+        self.tree.last().map(|entry| &entry.0)
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        self.tree.pop_first().map(|entry| entry.0)
+    }
+
+    pub fn pop_max(&mut self) -> Option<T> {
+        self.tree.pop_last().map(|entry| entry.0)
+    }
+
+    pub fn contains<Q: ?Sized + Ord>(&self, elem: &Q) -> bool where T: Borrow<Q> {
+        self.tree.find_by(|entry| elem.cmp(entry.0.borrow())).is_some()
+    }
+
+    // Removes the earliest pushed of the elements equal to the given one.
+    pub fn remove<Q: ?Sized + Ord>(&mut self, elem: &Q) -> Option<T> where T: Borrow<Q> {
+        let mut node = self.tree.root;
+        let mut found = None;
+        while let Some(ptr) = node {
+            let n = self.tree.deref(&ptr);
+            match elem.cmp(n.elem.0.borrow()) {
+                Ordering::Less => node = n.left,
+                Ordering::Greater => node = n.right,
+                Ordering::Equal => {
+                    found = Some(ptr);
+                    node = n.left;
+                },
+            }
+        }
+        found.map(|ptr| self.tree.delete_node(ptr).0)
+    }
+
+    // Iterates from the smallest element to the largest.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { inner: self.tree.iter() }
+    }
+}
+
+pub struct Iter<'a, T: 'a> {
+    inner: iter::Iter<'a, (T, u64)>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next().map(|entry| &entry.0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.inner.next_back().map(|entry| &entry.0)
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<T: Ord> Extend<T> for TreePriorityQueue<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::borrow::Borrow;
+    use core::cmp::Ordering;
+    use std::vec::Vec;
+
+    use super::TreePriorityQueue;
+
+    #[test]
+    fn both_ends() {
+        let mut queue = TreePriorityQueue::new();
+        queue.extend(vec![5, 1, 4, 1, 5, 9, 2, 6]);
+        assert_eq!(queue.len(), 8);
+        assert_eq!((queue.peek_min(), queue.peek_max()), (Some(&1), Some(&9)));
+        assert!(queue.iter().cloned().eq(vec![1, 1, 2, 4, 5, 5, 6, 9]));
+
+        assert_eq!(queue.remove(&5), Some(5));
+        assert_eq!(queue.remove(&3), None);
+        assert!(queue.contains(&5));
+        assert_eq!(queue.pop_min(), Some(1));
+        assert_eq!(queue.pop_max(), Some(9));
+        let mut rest = Vec::new();
+        while let Some(x) = queue.pop_min() {
+            rest.push(x);
+        }
+        assert_eq!(rest, vec![1, 2, 4, 5, 6]);
+        assert!(queue.is_empty());
+
+        // Ties between equal elements are broken by age.
+        let mut jobs = TreePriorityQueue::new();
+        for &(priority, name) in &[(1, "a"), (0, "b"), (1, "c"), (0, "d"), (1, "e")] {
+            jobs.push(Job(priority, name));
+        }
+        assert_eq!(jobs.remove(&0).map(|job| job.1), Some("b"));
+        assert_eq!(jobs.pop_min().map(|job| job.1), Some("d"));
+        assert_eq!(jobs.pop_min().map(|job| job.1), Some("a"));
+        assert_eq!(jobs.pop_max().map(|job| job.1), Some("e"));
+        assert_eq!(jobs.len(), 1);
+    }
+
+    // Ordered by priority alone.
+    struct Job(u32, &'static str);
+
+    impl PartialEq for Job {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for Job {}
+
+    impl PartialOrd for Job {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Job {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+
+    impl Borrow<u32> for Job {
+        fn borrow(&self) -> &u32 {
+            &self.0
+        }
+    }
+}
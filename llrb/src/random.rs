@@ -0,0 +1,13 @@
+use rand::Rng;
+
+use super::BST;
+
+impl<T: Ord> BST<T> {
+    // Picks an element uniformly at random by selecting a random rank.
+    pub fn choose<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<&T> {
+        if self.is_empty() {
+            return None;
+        }
+        self.select(rng.gen_range(0..self.len()))
+    }
+}
@@ -0,0 +1,273 @@
+use core::fmt::{self, Write};
+
+use alloc::string::{String, ToString};
+
+use allocator_api2::alloc::Allocator;
+
+use super::{BST, Color, NodeIndex, Ptr};
+use iter::Iter;
+
+fn escape_label(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Mermaid has no backslash escapes inside quoted labels, only HTML-like
+// entity codes, which themselves start with '#'.
+fn escape_mermaid_label(label: &str) -> String {
+    label.replace('#', "#35;").replace('"', "#quot;")
+}
+
+// What each node of a TikZ picture is labelled with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TikzLabel {
+    // The element, formatted with Display.
+    Value,
+    // The arena slot of the node, which reflects the order of insertion.
+    Slot,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TikzOptions {
+    label: TikzLabel,
+    // A TikZ dimension such as "8mm".
+    node_size: Option<String>,
+    red: (u8, u8, u8),
+    black: (u8, u8, u8),
+    // Nodes below this depth, where the root is at depth 0, are replaced
+    // by an ellipsis.
+    max_depth: Option<usize>,
+}
+
+impl TikzOptions {
+    pub fn new() -> Self {
+        TikzOptions { label: TikzLabel::Value, node_size: None, red: (171, 50, 37), black: (0, 0, 0), max_depth: None }
+    }
+
+    pub fn label(mut self, label: TikzLabel) -> Self {
+        self.label = label;
+        self
+    }
+
+    pub fn node_size(mut self, size: &str) -> Self {
+        self.node_size = Some(size.to_string());
+        self
+    }
+
+    pub fn red(mut self, rgb: (u8, u8, u8)) -> Self {
+        self.red = rgb;
+        self
+    }
+
+    pub fn black(mut self, rgb: (u8, u8, u8)) -> Self {
+        self.black = rgb;
+        self
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+}
+
+impl Default for TikzOptions {
+    fn default() -> Self {
+        TikzOptions::new()
+    }
+}
+
+impl<T: Ord + fmt::Display> BST<T> {
+    fn write_dot_inner<W: Write>(&self, w: &mut W, ptr: Ptr) -> fmt::Result {
+        let node = self.deref(&ptr);
+        let fill = match node.color {
+            Color::Red => "red",
+            Color::Black => "black",
+        };
+        writeln!(w, "    n{} [label=\"{}\", fillcolor={}];", ptr.index(), escape_label(&node.elem.to_string()), fill)?;
+        for child in node.left.iter().chain(node.right.iter()) {
+            write!(w, "    n{} -> n{}", ptr.index(), child.index())?;
+            if let Color::Red = self.deref(child).color {
+                write!(w, " [color=red]")?;
+            }
+            writeln!(w, ";")?;
+            self.write_dot_inner(w, *child)?;
+        }
+        Ok(())
+    }
+
+    fn write_mermaid_inner<W: Write>(&self, w: &mut W, ptr: Ptr) -> fmt::Result {
+        let node = self.deref(&ptr);
+        let class = match node.color {
+            Color::Red => "red",
+            Color::Black => "black",
+        };
+        writeln!(w, "    n{}((\"{}\")):::{}", ptr.index(), escape_mermaid_label(&node.elem.to_string()), class)?;
+        for child in node.left.iter().chain(node.right.iter()) {
+            // Red links are drawn thick.
+            let arrow = match self.deref(child).color {
+                Color::Red => "==>",
+                Color::Black => "-->",
+            };
+            writeln!(w, "    n{} {} n{}", ptr.index(), arrow, child.index())?;
+            self.write_mermaid_inner(w, *child)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_tikz<W: Write>(&self, w: &mut W, options: &TikzOptions) -> fmt::Result {
+        match options.label {
+            TikzLabel::Value => self.write_tikz_labelled(w, options, &|w: &mut W, ptr: Ptr| write!(w, "{{{}}}", self.deref(&ptr).elem)),
+            TikzLabel::Slot => self.write_tikz_slots(w, options),
+        }
+    }
+
+    // Writes the tree as a TikZ picture with the default options, which
+    // label nodes with their elements.
+    pub fn write_structure<W: Write>(&self, w: &mut W) -> fmt::Result {
+        self.write_tikz(w, &TikzOptions::new())
+    }
+
+    pub fn to_tikz_string(&self) -> String {
+        let mut s = String::new();
+        self.write_structure(&mut s).expect("writing to a String cannot fail");
+        s
+    }
+
+    pub fn to_tikz_string_with(&self, options: &TikzOptions) -> String {
+        let mut s = String::new();
+        self.write_tikz(&mut s, options).expect("writing to a String cannot fail");
+        s
+    }
+
+    #[cfg(any(feature = "std", test))]
+    pub fn print_structure(&self) {
+        std::print!("{}", self.to_tikz_string());
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut s = String::new();
+        s.push_str("digraph {\n    node [shape=circle, style=filled, fontcolor=white];\n");
+        if let Some(root) = self.root {
+            self.write_dot_inner(&mut s, root).expect("writing to a String cannot fail");
+        }
+        s.push_str("}\n");
+        s
+    }
+
+    // Renders the tree as a Mermaid flowchart, which can be embedded in
+    // Markdown that supports Mermaid blocks.
+    pub fn to_mermaid(&self) -> String {
+        let mut s = String::new();
+        s.push_str("graph TD\n    classDef red fill:#d00,stroke:#d00,color:#fff\n    classDef black fill:#000,stroke:#000,color:#fff\n");
+        if let Some(root) = self.root {
+            self.write_mermaid_inner(&mut s, root).expect("writing to a String cannot fail");
+        }
+        s
+    }
+}
+
+// The shape of the tree can be drawn for any elements, labelled with the
+// arena slots.
+impl<T: Ord> BST<T> {
+    // Writes both child slots of the node, whose children are at the given
+    // depth.
+    fn write_tikz_children<W: Write, L: Fn(&mut W, Ptr) -> fmt::Result>(&self, w: &mut W, ptr: Ptr, depth: usize, options: &TikzOptions, label: &L) -> fmt::Result {
+        let node = self.deref(&ptr);
+        for &child in &[node.left, node.right] {
+            write!(w, " child ")?;
+            let child = match child {
+                None => {
+                    write!(w, "[missing]")?;
+                    continue;
+                },
+                Some(child) => child,
+            };
+            if options.max_depth.is_some_and(|max| depth > max) {
+                write!(w, "{{ node [draw=none] {{\\ldots}} }}")?;
+                continue;
+            }
+            write!(w, "{{ node ")?;
+            let red = self.deref(&child).color == Color::Red;
+            if red {
+                write!(w, "[draw=red]")?;
+            }
+            label(w, child)?;
+            write!(w, " ")?;
+            if red {
+                write!(w, "edge from parent[red]")?;
+            }
+            self.write_tikz_children(w, child, depth + 1, options, label)?;
+            write!(w, " }}")?;
+        }
+        Ok(())
+    }
+
+    // Draws the shape of the tree with each node labelled by its arena
+    // slot, whatever the label option says, so that the elements need not
+    // be Display.
+    pub fn write_tikz_slots<W: Write>(&self, w: &mut W, options: &TikzOptions) -> fmt::Result {
+        self.write_tikz_labelled(w, options, &|w: &mut W, ptr: Ptr| write!(w, "{{{}}}", ptr.index()))
+    }
+
+    pub fn to_tikz_slots_string(&self, options: &TikzOptions) -> String {
+        let mut s = String::new();
+        self.write_tikz_slots(&mut s, options).expect("writing to a String cannot fail");
+        s
+    }
+
+    fn write_tikz_labelled<W: Write, L: Fn(&mut W, Ptr) -> fmt::Result>(&self, w: &mut W, options: &TikzOptions, label: &L) -> fmt::Result {
+        let root = match self.root {
+            None => return Ok(()),
+            Some(root) => root,
+        };
+        let (r, g, b) = options.red;
+        let (k, l, m) = options.black;
+        writeln!(w, "%% Put these in your preamble\n\
+                     \\usepackage{{tikz}}\n\
+                     \\usetikzlibrary{{graphdrawing}}\n\
+                     \\usegdlibrary{{trees}}\n\
+                     \\definecolor{{red}}{{RGB}}{{{},{},{}}}\n\
+                     \\definecolor{{treeblack}}{{RGB}}{{{},{},{}}}\n\n\
+                     %% Put these in the document body", r, g, b, k, l, m)?;
+        write!(w, "\\tikz [binary tree layout, nodes={{draw=treeblack,circle")?;
+        if let Some(ref size) = options.node_size {
+            write!(w, ",minimum size={}", size)?;
+        }
+        writeln!(w, "}}, font=\\sffamily, semithick] \\node")?;
+        label(w, root)?;
+        self.write_tikz_children(w, root, 1, options, label)?;
+        writeln!(w, ";")
+    }
+}
+
+// Lists the elements in order like a set. The alternate form, {:#?}, shows
+// the shape instead, as nested nodes with their colors, and a leaf where a
+// child is missing.
+impl<T: fmt::Debug, A: Allocator, I: NodeIndex> fmt::Debug for BST<T, A, I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            Shape(self, self.root).fmt(f)
+        } else {
+            f.debug_set().entries(Iter::new(self)).finish()
+        }
+    }
+}
+
+struct Shape<'a, T: 'a, A: 'a + Allocator, I: NodeIndex>(&'a BST<T, A, I>, Option<Ptr<I>>);
+
+impl<'a, T: fmt::Debug, A: Allocator, I: NodeIndex> fmt::Debug for Shape<'a, T, A, I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Shape(tree, node) = *self;
+        match node {
+            None => f.write_str("Leaf"),
+            Some(ptr) => {
+                let node = tree.deref(&ptr);
+                f.debug_struct("Node")
+                    .field("elem", &node.elem)
+                    .field("color", &node.color)
+                    .field("left", &Shape(tree, node.left))
+                    .field("right", &Shape(tree, node.right))
+                    .finish()
+            },
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use core::fmt;
+use core::marker::PhantomData;
+
+use alloc::vec::Vec;
+
+use serde::de::{Deserialize, Deserializer, Error, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use super::{BST, LLRBMap};
+
+// A tree is written as the sequence of its elements in order, and a map as
+// a map from key to value in order of key, so either can be read back as
+// any other sequence or map. Reading builds the tree bottom up in linear
+// time when the input is sorted. Unsorted input is sorted, and of several
+// equal elements the first is kept, as with From<Vec<T>>; of several
+// entries with equal keys, the last is kept, as with repeated inserts.
+impl<T: Ord + Serialize> Serialize for BST<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<K: Ord + Serialize, V: Serialize> Serialize for LLRBMap<K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self.iter() {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+// Deserializes a tree or map only from input that is strictly increasing,
+// failing on anything out of order or repeated instead of fixing it up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Strict<C>(pub C);
+
+// A size hint from the input is not trusted past this many elements, so
+// that a bogus one cannot make us allocate unboundedly up front.
+const MAX_PREALLOCATION: usize = 4096;
+
+struct SeqVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for SeqVisitor<T> {
+    type Value = Vec<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<T>, A::Error> {
+        let mut elems = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(MAX_PREALLOCATION));
+        while let Some(elem) = seq.next_element()? {
+            elems.push(elem);
+        }
+        Ok(elems)
+    }
+}
+
+struct MapVisitor<K, V>(PhantomData<(K, V)>);
+
+impl<'de, K: Deserialize<'de>, V: Deserialize<'de>> Visitor<'de> for MapVisitor<K, V> {
+    type Value = Vec<(K, V)>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Vec<(K, V)>, A::Error> {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0).min(MAX_PREALLOCATION));
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+impl<'de, T: Ord + Deserialize<'de>> Deserialize<'de> for BST<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(SeqVisitor(PhantomData)).map(BST::from)
+    }
+}
+
+impl<'de, T: Ord + Deserialize<'de>> Deserialize<'de> for Strict<BST<T>> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let elems: Vec<T> = deserializer.deserialize_seq(SeqVisitor(PhantomData))?;
+        if !elems.windows(2).all(|w| w[0] < w[1]) {
+            return Err(D::Error::custom("elements are not strictly increasing"));
+        }
+        Ok(Strict(BST::from_sorted_vec(elems)))
+    }
+}
+
+impl<'de, K: Ord + Deserialize<'de>, V: Deserialize<'de>> Deserialize<'de> for LLRBMap<K, V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut entries: Vec<(K, V)> = deserializer.deserialize_map(MapVisitor(PhantomData))?;
+        // Reversed so that the stable sort puts the last of the equal keys
+        // first, where dedup keeps it.
+        entries.reverse();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries.dedup_by(|a, b| a.0 == b.0);
+        Ok(LLRBMap::from_sorted_vec(entries))
+    }
+}
+
+impl<'de, K: Ord + Deserialize<'de>, V: Deserialize<'de>> Deserialize<'de> for Strict<LLRBMap<K, V>> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries: Vec<(K, V)> = deserializer.deserialize_map(MapVisitor(PhantomData))?;
+        if !entries.windows(2).all(|w| w[0].0 < w[1].0) {
+            return Err(D::Error::custom("keys are not strictly increasing"));
+        }
+        Ok(Strict(LLRBMap::from_sorted_vec(entries)))
+    }
+}
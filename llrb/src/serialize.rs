@@ -0,0 +1,172 @@
+use core::convert::TryFrom;
+
+use std::io::{self, Read, Write};
+use std::vec::Vec;
+
+use super::{BST, Color, Node, Ptr};
+
+// Converts elements to and from bytes for serialize_into and
+// deserialize_from.
+pub trait Codec<T> {
+    fn encode<W: Write>(&self, elem: &T, writer: &mut W) -> io::Result<()>;
+
+    fn decode<R: Read>(&self, reader: &mut R) -> io::Result<T>;
+}
+
+// The encoding is the number of nodes as a little-endian u64, then three
+// bits per node in preorder (red, has a left child, has a right child)
+// packed from the low bits up, then the elements in preorder.
+const RED: u8 = 1;
+const HAS_LEFT: u8 = 2;
+const HAS_RIGHT: u8 = 4;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl<T: Ord> BST<T> {
+    fn preorder(&self) -> Vec<Ptr> {
+        let mut order = Vec::with_capacity(self.len());
+        let mut stack: Vec<Ptr> = self.root.into_iter().collect();
+        while let Some(ptr) = stack.pop() {
+            order.push(ptr);
+            let node = self.deref(&ptr);
+            stack.extend(node.right);
+            stack.extend(node.left);
+        }
+        order
+    }
+
+    pub fn serialize_into<W: Write, C: Codec<T>>(&self, writer: &mut W, codec: &C) -> io::Result<()> {
+        let order = self.preorder();
+        writer.write_all(&(order.len() as u64).to_le_bytes())?;
+        let mut shape = vec![0u8; (3 * order.len()).div_ceil(8)];
+        for (i, ptr) in order.iter().enumerate() {
+            let node = self.deref(ptr);
+            let mut bits = 0;
+            if node.color == Color::Red {
+                bits |= RED;
+            }
+            if node.left.is_some() {
+                bits |= HAS_LEFT;
+            }
+            if node.right.is_some() {
+                bits |= HAS_RIGHT;
+            }
+            // The three bits may straddle a byte boundary.
+            let bits = (bits as u16) << (3 * i % 8);
+            shape[3 * i / 8] |= bits as u8;
+            if let Some(next) = shape.get_mut(3 * i / 8 + 1) {
+                *next |= (bits >> 8) as u8;
+            }
+        }
+        writer.write_all(&shape)?;
+        for ptr in &order {
+            codec.encode(&self.deref(ptr).elem, writer)?;
+        }
+        Ok(())
+    }
+
+    // Rebuilds a tree written by serialize_into, placing the nodes in the
+    // arena in preorder. The result is checked with try_self_check, so
+    // malformed input is reported as InvalidData rather than producing a
+    // broken tree.
+    pub fn deserialize_from<R: Read, C: Codec<T>>(reader: &mut R, codec: &C) -> io::Result<BST<T>> {
+        let mut len = [0u8; 8];
+        reader.read_exact(&mut len)?;
+        let len = usize::try_from(u64::from_le_bytes(len)).map_err(|_| invalid("tree is too large"))?;
+        let shape_len = len.checked_mul(3).ok_or_else(|| invalid("tree is too large"))?.div_ceil(8);
+        let mut shape = Vec::new();
+        reader.by_ref().take(shape_len as u64).read_to_end(&mut shape)?;
+        if shape.len() != shape_len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+
+        let mut tree = BST::new();
+        // Slots that are waiting for their left or right child; in preorder
+        // the next node always fills the most recent one.
+        let mut pending: Vec<(usize, bool)> = Vec::new();
+        for i in 0..len {
+            let lo = shape[3 * i / 8] as u16;
+            let hi = shape.get(3 * i / 8 + 1).map_or(0, |&b| b as u16);
+            let bits = ((lo | hi << 8) >> (3 * i % 8)) as u8;
+            let color = if bits & RED != 0 { Color::Red } else { Color::Black };
+            tree.nodes.push(Some(Node::new(codec.decode(reader)?, color)));
+            if i > 0 {
+                let (parent, is_left) = pending.pop().ok_or_else(|| invalid("node has no place in the tree"))?;
+                let parent = tree.deref_mut(&Ptr::new(parent));
+                if is_left {
+                    parent.left = Some(Ptr::new(i));
+                } else {
+                    parent.right = Some(Ptr::new(i));
+                }
+            }
+            if bits & HAS_RIGHT != 0 {
+                pending.push((i, false));
+            }
+            if bits & HAS_LEFT != 0 {
+                pending.push((i, true));
+            }
+        }
+        if !pending.is_empty() {
+            return Err(invalid("tree is missing nodes"));
+        }
+
+        // Children come after their parents, so sizes can be settled
+        // backwards.
+        for i in (0..len).rev() {
+            tree.update_size(Ptr::new(i));
+        }
+        tree.root = if len == 0 { None } else { Some(Ptr::new(0)) };
+        tree.try_self_check().map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+    use std::vec::Vec;
+
+    use super::Codec;
+    use BST;
+
+    struct LittleEndian;
+
+    impl Codec<u32> for LittleEndian {
+        fn encode<W: Write>(&self, elem: &u32, writer: &mut W) -> io::Result<()> {
+            writer.write_all(&elem.to_le_bytes())
+        }
+
+        fn decode<R: Read>(&self, reader: &mut R) -> io::Result<u32> {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes))
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for &n in &[0, 1, 2, 3, 10, 1000] {
+            let mut tree = BST::new();
+            for i in 0..n {
+                tree.insert(i * 7 % 1009);
+            }
+            let mut bytes = Vec::new();
+            tree.serialize_into(&mut bytes, &LittleEndian).unwrap();
+            assert_eq!(bytes.len(), 8 + (3 * n as usize).div_ceil(8) + 4 * n as usize);
+            let copy = BST::deserialize_from(&mut &bytes[..], &LittleEndian).unwrap();
+            assert_eq!(copy, tree);
+            assert_eq!(copy.shape_stats(), tree.shape_stats());
+        }
+
+        // Truncated or reordered input is rejected.
+        let tree = BST::from_sorted_iter(0..10u32);
+        let mut bytes = Vec::new();
+        tree.serialize_into(&mut bytes, &LittleEndian).unwrap();
+        assert_eq!(BST::deserialize_from(&mut &bytes[..bytes.len() - 1], &LittleEndian).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        let last = bytes.len() - 4;
+        bytes[last..].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(BST::deserialize_from(&mut &bytes[..], &LittleEndian).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
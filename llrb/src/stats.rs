@@ -0,0 +1,93 @@
+use core::mem::size_of;
+
+use alloc::vec::Vec;
+
+use super::{BST, Node, Ptr};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShapeStats {
+    pub len: usize,
+    pub height: usize,
+    pub black_height: usize,
+    pub red_nodes: usize,
+    // The number of nodes at each depth, starting with the root at depth 0.
+    pub nodes_per_depth: Vec<usize>,
+    pub average_depth: f64,
+}
+
+// The arena's footprint. Memory owned by the elements themselves is not
+// counted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub bytes_allocated: usize,
+    pub live_nodes: usize,
+    // Slots of deleted nodes that are waiting to be reused.
+    pub deleted_slots: usize,
+    // The number of slots the arena can hold without reallocating.
+    pub capacity: usize,
+}
+
+impl MemoryReport {
+    // The share of used slots that hold no node, which compact would
+    // reclaim.
+    pub fn fragmentation(&self) -> f64 {
+        let slots = self.live_nodes + self.deleted_slots;
+        if slots == 0 { 0.0 } else { self.deleted_slots as f64 / slots as f64 }
+    }
+}
+
+impl<T: Ord> BST<T> {
+    // The number of nodes on the longest path from the root to a leaf.
+    pub fn height(&self) -> usize {
+        let mut height = 0;
+        let mut stack: Vec<(Ptr, usize)> = self.root.map(|root| (root, 1)).into_iter().collect();
+        while let Some((ptr, depth)) = stack.pop() {
+            height = height.max(depth);
+            let node = self.deref(&ptr);
+            stack.extend(node.left.into_iter().chain(node.right).map(|child| (child, depth + 1)));
+        }
+        height
+    }
+
+    // The number of black nodes on every path from the root to a leaf.
+    pub fn black_height(&self) -> usize {
+        self.subtree_black_height(self.root)
+    }
+
+    pub fn shape_stats(&self) -> ShapeStats {
+        let mut nodes_per_depth = Vec::new();
+        let mut red_nodes = 0;
+        let mut total_depth = 0;
+        let mut stack: Vec<(Ptr, usize)> = self.root.map(|root| (root, 0)).into_iter().collect();
+        while let Some((ptr, depth)) = stack.pop() {
+            if nodes_per_depth.len() == depth {
+                nodes_per_depth.push(0);
+            }
+            nodes_per_depth[depth] += 1;
+            total_depth += depth;
+            if self.is_red(&Some(ptr)) {
+                red_nodes += 1;
+            }
+            let node = self.deref(&ptr);
+            stack.extend(node.left.into_iter().chain(node.right).map(|child| (child, depth + 1)));
+        }
+        let len = self.len();
+        ShapeStats {
+            len,
+            height: nodes_per_depth.len(),
+            black_height: self.black_height(),
+            red_nodes,
+            nodes_per_depth,
+            average_depth: if len == 0 { 0.0 } else { total_depth as f64 / len as f64 },
+        }
+    }
+
+    pub fn memory_usage(&self) -> MemoryReport {
+        MemoryReport {
+            bytes_allocated: self.nodes.capacity() * size_of::<Option<Node<T>>>() + self.deleted_indices.capacity() * size_of::<Ptr>(),
+            live_nodes: self.len(),
+            deleted_slots: self.deleted_indices.len(),
+            capacity: self.nodes.capacity(),
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::ops::Bound;
+
+use super::{BST, Handle};
+use {cursor, iter};
+
+// An element along with the metadata attached to its node. Entries are
+// ordered by element alone, so the metadata can be changed in place.
+#[derive(Debug, Clone)]
+struct Tagged<T, M> {
+    elem: T,
+    meta: M,
+}
+
+impl<T: Ord, M> PartialEq for Tagged<T, M> {
+    fn eq(&self, other: &Self) -> bool {
+        self.elem == other.elem
+    }
+}
+
+impl<T: Ord, M> Eq for Tagged<T, M> {}
+
+impl<T: Ord, M> PartialOrd for Tagged<T, M> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord, M> Ord for Tagged<T, M> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.elem.cmp(&other.elem)
+    }
+}
+
+// A tree with a user-defined payload attached to every node, such as a
+// visited mark or a weight. The payload lives in the node alongside the
+// element, so it moves with it through rotations and can be reached
+// through a handle without a side table.
+#[derive(Debug, Clone)]
+pub struct TaggedBST<T, M = ()> {
+    tree: BST<Tagged<T, M>>,
+}
+
+#[allow(clippy::new_without_default)]
+impl<T: Ord, M> TaggedBST<T, M> {
+    pub fn new() -> Self {
+        TaggedBST { tree: BST::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear()
+    }
+
+    pub fn contains<Q: ?Sized + Ord>(&self, elem: &Q) -> bool where T: Borrow<Q> {
+        self.tree.find_by(|e| elem.cmp(e.elem.borrow())).is_some()
+    }
+
+    // Inserts the element with its metadata and returns a handle to it.
+    // An equal element is replaced and returned along with its metadata.
+    pub fn insert(&mut self, elem: T, meta: M) -> (Handle, Option<(T, M)>) {
+        let (handle, old) = self.tree.insert_with_handle(Tagged { elem, meta });
+        (handle, old.map(|old| (old.elem, old.meta)))
+    }
+
+    pub fn remove<Q: ?Sized + Ord>(&mut self, elem: &Q) -> Option<(T, M)> where T: Borrow<Q> {
+        self.tree.delete_by(|e| elem.cmp(e.elem.borrow())).map(|old| (old.elem, old.meta))
+    }
+
+    pub fn meta<Q: ?Sized + Ord>(&self, elem: &Q) -> Option<&M> where T: Borrow<Q> {
+        self.tree.find_by(|e| elem.cmp(e.elem.borrow())).map(|ptr| &self.tree.deref(&ptr).elem.meta)
+    }
+
+    pub fn meta_mut<Q: ?Sized + Ord>(&mut self, elem: &Q) -> Option<&mut M> where T: Borrow<Q> {
+        let ptr = self.tree.find_by(|e| elem.cmp(e.elem.borrow()))?;
+        Some(&mut self.tree.deref_mut(&ptr).elem.meta)
+    }
+
+    pub fn get_by_handle(&self, handle: Handle) -> Option<(&T, &M)> {
+        self.tree.get_by_handle(handle).map(|e| (&e.elem, &e.meta))
+    }
+
+    pub fn meta_by_handle_mut(&mut self, handle: Handle) -> Option<&mut M> {
+        self.tree.get_mut_by_handle(handle).map(|e| &mut e.meta)
+    }
+
+    pub fn remove_by_handle(&mut self, handle: Handle) -> Option<(T, M)> {
+        self.tree.remove_by_handle(handle).map(|old| (old.elem, old.meta))
+    }
+
+    // Iterates over the elements in order, each with its metadata.
+    pub fn iter(&self) -> Iter<'_, T, M> {
+        Iter { inner: self.tree.iter() }
+    }
+
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T, M> {
+        CursorMut { inner: self.tree.cursor_mut() }
+    }
+
+    // Returns a cursor at the first element above the bound, or at the
+    // ghost position if there is none.
+    pub fn lower_bound<Q: ?Sized + Ord>(&self, bound: Bound<&Q>) -> Cursor<'_, T, M> where T: Borrow<Q> {
+        let index = self.tree.count_prefix(|e| !iter::above_lower(bound, e.elem.borrow()));
+        Cursor { inner: cursor::Cursor::new(&self.tree, index) }
+    }
+
+    // Returns a cursor at the last element below the bound, or at the ghost
+    // position if there is none.
+    pub fn upper_bound<Q: ?Sized + Ord>(&self, bound: Bound<&Q>) -> Cursor<'_, T, M> where T: Borrow<Q> {
+        let index = match self.tree.count_prefix(|e| iter::below_upper(bound, e.elem.borrow())) {
+            0 => self.tree.len(),
+            n => n - 1,
+        };
+        Cursor { inner: cursor::Cursor::new(&self.tree, index) }
+    }
+}
+
+// The cursors of BST, handing out each element with its metadata.
+pub struct CursorMut<'a, T: 'a, M: 'a> {
+    inner: cursor::CursorMut<'a, Tagged<T, M>>,
+}
+
+impl<'a, T: Ord, M> CursorMut<'a, T, M> {
+    pub fn current(&self) -> Option<(&T, &M)> {
+        self.inner.current().map(|e| (&e.elem, &e.meta))
+    }
+
+    pub fn meta_mut(&mut self) -> Option<&mut M> {
+        self.inner.current_mut().map(|e| &mut e.meta)
+    }
+
+    pub fn index(&self) -> Option<usize> {
+        self.inner.index()
+    }
+
+    pub fn peek_next(&self) -> Option<(&T, &M)> {
+        self.inner.peek_next().map(|e| (&e.elem, &e.meta))
+    }
+
+    pub fn peek_prev(&self) -> Option<(&T, &M)> {
+        self.inner.peek_prev().map(|e| (&e.elem, &e.meta))
+    }
+
+    pub fn move_next(&mut self) {
+        self.inner.move_next()
+    }
+
+    pub fn move_prev(&mut self) {
+        self.inner.move_prev()
+    }
+
+    pub fn remove_current(&mut self) -> Option<(T, M)> {
+        self.inner.remove_current().map(|old| (old.elem, old.meta))
+    }
+
+    pub fn insert_before(&mut self, elem: T, meta: M) {
+        self.inner.insert_before(Tagged { elem, meta })
+    }
+
+    pub fn insert_after(&mut self, elem: T, meta: M) {
+        self.inner.insert_after(Tagged { elem, meta })
+    }
+}
+
+pub struct Cursor<'a, T: 'a, M: 'a> {
+    inner: cursor::Cursor<'a, Tagged<T, M>>,
+}
+
+impl<'a, T, M> Clone for Cursor<'a, T, M> {
+    fn clone(&self) -> Self {
+        Cursor { inner: self.inner.clone() }
+    }
+}
+
+impl<'a, T: Ord, M> Cursor<'a, T, M> {
+    pub fn current(&self) -> Option<(&'a T, &'a M)> {
+        self.inner.current().map(|e| (&e.elem, &e.meta))
+    }
+
+    pub fn index(&self) -> Option<usize> {
+        self.inner.index()
+    }
+
+    pub fn peek_next(&self) -> Option<(&'a T, &'a M)> {
+        self.inner.peek_next().map(|e| (&e.elem, &e.meta))
+    }
+
+    pub fn peek_prev(&self) -> Option<(&'a T, &'a M)> {
+        self.inner.peek_prev().map(|e| (&e.elem, &e.meta))
+    }
+
+    pub fn move_next(&mut self) {
+        self.inner.move_next()
+    }
+
+    pub fn move_prev(&mut self) {
+        self.inner.move_prev()
+    }
+}
+
+pub struct Iter<'a, T: 'a, M: 'a> {
+    inner: iter::Iter<'a, Tagged<T, M>>,
+}
+
+impl<'a, T, M> Iterator for Iter<'a, T, M> {
+    type Item = (&'a T, &'a M);
+
+    fn next(&mut self) -> Option<(&'a T, &'a M)> {
+        self.inner.next().map(|e| (&e.elem, &e.meta))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T, M> DoubleEndedIterator for Iter<'a, T, M> {
+    fn next_back(&mut self) -> Option<(&'a T, &'a M)> {
+        self.inner.next_back().map(|e| (&e.elem, &e.meta))
+    }
+}
+
+impl<'a, T, M> ExactSizeIterator for Iter<'a, T, M> {}
+
+#[cfg(test)]
+mod tests {
+    use core::ops::Bound;
+    use std::vec::Vec;
+
+    use super::TaggedBST;
+
+    #[test]
+    fn metadata_follows_elements() {
+        let mut tree = TaggedBST::new();
+        let handles: Vec<_> = (0..100u32).map(|i| tree.insert(i, false).0).collect();
+        for i in (0..100).filter(|i| i % 3 == 0) {
+            *tree.meta_by_handle_mut(handles[i]).unwrap() = true;
+        }
+        // Deleting rebalances the tree, which must not mix up the marks.
+        for i in (0..100u32).filter(|i| i % 5 == 0) {
+            assert_eq!(tree.remove(&i), Some((i, i % 3 == 0)));
+        }
+        assert!(tree.iter().all(|(&i, &visited)| visited == (i % 3 == 0)));
+        assert_eq!(tree.get_by_handle(handles[3]), Some((&3, &true)));
+        assert_eq!(tree.get_by_handle(handles[5]), None);
+
+        *tree.meta_mut(&4).unwrap() = true;
+        assert_eq!(tree.meta(&4), Some(&true));
+        assert_eq!(tree.insert(4, false).1, Some((4, true)));
+        assert_eq!(tree.remove_by_handle(handles[4]), Some((4, false)));
+        assert!(!tree.contains(&4));
+        assert_eq!(tree.len(), 79);
+    }
+
+    #[test]
+    fn cursors_reach_metadata() {
+        let mut tree = TaggedBST::new();
+        for i in 0..20u32 {
+            tree.insert(i * 10, 0);
+        }
+        {
+            // Weigh every element by its rank in a single pass.
+            let mut cursor = tree.cursor_mut();
+            while let Some(index) = cursor.index() {
+                *cursor.meta_mut().unwrap() = index;
+                cursor.move_next();
+            }
+            assert_eq!(cursor.meta_mut(), None);
+            cursor.move_next();
+            assert_eq!(cursor.remove_current(), Some((0, 0)));
+            cursor.insert_before(5, 99);
+            assert_eq!(cursor.current(), Some((&10, &1)));
+            assert_eq!(cursor.peek_prev(), Some((&5, &99)));
+        }
+        assert!(tree.iter().skip(1).all(|(&i, &rank)| rank == i as usize / 10));
+
+        let mut cursor = tree.lower_bound(Bound::Excluded(&100));
+        assert_eq!(cursor.current(), Some((&110, &11)));
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some((&100, &10)));
+        let cursor = tree.upper_bound(Bound::Included(&4));
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), Some((&5, &99)));
+    }
+}
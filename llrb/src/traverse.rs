@@ -0,0 +1,72 @@
+use core::ops::{Add, ControlFlow};
+
+use alloc::vec::Vec;
+
+use super::{BST, Ptr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Traversal {
+    PreOrder,
+    InOrder,
+    PostOrder,
+}
+
+impl<T> BST<T> {
+    // Calls f on every element in the given order until it breaks, and
+    // returns whether it did.
+    pub fn traverse<F: FnMut(&T) -> ControlFlow<()>>(&self, order: Traversal, mut f: F) -> ControlFlow<()> {
+        // Each entry records whether the children of the node have already
+        // been pushed.
+        let mut stack: Vec<(Ptr, bool)> = self.root.map(|root| (root, false)).into_iter().collect();
+        while let Some((ptr, expanded)) = stack.pop() {
+            let node = self.deref(&ptr);
+            if expanded {
+                f(&node.elem)?;
+                continue;
+            }
+            // Entries are pushed in reverse of the order they are visited.
+            let children = |stack: &mut Vec<(Ptr, bool)>, child: Option<Ptr>| stack.extend(child.map(|c| (c, false)));
+            match order {
+                Traversal::PreOrder => {
+                    children(&mut stack, node.right);
+                    children(&mut stack, node.left);
+                    f(&node.elem)?;
+                },
+                Traversal::InOrder => {
+                    children(&mut stack, node.right);
+                    stack.push((ptr, true));
+                    children(&mut stack, node.left);
+                },
+                Traversal::PostOrder => {
+                    stack.push((ptr, true));
+                    children(&mut stack, node.right);
+                    children(&mut stack, node.left);
+                },
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn fold_subtree<B, F: FnMut(B, &T) -> B>(&self, node: Option<Ptr>, init: B, f: &mut F) -> B {
+        match node {
+            None => init,
+            Some(ptr) => {
+                let node = self.deref(&ptr);
+                let acc = self.fold_subtree(node.left, init, f);
+                let acc = f(acc, &node.elem);
+                self.fold_subtree(node.right, acc, f)
+            }
+        }
+    }
+
+    // Folds the elements in order. Unlike Iterator::fold this recurses
+    // directly, which needs no heap allocation since the recursion depth is
+    // bounded by the height of the tree.
+    pub fn fold_in_order<B, F: FnMut(B, &T) -> B>(&self, init: B, mut f: F) -> B {
+        self.fold_subtree(self.root, init, &mut f)
+    }
+
+    pub fn sum_by<S: Default + Add<Output = S>, F: FnMut(&T) -> S>(&self, mut f: F) -> S {
+        self.fold_in_order(S::default(), |acc, elem| acc + f(elem))
+    }
+}
@@ -0,0 +1,38 @@
+// The serde tests live in their own crate, since linking serde_json into
+// the unit tests adds PartialEq impls that make vec![] ambiguous there.
+#![cfg(feature = "serde")]
+
+extern crate llrb;
+extern crate serde_json;
+
+use llrb::{Strict, BST, LLRBMap};
+
+#[test]
+fn round_trip() {
+    let tree = BST::from_sorted_iter(0..100u32);
+    let json = serde_json::to_string(&tree).unwrap();
+    assert_eq!(serde_json::from_str::<BST<u32>>(&json).unwrap(), tree);
+    let Strict(strict) = serde_json::from_str::<Strict<BST<u32>>>(&json).unwrap();
+    assert_eq!(strict, tree);
+    assert_eq!(strict.try_self_check(), Ok(()));
+
+    let loose: BST<u32> = serde_json::from_str("[3, 1, 2, 1]").unwrap();
+    assert!(loose.iter().cloned().eq(1..4));
+    assert!(serde_json::from_str::<Strict<BST<u32>>>("[3, 1, 2]").is_err());
+    assert!(serde_json::from_str::<Strict<BST<u32>>>("[1, 2, 2]").is_err());
+
+    let mut map = LLRBMap::new();
+    for i in 0..20 {
+        map.insert(i, i * i);
+    }
+    let json = serde_json::to_string(&map).unwrap();
+    let back: LLRBMap<u32, u32> = serde_json::from_str(&json).unwrap();
+    assert!(back.iter().eq(map.iter()));
+    let Strict(back) = serde_json::from_str::<Strict<LLRBMap<u32, u32>>>(&json).unwrap();
+    assert!(back.iter().eq(map.iter()));
+
+    let loose: LLRBMap<String, u32> = serde_json::from_str(r#"{"b": 1, "a": 2, "b": 3}"#).unwrap();
+    assert_eq!(loose.len(), 2);
+    assert_eq!(loose.get("b"), Some(&3));
+    assert!(serde_json::from_str::<Strict<LLRBMap<String, u32>>>(r#"{"b": 1, "a": 2}"#).is_err());
+}
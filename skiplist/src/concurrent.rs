@@ -0,0 +1,296 @@
+use core::borrow::Borrow;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use alloc::boxed::Box;
+
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+
+use DEFAULT_SEED;
+
+const MAX_LEVEL: usize = 32;
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    // The inserting and the removing thread each hold a reference, and
+    // whichever lets go last frees the node.
+    refs: AtomicUsize,
+    // The next node on each of the levels this node is on. A tag of 1 on a
+    // link marks this node as removed on that level, which also freezes the
+    // link, since every update to it expects it untagged.
+    next: Box<[Atomic<Node<K, V>>]>,
+}
+
+type Path<'g, K, V> = [Option<Shared<'g, Node<K, V>>>; MAX_LEVEL];
+type Successors<'g, K, V> = [Shared<'g, Node<K, V>>; MAX_LEVEL];
+
+// A lock-free ordered map for many threads, as a skip list in the style of
+// Fraser and of Herlihy and Shavit. An entry is removed by first tagging
+// its links from the top level down, which takes it out of the map once
+// the bottom link is tagged, and then unlinking it on each level with a
+// compare-and-swap on its predecessor there; searches unlink any tagged
+// node they pass, so a stalled thread never blocks the others. Nodes are
+// freed through crossbeam-epoch once no thread can still be reading them.
+// Values are shared between threads, so reads hand out clones.
+pub struct ConcurrentSkipListMap<K, V> {
+    head: Box<[Atomic<Node<K, V>>]>,
+    len: AtomicUsize,
+    // Each insertion takes the next state of a splitmix64 generator, so
+    // that drawing a height needs no lock.
+    seed: AtomicU64,
+}
+
+impl<K: Ord + Send + 'static, V: Send + 'static> ConcurrentSkipListMap<K, V> {
+    pub fn new() -> Self {
+        ConcurrentSkipListMap::with_seed(DEFAULT_SEED)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        let head = (0..MAX_LEVEL).map(|_| Atomic::null()).collect();
+        ConcurrentSkipListMap { head, len: AtomicUsize::new(0), seed: AtomicU64::new(seed) }
+    }
+
+    // The number of entries, which may be stale by the time it is read if
+    // other threads are changing the map.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn random_height(&self) -> usize {
+        let mut x = self.seed.fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        x ^= x >> 31;
+        (x | 1 << (MAX_LEVEL - 1)).trailing_zeros() as usize + 1
+    }
+
+    fn tower<'g>(&'g self, node: Option<Shared<'g, Node<K, V>>>) -> &'g [Atomic<Node<K, V>>] {
+        match node {
+            None => &self.head,
+            // Nodes reached under the guard are not freed before it is
+            // dropped.
+            Some(node) => unsafe { &node.deref().next },
+        }
+    }
+
+    // Finds on each level the last node whose key comes before the
+    // position, given that the predicate holds for a prefix of the keys,
+    // and the node after it, unlinking the removed nodes on the way. When
+    // an unlinking fails, or a predecessor turns out to be removed, the
+    // search starts over from the top.
+    fn search<'g, F: Fn(&K) -> bool>(&'g self, before: F, guard: &'g Guard) -> (Path<'g, K, V>, Successors<'g, K, V>) {
+        'retry: loop {
+            let mut preds = [None; MAX_LEVEL];
+            let mut succs = [Shared::null(); MAX_LEVEL];
+            let mut pred = None;
+            for level in (0..MAX_LEVEL).rev() {
+                let mut curr = self.tower(pred)[level].load(Ordering::Acquire, guard);
+                if curr.tag() == 1 {
+                    continue 'retry;
+                }
+                while let Some(c) = unsafe { curr.as_ref() } {
+                    let succ = c.next[level].load(Ordering::Acquire, guard);
+                    if succ.tag() == 1 {
+                        let unlinked = self.tower(pred)[level].compare_exchange(
+                            curr, succ.with_tag(0), Ordering::AcqRel, Ordering::Acquire, guard);
+                        match unlinked {
+                            Ok(_) => curr = succ.with_tag(0),
+                            Err(_) => continue 'retry,
+                        }
+                        continue;
+                    }
+                    if !before(&c.key) {
+                        break;
+                    }
+                    pred = Some(curr);
+                    curr = succ;
+                }
+                preds[level] = pred;
+                succs[level] = curr;
+            }
+            return (preds, succs);
+        }
+    }
+
+    fn find<'g, Q: ?Sized + Ord>(&'g self, key: &Q, guard: &'g Guard) -> Option<&'g Node<K, V>> where K: Borrow<Q> {
+        let (_, succs) = self.search(|k| k.borrow() < key, guard);
+        unsafe { succs[0].as_ref() }.filter(|node| node.key.borrow() == key)
+    }
+
+    pub fn contains_key<Q: ?Sized + Ord>(&self, key: &Q) -> bool where K: Borrow<Q> {
+        let guard = &epoch::pin();
+        self.find(key, guard).is_some()
+    }
+
+    pub fn get<Q: ?Sized + Ord>(&self, key: &Q) -> Option<V> where K: Borrow<Q>, V: Clone {
+        let guard = &epoch::pin();
+        self.find(key, guard).map(|node| node.value.clone())
+    }
+
+    // Inserts the entry if the key is absent, and returns whether it did.
+    // The entry is in the map once it is linked on the bottom level; the
+    // links above only speed up searches, and linking them is given up if
+    // the entry is removed in the meantime.
+    pub fn insert(&self, key: K, value: V) -> bool {
+        let guard = &epoch::pin();
+        let height = self.random_height();
+        let next = (0..height).map(|_| Atomic::null()).collect();
+        let mut node = Owned::new(Node { key, value, refs: AtomicUsize::new(2), next });
+        let (mut preds, mut succs, shared) = loop {
+            let (preds, succs) = self.search(|k| *k < node.key, guard);
+            if unsafe { succs[0].as_ref() }.is_some_and(|succ| succ.key == node.key) {
+                return false;
+            }
+            node.next[0].store(succs[0], Ordering::Relaxed);
+            let linked = self.tower(preds[0])[0].compare_exchange(
+                succs[0], node, Ordering::AcqRel, Ordering::Acquire, guard);
+            match linked {
+                Ok(shared) => break (preds, succs, shared),
+                Err(err) => node = err.new,
+            }
+        };
+        self.len.fetch_add(1, Ordering::Relaxed);
+
+        let n = unsafe { shared.deref() };
+        'levels: for level in 1..height {
+            loop {
+                let next = n.next[level].load(Ordering::Acquire, guard);
+                if next.tag() == 1 {
+                    break 'levels;
+                }
+                let pointed = n.next[level].compare_exchange(
+                    next, succs[level], Ordering::AcqRel, Ordering::Acquire, guard);
+                if pointed.is_err() {
+                    break 'levels;
+                }
+                let linked = self.tower(preds[level])[level].compare_exchange(
+                    succs[level], shared, Ordering::AcqRel, Ordering::Acquire, guard);
+                if linked.is_ok() {
+                    break;
+                }
+                let found = self.search(|k| *k < n.key, guard);
+                preds = found.0;
+                succs = found.1;
+            }
+        }
+        // A removal may have come while the upper levels were being linked,
+        // and missed the links made after its own search, so they are
+        // unlinked again here.
+        if n.next[0].load(Ordering::Acquire, guard).tag() == 1 {
+            self.search(|k| *k <= n.key, guard);
+        }
+        self.release(shared, guard);
+        true
+    }
+
+    // Removes the entry with the key, if any, and returns whether this
+    // call was the one that removed it.
+    pub fn remove<Q: ?Sized + Ord>(&self, key: &Q) -> bool where K: Borrow<Q> {
+        let guard = &epoch::pin();
+        let (_, succs) = self.search(|k| k.borrow() < key, guard);
+        let node = succs[0];
+        let n = match unsafe { node.as_ref() } {
+            Some(n) if n.key.borrow() == key => n,
+            _ => return false,
+        };
+        for level in (1..n.next.len()).rev() {
+            n.next[level].fetch_or(1, Ordering::AcqRel, guard);
+        }
+        if n.next[0].fetch_or(1, Ordering::AcqRel, guard).tag() == 1 {
+            return false;
+        }
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        // Searching past every node with the key unlinks this one from
+        // every level it is on.
+        self.search(|k| *k <= n.key, guard);
+        self.release(node, guard);
+        true
+    }
+
+    fn release<'g>(&self, node: Shared<'g, Node<K, V>>, guard: &'g Guard) {
+        if unsafe { node.deref() }.refs.fetch_sub(1, Ordering::AcqRel) == 1 {
+            unsafe { guard.defer_destroy(node) };
+        }
+    }
+}
+
+impl<K: Ord + Send + 'static, V: Send + 'static> Default for ConcurrentSkipListMap<K, V> {
+    fn default() -> Self {
+        ConcurrentSkipListMap::new()
+    }
+}
+
+impl<K, V> Drop for ConcurrentSkipListMap<K, V> {
+    // With no other thread left, every removed node has been unlinked and
+    // handed to the collector, so the bottom level holds exactly the nodes
+    // still to free.
+    fn drop(&mut self) {
+        unsafe {
+            let guard = epoch::unprotected();
+            let mut curr = self.head[0].load(Ordering::Relaxed, guard);
+            while !curr.is_null() {
+                let next = curr.deref().next[0].load(Ordering::Relaxed, guard);
+                drop(curr.into_owned());
+                curr = next;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use std::vec::Vec;
+
+    use super::ConcurrentSkipListMap;
+
+    #[test]
+    fn many_threads() {
+        let map = Arc::new(ConcurrentSkipListMap::new());
+        assert!(map.insert(0u32, 0u32));
+        assert!(!map.insert(0, 1));
+        assert_eq!(map.get(&0), Some(0));
+        assert!(map.remove(&0));
+        assert!(!map.remove(&0));
+        assert!(map.is_empty());
+
+        // Each thread inserts its own keys and removes the odd ones, while
+        // also racing the others to remove multiples of 10 from the whole
+        // range.
+        let threads: Vec<_> = (0..4u32).map(|t| {
+            let map = Arc::clone(&map);
+            thread::spawn(move || {
+                let mut removed = 0;
+                for i in 0..2000 {
+                    let key = i * 4 + t;
+                    assert!(map.insert(key, key * 2));
+                    if key % 2 == 1 {
+                        assert!(map.remove(&key));
+                    }
+                    if i % 5 == 0 && map.remove(&(i * 2)) {
+                        removed += 1;
+                    }
+                }
+                removed
+            })
+        }).collect();
+        let removed: usize = threads.into_iter().map(|thread| thread.join().unwrap()).sum();
+
+        let mut expected = 0;
+        for key in 0..8000u32 {
+            let present = key % 2 == 0 && map.contains_key(&key);
+            if present {
+                assert_eq!(map.get(&key), Some(key * 2));
+                expected += 1;
+            }
+            assert!(key % 2 == 0 || !map.contains_key(&key));
+        }
+        assert_eq!(map.len(), expected);
+        assert_eq!(expected + removed, 4000);
+    }
+}
@@ -0,0 +1,52 @@
+#![no_std]
+
+#[cfg(any(feature = "std", test))]
+extern crate std;
+extern crate alloc;
+extern crate common;
+#[cfg(feature = "std")]
+extern crate crossbeam_epoch;
+
+use core::ops::{Bound, RangeBounds};
+
+#[cfg(feature = "std")]
+pub mod concurrent;
+pub mod map;
+pub mod set;
+
+#[cfg(feature = "std")]
+pub use concurrent::ConcurrentSkipListMap;
+pub use map::SkipListMap;
+pub use set::SkipListSet;
+
+// Seeds the generator that draws the heights of nodes.
+const DEFAULT_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+fn check_range_bounds<Q: ?Sized + Ord, R: RangeBounds<Q>>(range: &R) {
+    match (range.start_bound(), range.end_bound()) {
+        (Bound::Excluded(start), Bound::Excluded(end)) if start == end =>
+            panic!("range start and end are equal and excluded"),
+        (Bound::Included(start), Bound::Included(end)) |
+        (Bound::Included(start), Bound::Excluded(end)) |
+        (Bound::Excluded(start), Bound::Included(end)) |
+        (Bound::Excluded(start), Bound::Excluded(end)) if start > end =>
+            panic!("range start is greater than range end"),
+        _ => (),
+    }
+}
+
+fn above_lower<Q: ?Sized + Ord>(lower: Bound<&Q>, elem: &Q) -> bool {
+    match lower {
+        Bound::Included(bound) => bound <= elem,
+        Bound::Excluded(bound) => bound < elem,
+        Bound::Unbounded => true,
+    }
+}
+
+fn below_upper<Q: ?Sized + Ord>(upper: Bound<&Q>, elem: &Q) -> bool {
+    match upper {
+        Bound::Included(bound) => elem <= bound,
+        Bound::Excluded(bound) => elem < bound,
+        Bound::Unbounded => true,
+    }
+}
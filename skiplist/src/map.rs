@@ -0,0 +1,459 @@
+use core::borrow::Borrow;
+use core::iter::FromIterator;
+use core::mem;
+use core::ops::RangeBounds;
+
+use alloc::vec::Vec;
+
+use common::XorShift;
+
+use {above_lower, below_upper, check_range_bounds, DEFAULT_SEED};
+
+// Nodes reach each level with probability 1/2, so 32 levels are enough for
+// about 2^32 entries before searches start to slow down. The cap lets a
+// search path fit in an array.
+const MAX_LEVEL: usize = 32;
+
+#[derive(Debug, Clone)]
+struct Node<K, V> {
+    key: K,
+    value: V,
+    // The next node on each of the levels this node is on, from the bottom.
+    next: Vec<Option<usize>>,
+}
+
+// The last node before a position on each level, where None stands for the
+// head of the list.
+type Path = [Option<usize>; MAX_LEVEL];
+
+// An ordered map as a skip list: a sorted linked list at the bottom level,
+// with each node also linked into the lists of the levels above, up to a
+// random height, so that a search can skip ahead on the sparser levels
+// before dropping down. Searches, insertions and removals take O(log n)
+// expected time without any rebalancing, and a removal just unlinks the
+// node from each of its levels. Scans follow the bottom level. The nodes
+// live in an arena of slots and link to each other by index.
+#[derive(Debug, Clone)]
+pub struct SkipListMap<K, V> {
+    nodes: Vec<Option<Node<K, V>>>,
+    free: Vec<usize>,
+    // The first node on each level, up to the highest level in use.
+    head: Vec<Option<usize>>,
+    len: usize,
+    rng: XorShift,
+}
+
+impl<K: Ord, V> SkipListMap<K, V> {
+    pub fn new() -> Self {
+        SkipListMap::with_seed(DEFAULT_SEED)
+    }
+
+    // Draws the heights of nodes from a generator with the given seed, so
+    // that the same insertions and removals build the same list.
+    pub fn with_seed(seed: u64) -> Self {
+        SkipListMap { nodes: Vec::new(), free: Vec::new(), head: Vec::new(), len: 0, rng: XorShift::new(seed) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.free.clear();
+        self.head.clear();
+        self.len = 0;
+    }
+
+    fn node(&self, i: usize) -> &Node<K, V> {
+        self.nodes[i].as_ref().expect("skip list refers to a free slot")
+    }
+
+    fn node_mut(&mut self, i: usize) -> &mut Node<K, V> {
+        self.nodes[i].as_mut().expect("skip list refers to a free slot")
+    }
+
+    fn next(&self, at: Option<usize>, level: usize) -> Option<usize> {
+        match at {
+            None => self.head[level],
+            Some(i) => self.node(i).next[level],
+        }
+    }
+
+    fn set_next(&mut self, at: Option<usize>, level: usize, next: Option<usize>) {
+        match at {
+            None => self.head[level] = next,
+            Some(i) => self.node_mut(i).next[level] = next,
+        }
+    }
+
+    // Finds the last node on each level whose key comes before the
+    // position, given that the predicate holds for a prefix of the keys.
+    fn path<F: Fn(&K) -> bool>(&self, before: F) -> Path {
+        let mut path = [None; MAX_LEVEL];
+        let mut at = None;
+        for level in (0..self.head.len()).rev() {
+            while let Some(next) = self.next(at, level) {
+                if !before(&self.node(next).key) {
+                    break;
+                }
+                at = Some(next);
+            }
+            path[level] = at;
+        }
+        path
+    }
+
+    // The node that follows the path on the bottom level.
+    fn seek_from(&self, path: &Path) -> Option<usize> {
+        if self.head.is_empty() { None } else { self.next(path[0], 0) }
+    }
+
+    // The first node whose key does not come before the position.
+    fn seek<F: Fn(&K) -> bool>(&self, before: F) -> Option<usize> {
+        self.seek_from(&self.path(before))
+    }
+
+    fn find<Q: ?Sized + Ord>(&self, key: &Q) -> Option<usize> where K: Borrow<Q> {
+        self.seek(|k| k.borrow() < key).filter(|&i| self.node(i).key.borrow() == key)
+    }
+
+    pub fn get<Q: ?Sized + Ord>(&self, key: &Q) -> Option<&V> where K: Borrow<Q> {
+        self.find(key).map(|i| &self.node(i).value)
+    }
+
+    pub fn get_key_value<Q: ?Sized + Ord>(&self, key: &Q) -> Option<(&K, &V)> where K: Borrow<Q> {
+        self.find(key).map(|i| {
+            let node = self.node(i);
+            (&node.key, &node.value)
+        })
+    }
+
+    pub fn get_mut<Q: ?Sized + Ord>(&mut self, key: &Q) -> Option<&mut V> where K: Borrow<Q> {
+        self.find(key).map(move |i| &mut self.node_mut(i).value)
+    }
+
+    pub fn contains_key<Q: ?Sized + Ord>(&self, key: &Q) -> bool where K: Borrow<Q> {
+        self.find(key).is_some()
+    }
+
+    // A node is on each level above the bottom with probability 1/2.
+    fn random_height(&mut self) -> usize {
+        (self.rng.next_u64() | 1 << (MAX_LEVEL - 1)).trailing_zeros() as usize + 1
+    }
+
+    // Inserts the entry and returns the value it replaced, keeping the key
+    // already in the map, if any.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let path = self.path(|k| *k < key);
+        if let Some(i) = self.seek_from(&path) {
+            if self.node(i).key == key {
+                return Some(mem::replace(&mut self.node_mut(i).value, value));
+            }
+        }
+        let height = self.random_height();
+        if self.head.len() < height {
+            self.head.resize(height, None);
+        }
+        let next = (0..height).map(|level| self.next(path[level], level)).collect();
+        let node = Some(Node { key, value, next });
+        let i = match self.free.pop() {
+            Some(i) => {
+                self.nodes[i] = node;
+                i
+            },
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            },
+        };
+        for (level, &at) in path.iter().enumerate().take(height) {
+            self.set_next(at, level, Some(i));
+        }
+        self.len += 1;
+        None
+    }
+
+    // Unlinks the node that follows the path on the bottom level.
+    fn unlink(&mut self, path: &Path, i: usize) -> (K, V) {
+        let node = self.nodes[i].take().unwrap();
+        for (level, &next) in node.next.iter().enumerate() {
+            self.set_next(path[level], level, next);
+        }
+        self.free.push(i);
+        while let Some(&None) = self.head.last() {
+            self.head.pop();
+        }
+        self.len -= 1;
+        (node.key, node.value)
+    }
+
+    pub fn remove<Q: ?Sized + Ord>(&mut self, key: &Q) -> Option<V> where K: Borrow<Q> {
+        self.remove_entry(key).map(|entry| entry.1)
+    }
+
+    pub fn remove_entry<Q: ?Sized + Ord>(&mut self, key: &Q) -> Option<(K, V)> where K: Borrow<Q> {
+        let path = self.path(|k| k.borrow() < key);
+        let i = self.seek_from(&path).filter(|&i| self.node(i).key.borrow() == key)?;
+        Some(self.unlink(&path, i))
+    }
+
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        let node = self.node(self.head.first().cloned().flatten()?);
+        Some((&node.key, &node.value))
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        let node = self.node(self.path(|_| true)[0]?);
+        Some((&node.key, &node.value))
+    }
+
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let first = self.head.first().cloned().flatten()?;
+        Some(self.unlink(&[None; MAX_LEVEL], first))
+    }
+
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let last = self.path(|_| true)[0]?;
+        let path = {
+            let key = &self.node(last).key;
+            self.path(|k| k < key)
+        };
+        Some(self.unlink(&path, last))
+    }
+
+    // Iterates in order along the bottom level.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let range = Range { map: self, next: self.head.first().cloned().flatten(), stop: None };
+        Iter { range, remaining: self.len }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    // Iterates in order over the entries with keys in the range, which is
+    // found by two searches, one for each end.
+    pub fn range<Q: ?Sized + Ord, R: RangeBounds<Q>>(&self, range: R) -> Range<'_, K, V> where K: Borrow<Q> {
+        check_range_bounds(&range);
+        let next = self.seek(|k| !above_lower(range.start_bound(), k.borrow()));
+        let stop = self.seek(|k| below_upper(range.end_bound(), k.borrow()));
+        Range { map: self, next, stop }
+    }
+}
+
+impl<K: Ord, V> Default for SkipListMap<K, V> {
+    fn default() -> Self {
+        SkipListMap::new()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for SkipListMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = SkipListMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for SkipListMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a SkipListMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<K, V> IntoIterator for SkipListMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        let next = self.head.first().cloned().flatten();
+        IntoIter { nodes: self.nodes, next, remaining: self.len }
+    }
+}
+
+// Follows the bottom level until it reaches the first node past the range.
+pub struct Range<'a, K: 'a, V: 'a> {
+    map: &'a SkipListMap<K, V>,
+    next: Option<usize>,
+    stop: Option<usize>,
+}
+
+impl<'a, K: Ord, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let i = self.next.filter(|&i| Some(i) != self.stop)?;
+        let node = self.map.node(i);
+        self.next = node.next[0];
+        Some((&node.key, &node.value))
+    }
+}
+
+pub struct Iter<'a, K: 'a, V: 'a> {
+    range: Range<'a, K, V>,
+    remaining: usize,
+}
+
+impl<'a, K: Ord, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let entry = self.range.next()?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+pub struct Keys<'a, K: 'a, V: 'a> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K: Ord, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.inner.next().map(|entry| entry.0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for Keys<'a, K, V> {}
+
+pub struct Values<'a, K: 'a, V: 'a> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K: Ord, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(|entry| entry.1)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for Values<'a, K, V> {}
+
+pub struct IntoIter<K, V> {
+    nodes: Vec<Option<Node<K, V>>>,
+    next: Option<usize>,
+    remaining: usize,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        let node = self.nodes[self.next?].take().unwrap();
+        self.next = node.next[0];
+        self.remaining -= 1;
+        Some((node.key, node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::vec::Vec;
+
+    use super::SkipListMap;
+
+    // Checks that every level is sorted and skips over a sublist of the
+    // level below, and that the head reaches no higher than needed.
+    fn check<K: Ord, V>(map: &SkipListMap<K, V>) {
+        let mut below: Option<Vec<usize>> = None;
+        for level in 0..map.head.len() {
+            let mut nodes = Vec::new();
+            let mut at = map.head[level];
+            while let Some(i) = at {
+                nodes.push(i);
+                at = map.node(i).next[level];
+            }
+            assert!(!nodes.is_empty());
+            assert!(nodes.windows(2).all(|pair| map.node(pair[0]).key < map.node(pair[1]).key));
+            match below {
+                None => assert_eq!(nodes.len(), map.len()),
+                Some(ref below) => assert!(nodes.iter().all(|i| below.contains(i))),
+            }
+            below = Some(nodes);
+        }
+        assert_eq!(map.nodes.iter().filter(|node| node.is_some()).count(), map.len());
+    }
+
+    #[test]
+    fn against_btree_map() {
+        let mut map = SkipListMap::with_seed(7);
+        let mut expected = BTreeMap::new();
+        for i in 0..3000u32 {
+            let key = i.wrapping_mul(2654435761) % 1000;
+            if i % 3 == 2 {
+                assert_eq!(map.remove(&key), expected.remove(&key));
+            } else {
+                assert_eq!(map.insert(key, i), expected.insert(key, i));
+            }
+        }
+        check(&map);
+        assert_eq!(map.len(), expected.len());
+        assert!(map.iter().eq(expected.iter()));
+        assert!(map.range(100..200).eq(expected.range(100..200)));
+        assert!(map.range(..=500).eq(expected.range(..=500)));
+        assert_eq!(map.range(300..300).count(), 0);
+        assert_eq!(map.first_key_value(), expected.iter().next());
+        assert_eq!(map.last_key_value(), expected.iter().next_back());
+        *map.get_mut(&map.first_key_value().map(|entry| *entry.0).unwrap()).unwrap() = 0;
+        assert_eq!(map.values().next(), Some(&0));
+
+        let first = map.pop_first().unwrap();
+        let last = map.pop_last().unwrap();
+        assert!(first.0 < last.0);
+        assert!(!map.contains_key(&first.0) && !map.contains_key(&last.0));
+        check(&map);
+        let keys: Vec<u32> = map.into_iter().map(|entry| entry.0).collect();
+        assert_eq!(keys.len(), expected.len() - 2);
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // The same seed builds the same list.
+        let a: SkipListMap<u32, ()> = (0..100).map(|i| (i, ())).collect();
+        let b: SkipListMap<u32, ()> = (0..100).map(|i| (i, ())).collect();
+        assert_eq!(a.head, b.head);
+        assert!(a.nodes.iter().zip(&b.nodes).all(|(x, y)| x.as_ref().unwrap().next == y.as_ref().unwrap().next));
+    }
+}
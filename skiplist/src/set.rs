@@ -0,0 +1,188 @@
+use core::borrow::Borrow;
+use core::iter::FromIterator;
+use core::ops::RangeBounds;
+
+use map::{self, SkipListMap};
+
+// An ordered set as a skip list, which is a SkipListMap with no values.
+#[derive(Debug, Clone)]
+pub struct SkipListSet<T> {
+    map: SkipListMap<T, ()>,
+}
+
+impl<T: Ord> SkipListSet<T> {
+    pub fn new() -> Self {
+        SkipListSet { map: SkipListMap::new() }
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        SkipListSet { map: SkipListMap::with_seed(seed) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    pub fn contains<Q: ?Sized + Ord>(&self, elem: &Q) -> bool where T: Borrow<Q> {
+        self.map.contains_key(elem)
+    }
+
+    pub fn get<Q: ?Sized + Ord>(&self, elem: &Q) -> Option<&T> where T: Borrow<Q> {
+        self.map.get_key_value(elem).map(|entry| entry.0)
+    }
+
+    // Inserts the element and returns whether it was not already present,
+    // in which case the set is left unchanged.
+    pub fn insert(&mut self, elem: T) -> bool {
+        !self.map.contains_key(&elem) && self.map.insert(elem, ()).is_none()
+    }
+
+    pub fn remove<Q: ?Sized + Ord>(&mut self, elem: &Q) -> Option<T> where T: Borrow<Q> {
+        self.map.remove_entry(elem).map(|entry| entry.0)
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.map.first_key_value().map(|entry| entry.0)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.map.last_key_value().map(|entry| entry.0)
+    }
+
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.map.pop_first().map(|entry| entry.0)
+    }
+
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.map.pop_last().map(|entry| entry.0)
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { inner: self.map.keys() }
+    }
+
+    pub fn range<Q: ?Sized + Ord, R: RangeBounds<Q>>(&self, range: R) -> Range<'_, T> where T: Borrow<Q> {
+        Range { inner: self.map.range(range) }
+    }
+}
+
+impl<T: Ord> Default for SkipListSet<T> {
+    fn default() -> Self {
+        SkipListSet::new()
+    }
+}
+
+impl<T: Ord> FromIterator<T> for SkipListSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = SkipListSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<T: Ord> Extend<T> for SkipListSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.insert(elem);
+        }
+    }
+}
+
+impl<'a, T: Ord> IntoIterator for &'a SkipListSet<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for SkipListSet<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { inner: self.map.into_iter() }
+    }
+}
+
+pub struct Iter<'a, T: 'a> {
+    inner: map::Keys<'a, T, ()>,
+}
+
+impl<'a, T: Ord> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T: Ord> ExactSizeIterator for Iter<'a, T> {}
+
+pub struct Range<'a, T: 'a> {
+    inner: map::Range<'a, T, ()>,
+}
+
+impl<'a, T: Ord> Iterator for Range<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next().map(|entry| entry.0)
+    }
+}
+
+pub struct IntoIter<T> {
+    inner: map::IntoIter<T, ()>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.inner.next().map(|entry| entry.0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::SkipListSet;
+
+    #[test]
+    fn set_operations() {
+        let mut set: SkipListSet<u32> = (0..100).rev().map(|i| i * 3).collect();
+        assert_eq!(set.len(), 100);
+        assert!(!set.insert(30));
+        assert!(set.insert(31));
+        assert_eq!(set.remove(&33), Some(33));
+        assert_eq!(set.remove(&34), None);
+        assert!(set.contains(&31) && !set.contains(&33));
+        assert_eq!((set.first(), set.last()), (Some(&0), Some(&297)));
+        assert!(set.range(28..40).cloned().eq([30, 31, 36, 39].iter().cloned()));
+        assert!(set.range(290..).cloned().eq([291, 294, 297].iter().cloned()));
+        assert_eq!((set.pop_first(), set.pop_last()), (Some(0), Some(297)));
+        let elems: Vec<u32> = set.into_iter().collect();
+        assert_eq!(elems.len(), 98);
+        assert!(elems.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
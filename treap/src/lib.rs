@@ -0,0 +1,426 @@
+#![no_std]
+
+#[cfg(test)]
+extern crate std;
+extern crate alloc;
+extern crate common;
+
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::iter::FromIterator;
+use core::mem;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use common::{drop_tree, BoxedNode, XorShift};
+
+// Seeds the generator that draws priorities.
+const DEFAULT_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+type Link<T> = Option<Box<Node<T>>>;
+
+struct Node<T> {
+    elem: T,
+    priority: u64,
+    // The number of elements in the subtree, which lets a split know the
+    // lengths of both halves.
+    size: usize,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+impl<T> BoxedNode for Node<T> {
+    fn take_children(&mut self) -> (Link<T>, Link<T>) {
+        (self.left.take(), self.right.take())
+    }
+}
+
+fn size<T>(link: &Link<T>) -> usize {
+    link.as_ref().map_or(0, |node| node.size)
+}
+
+fn update<T>(node: &mut Node<T>) {
+    node.size = 1 + size(&node.left) + size(&node.right);
+}
+
+// Hangs a subtree back below the path that led down to it, from the
+// bottom up, refreshing the sizes on the way. Each step of the path is a
+// node with the side its missing child is on, true for the left. Keeping
+// the path in a Vec rather than on the call stack lets an unlucky tree be
+// as deep as it is long.
+fn reattach<T>(mut path: Vec<(Box<Node<T>>, bool)>, mut link: Link<T>) -> Link<T> {
+    while let Some((mut node, left)) = path.pop() {
+        if left {
+            node.left = link;
+        } else {
+            node.right = link;
+        }
+        update(&mut node);
+        link = Some(node);
+    }
+    link
+}
+
+// Splits a subtree into the elements less than the key and the rest,
+// following a single path down. The nodes on it fall into two spines, one
+// for each half.
+fn split<T: Borrow<Q>, Q: ?Sized + Ord>(mut link: Link<T>, key: &Q) -> (Link<T>, Link<T>) {
+    let (mut less, mut rest) = (Vec::new(), Vec::new());
+    while let Some(mut node) = link {
+        if node.elem.borrow() < key {
+            link = node.right.take();
+            less.push((node, false));
+        } else {
+            link = node.left.take();
+            rest.push((node, true));
+        }
+    }
+    (reattach(less, None), reattach(rest, None))
+}
+
+// Joins two subtrees, every element of the first being less than every
+// element of the second, by zipping their facing spines together in order
+// of priority.
+fn merge<T>(mut a: Link<T>, mut b: Link<T>) -> Link<T> {
+    let mut path = Vec::new();
+    let rest = loop {
+        match (a, b) {
+            (None, rest) | (rest, None) => break rest,
+            (Some(mut x), Some(mut y)) => {
+                if x.priority > y.priority {
+                    a = x.right.take();
+                    b = Some(y);
+                    path.push((x, false));
+                } else {
+                    a = Some(x);
+                    b = y.left.take();
+                    path.push((y, true));
+                }
+            },
+        }
+    };
+    reattach(path, rest)
+}
+
+// Inserts a node whose element is not in the subtree: it goes down until
+// it meets a node of lower priority, and the subtree there is split
+// around it.
+fn insert<T: Ord>(mut link: Link<T>, mut new: Box<Node<T>>) -> Box<Node<T>> {
+    let mut path = Vec::new();
+    while let Some(mut node) = link {
+        if node.priority <= new.priority {
+            link = Some(node);
+            break;
+        }
+        let left = new.elem < node.elem;
+        link = if left { node.left.take() } else { node.right.take() };
+        path.push((node, left));
+    }
+    let (less, rest) = split(link, &new.elem);
+    new.left = less;
+    new.right = rest;
+    update(&mut new);
+    reattach(path, Some(new)).unwrap()
+}
+
+// Returns the subtree without the element, and the element if it was
+// there.
+fn delete<T: Borrow<Q>, Q: ?Sized + Ord>(mut link: Link<T>, key: &Q) -> (Link<T>, Option<T>) {
+    let mut path = Vec::new();
+    let mut deleted = None;
+    while let Some(mut node) = link {
+        match key.cmp(node.elem.borrow()) {
+            Ordering::Less => {
+                link = node.left.take();
+                path.push((node, true));
+            },
+            Ordering::Greater => {
+                link = node.right.take();
+                path.push((node, false));
+            },
+            Ordering::Equal => {
+                let mut node = *node;
+                link = merge(node.left.take(), node.right.take());
+                deleted = Some(node.elem);
+                break;
+            },
+        }
+    }
+    (reattach(path, link), deleted)
+}
+
+// A set as a treap: a binary search tree by element that is also a
+// max-heap by a random priority drawn for each element, which makes its
+// shape that of a tree built by inserting in random order, and so of
+// expected logarithmic height whatever the order of insertion. Unlike the
+// LLRB, there is no balance to restore after cutting or joining trees, so
+// a treap splits by key and merges with another in O(log n) expected time.
+pub struct Treap<T> {
+    root: Link<T>,
+    rng: XorShift,
+}
+
+impl<T: Ord> Treap<T> {
+    pub fn new() -> Self {
+        Treap::with_seed(DEFAULT_SEED)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Treap { root: None, rng: XorShift::new(seed) }
+    }
+
+    pub fn len(&self) -> usize {
+        size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Treap { root: None, rng: self.rng.clone() };
+    }
+
+    pub fn member<Q: ?Sized + Ord>(&self, elem: &Q) -> bool where T: Borrow<Q> {
+        self.get(elem).is_some()
+    }
+
+    pub fn get<Q: ?Sized + Ord>(&self, elem: &Q) -> Option<&T> where T: Borrow<Q> {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            match elem.cmp(node.elem.borrow()) {
+                Ordering::Less => link = &node.left,
+                Ordering::Greater => link = &node.right,
+                Ordering::Equal => return Some(&node.elem),
+            }
+        }
+        None
+    }
+
+    fn get_mut<Q: ?Sized + Ord>(&mut self, elem: &Q) -> Option<&mut T> where T: Borrow<Q> {
+        let mut link = &mut self.root;
+        while let Some(node) = link {
+            match elem.cmp(node.elem.borrow()) {
+                Ordering::Less => link = &mut node.left,
+                Ordering::Greater => link = &mut node.right,
+                Ordering::Equal => return Some(&mut node.elem),
+            }
+        }
+        None
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        let mut node = self.root.as_ref()?;
+        while let Some(left) = node.left.as_ref() {
+            node = left;
+        }
+        Some(&node.elem)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        let mut node = self.root.as_ref()?;
+        while let Some(right) = node.right.as_ref() {
+            node = right;
+        }
+        Some(&node.elem)
+    }
+
+    // Inserts the element, replacing and returning any equal one.
+    pub fn insert(&mut self, elem: T) -> Option<T> {
+        if let Some(old) = self.get_mut(&elem) {
+            return Some(mem::replace(old, elem));
+        }
+        let new = Box::new(Node { elem, priority: self.rng.next_u64(), size: 1, left: None, right: None });
+        self.root = Some(insert(self.root.take(), new));
+        None
+    }
+
+    pub fn delete<Q: ?Sized + Ord>(&mut self, elem: &Q) -> Option<T> where T: Borrow<Q> {
+        let (root, deleted) = delete(self.root.take(), elem);
+        self.root = root;
+        deleted
+    }
+
+    // Splits the set into the elements less than the key and the rest. The
+    // second half draws its priorities from a generator seeded by the
+    // first's.
+    pub fn split<Q: ?Sized + Ord>(mut self, key: &Q) -> (Treap<T>, Treap<T>) where T: Borrow<Q> {
+        let (less, rest) = split(self.root.take(), key);
+        let seed = self.rng.next_u64();
+        (Treap { root: less, rng: self.rng.clone() }, Treap { root: rest, rng: XorShift::new(seed) })
+    }
+
+    // Appends a set whose elements are all greater than those of this one.
+    pub fn merge(&mut self, mut other: Treap<T>) {
+        if let (Some(last), Some(first)) = (self.last(), other.first()) {
+            assert!(last < first, "merge: the treaps overlap");
+        }
+        self.root = merge(self.root.take(), other.root.take());
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        let mut iter = Iter { stack: Vec::new(), remaining: self.len() };
+        iter.push_left(&self.root);
+        iter
+    }
+}
+
+impl<T: Ord> Default for Treap<T> {
+    fn default() -> Self {
+        Treap::new()
+    }
+}
+
+impl<T: Ord> FromIterator<T> for Treap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut treap = Treap::new();
+        treap.extend(iter);
+        treap
+    }
+}
+
+impl<T: Ord> Extend<T> for Treap<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.insert(elem);
+        }
+    }
+}
+
+impl<'a, T: Ord> IntoIterator for &'a Treap<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T> Drop for Treap<T> {
+    // Expected depth is logarithmic, but an unlucky tree can be deeper.
+    fn drop(&mut self) {
+        drop_tree(self.root.take());
+    }
+}
+
+pub struct Iter<'a, T: 'a> {
+    stack: Vec<&'a Node<T>>,
+    remaining: usize,
+}
+
+impl<'a, T> Iter<'a, T> {
+    fn push_left(&mut self, mut link: &'a Link<T>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+        self.push_left(&node.right);
+        self.remaining -= 1;
+        Some(&node.elem)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use std::boxed::Box;
+    use std::vec::Vec;
+
+    use common::XorShift;
+
+    use super::{Link, Node, Treap};
+
+    // Checks the search order, the heap order and the sizes, and returns
+    // the height.
+    fn check<T: Ord>(link: &Link<T>) -> usize {
+        let node = match link {
+            None => return 0,
+            Some(node) => node,
+        };
+        if let Some(left) = node.left.as_ref() {
+            assert!(left.elem < node.elem && left.priority <= node.priority);
+        }
+        if let Some(right) = node.right.as_ref() {
+            assert!(right.elem > node.elem && right.priority <= node.priority);
+        }
+        assert_eq!(node.size, 1 + super::size(&node.left) + super::size(&node.right));
+        1 + check(&node.left).max(check(&node.right))
+    }
+
+    #[test]
+    fn split_and_merge() {
+        // Inserting in order would make an unbalanced search tree a list.
+        let mut treap = Treap::with_seed(42);
+        for i in 0..10_000u32 {
+            assert_eq!(treap.insert(i), None);
+        }
+        assert!(check(&treap.root) < 60);
+        assert_eq!(treap.insert(5000), Some(5000));
+        for i in 0..2500 {
+            assert_eq!(treap.delete(&(i * 4)), Some(i * 4));
+        }
+        assert_eq!(treap.delete(&0), None);
+        assert_eq!(treap.len(), 7500);
+        assert!(treap.member(&1) && !treap.member(&4));
+        assert_eq!((treap.first(), treap.last()), (Some(&1), Some(&9999)));
+
+        let (mut less, rest) = treap.split(&6000);
+        check(&less.root);
+        check(&rest.root);
+        assert_eq!((less.len(), rest.len()), (4500, 3000));
+        assert_eq!((less.last(), rest.first()), (Some(&5999), Some(&6001)));
+        less.merge(rest);
+        check(&less.root);
+        assert!(less.iter().cloned().eq((0..10_000).filter(|i| i % 4 != 0)));
+
+        // The same seed and insertions build the same tree.
+        let shape = |treap: &Treap<u32>| treap.root.as_ref().map(|node| (node.elem, node.priority));
+        let a: Vec<Treap<u32>> = (0..2).map(|_| {
+            let mut treap = Treap::with_seed(7);
+            treap.extend(0..100);
+            treap
+        }).collect();
+        assert_eq!(shape(&a[0]), shape(&a[1]));
+        assert!(shape(&a[0]) != shape(&(0..100).collect()));
+    }
+
+    #[test]
+    fn degenerate_shape() {
+        // Priorities falling with the elements make the treap a list down
+        // its right spine, deeper than the call stack could follow.
+        let n = 200_000u64;
+        let mut root = None;
+        for i in (0..n).rev() {
+            root = Some(Box::new(Node { elem: i, priority: n - i, size: (n - i) as usize, left: None, right: root }));
+        }
+        let mut treap = Treap { root, rng: XorShift::new(1) };
+        assert_eq!(treap.delete(&(n - 1)), Some(n - 1));
+        assert_eq!(treap.insert(n), None);
+        let (mut less, rest) = treap.split(&(n / 2));
+        assert_eq!((less.len(), rest.len()), (n as usize / 2, n as usize / 2));
+        less.merge(rest);
+        assert_eq!(less.len(), n as usize);
+        assert!(less.iter().cloned().eq((0..n - 1).chain(Some(n))));
+    }
+
+    #[test]
+    #[should_panic(expected = "merge: the treaps overlap")]
+    fn overlapping_merge() {
+        let mut a: Treap<u32> = (0..10).collect();
+        a.merge((5..15).collect());
+    }
+}